@@ -0,0 +1,42 @@
+// Compares run_batch(), which runs rows in lanes, with running the same
+// rows one at a time. `cargo bench --features simd` for the vector lanes.
+use std::{hint::black_box, time::Instant};
+
+use rust_lazy::operation::{parse, Environment, Program};
+
+const ROWS: usize = 100_000;
+
+fn time(label: &str, run: impl Fn() -> Vec<f32>) -> f64 {
+    black_box(run());
+    let start = Instant::now();
+    for _ in 0..5 {
+        black_box(run());
+    }
+    let seconds = start.elapsed().as_secs_f64() / 5.;
+    println!("{:>10}: {:8.3} ms", label, seconds * 1e3);
+    seconds
+}
+
+fn main() {
+    let program = Program::compile(&[
+        parse("(x * x + y * y) / (x - y + 3) * 0.5 + x * y * 0.25").unwrap(),
+        parse("(x < y) * x + (x >= y) * y").unwrap(),
+    ]);
+    let rows: Vec<[f32; 2]> = (0..ROWS)
+        .map(|i| [i as f32 * 0.001, 1. - i as f32 * 0.0005])
+        .collect();
+    let rows: Vec<&[f32]> = rows.iter().map(|row| &row[..]).collect();
+    let envs: Vec<Environment> = rows
+        .iter()
+        .map(|row| [("x", row[0]), ("y", row[1])].into_iter().collect())
+        .collect();
+
+    let per_row = time("per row", || {
+        let mut context = program.context();
+        envs.iter()
+            .flat_map(|env| program.run_in(&mut context, env))
+            .collect()
+    });
+    let lanes = time("lanes", || program.run_batch(black_box(&rows)));
+    println!("{:>10}: {:8.1}x", "speedup", per_row / lanes);
+}
@@ -0,0 +1,86 @@
+use crate::operation::{Environment, Program};
+
+#[derive(Clone, Debug)]
+pub struct UlpReport {
+    pub max: u64,
+    pub mean: f64,
+    // Every input whose largest per-output difference equals max.
+    pub worst_inputs: Vec<Environment>,
+    pub samples: usize,
+}
+
+impl std::fmt::Display for UlpReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "max {} ulp, mean {} ulp ({} of {} inputs at max)",
+            self.max,
+            self.mean,
+            self.worst_inputs.len(),
+            self.samples
+        )
+    }
+}
+
+// Distance between two floats in units in the last place. +0 and -0 are the
+// same value, two NaNs agree, and a NaN against anything else is maximal.
+pub fn ulp_distance(a: f32, b: f32) -> u64 {
+    if a.is_nan() || b.is_nan() {
+        return if a.is_nan() && b.is_nan() {
+            0
+        } else {
+            u64::MAX
+        };
+    }
+    let ordered = |x: f32| {
+        let bits = x.to_bits() as i32 as i64;
+        if bits < 0 {
+            i32::MIN as i64 - bits
+        } else {
+            bits
+        }
+    };
+    ordered(a).abs_diff(ordered(b))
+}
+
+// Runs both programs on every input and compares their outputs pairwise; the
+// distance for an input is the largest over its outputs.
+pub fn ulp_report(p1: &Program, p2: &Program, inputs: &[Environment]) -> UlpReport {
+    assert_eq!(
+        p1.outputs().len(),
+        p2.outputs().len(),
+        "programs have different numbers of outputs"
+    );
+    let (mut first_context, mut second_context) = (p1.context(), p2.context());
+    let mut max = 0;
+    let mut total = 0.;
+    let mut worst_inputs = Vec::new();
+    for env in inputs {
+        let first = p1.run_in(&mut first_context, env);
+        let second = p2.run_in(&mut second_context, env);
+        let distance = first
+            .iter()
+            .zip(&second)
+            .map(|(&a, &b)| ulp_distance(a, b))
+            .max()
+            .unwrap_or(0);
+        total += distance as f64;
+        if distance > max {
+            max = distance;
+            worst_inputs.clear();
+        }
+        if distance == max {
+            worst_inputs.push(env.clone());
+        }
+    }
+    UlpReport {
+        max,
+        mean: if inputs.is_empty() {
+            0.
+        } else {
+            total / inputs.len() as f64
+        },
+        worst_inputs,
+        samples: inputs.len(),
+    }
+}
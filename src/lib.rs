@@ -1 +1,4 @@
+pub mod conformance;
+pub mod monte_carlo;
 pub mod operation;
+pub mod vm;
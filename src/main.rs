@@ -1,22 +1,64 @@
-use rust_lazy::operation;
+use std::{io::Read, process::ExitCode};
 
-fn main() {
-    let scalar1 = operation::Scalar::new(1.);
-    let scalar2 = operation::Scalar::new(2.); 
-    let scalar3 = operation::Scalar::new(3.);
-    let scalar4 = operation::Scalar::new(4.);
-    let scalar5 = operation::Scalar::new(5.);
+use rust_lazy::operation::{parse, DynScalar, Environment, OpKind, Program};
 
-    let add = scalar1.add(&scalar2);
-    let sub = scalar3.sub(&scalar4);
-    let mul = scalar5.mul(&add);
+// Plain std I/O only, so the binary also builds for wasm32-wasip1 and runs
+// under any WASI runtime.
+const USAGE: &str = "usage: rust_lazy [--compile] <expression | -> [name=value ...]";
 
-    let mulop = &add * &sub;
-    let divop = &add / &mul;
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(output) => {
+            println!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: impl Iterator<Item = String>) -> Result<String, String> {
+    let mut args = args.peekable();
+    let compile = args.next_if(|arg| arg == "--compile").is_some();
+    let source = match args.next() {
+        Some(arg) if arg == "-" => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|e| e.to_string())?;
+            source
+        }
+        Some(arg) => arg,
+        None => return Err(USAGE.to_string()),
+    };
+    let expr = parse(source.trim()).map_err(|e| e.to_string())?;
 
-    let res = &mulop + &divop;
+    let mut env = Environment::new();
+    for binding in args {
+        let (name, value) = binding
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=value, got {}", binding))?;
+        let value = value
+            .parse()
+            .map_err(|_| format!("invalid value for {}: {}", name, value))?;
+        env.set(name, value);
+    }
+
+    let program = Program::compile(std::slice::from_ref(&expr));
+    if compile {
+        return Ok(program.to_string());
+    }
+    if let Some(name) = unbound(&expr, &env) {
+        return Err(format!("unbound variable {}", name));
+    }
+    Ok(program.run_with(&env)[0].to_string())
+}
 
-    println!("{}", res);
-    println!("result: {}", res.execute());
-    res.compile().iter().for_each(|x| println!("{}", x)); 
+fn unbound(expr: &DynScalar, env: &Environment) -> Option<String> {
+    match expr.kind() {
+        OpKind::Variable(name) if env.get(&name).is_none() => Some(name),
+        _ => expr.children().iter().find_map(|child| unbound(child, env)),
+    }
 }
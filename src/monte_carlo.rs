@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use crate::operation::{fnv1a, stream, uniform, Distribution, DynScalar, Program};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Estimate {
+    pub mean: f64,
+    pub variance: f64,
+    pub samples: usize,
+}
+
+impl Estimate {
+    pub fn standard_error(&self) -> f64 {
+        (self.variance / self.samples as f64).sqrt()
+    }
+
+    pub fn confidence_interval(&self, z: f64) -> (f64, f64) {
+        let half_width = z * self.standard_error();
+        (self.mean - half_width, self.mean + half_width)
+    }
+
+    pub fn confidence_interval_95(&self) -> (f64, f64) {
+        self.confidence_interval(1.96)
+    }
+}
+
+impl std::fmt::Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (low, high) = self.confidence_interval_95();
+        write!(
+            f,
+            "mean {} variance {} (95% ci [{}, {}], n = {})",
+            self.mean, self.variance, low, high, self.samples
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputDistribution {
+    Constant(f32),
+    Uniform { low: f32, high: f32 },
+    // Noise's zero-centred distributions, moved to `center`.
+    Shifted { center: f32, noise: Distribution },
+}
+
+impl InputDistribution {
+    fn sample(&self, state: &mut u64) -> f32 {
+        match *self {
+            InputDistribution::Constant(value) => value,
+            InputDistribution::Uniform { low, high } => low + (high - low) * uniform(state),
+            InputDistribution::Shifted { center, noise } => center + noise.sample(state),
+        }
+    }
+}
+
+// The distribution each variable is drawn from. Sample i of a variable
+// comes from a stream keyed by the seed, i and the variable's name, so it
+// does not depend on which other variables are configured. Noise nodes in
+// the expression draw from streams keyed by the seed as well, so estimates
+// are reproducible as long as those nodes are seeded too.
+#[derive(Clone, Debug, Default)]
+pub struct RandomVars {
+    variables: BTreeMap<String, InputDistribution>,
+    seed: u64,
+}
+
+impl RandomVars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_variable(
+        mut self,
+        name: impl Into<String>,
+        distribution: InputDistribution,
+    ) -> Self {
+        self.variables.insert(name.into(), distribution);
+        self
+    }
+
+    pub fn with_constant(self, name: impl Into<String>, value: f32) -> Self {
+        self.with_variable(name, InputDistribution::Constant(value))
+    }
+
+    pub fn with_uniform(self, name: impl Into<String>, low: f32, high: f32) -> Self {
+        assert!(low <= high, "range is empty");
+        self.with_variable(name, InputDistribution::Uniform { low, high })
+    }
+
+    pub fn with_gaussian(self, name: impl Into<String>, mean: f32, sigma: f32) -> Self {
+        self.with_variable(
+            name,
+            InputDistribution::Shifted {
+                center: mean,
+                noise: Distribution::Gaussian(sigma),
+            },
+        )
+    }
+
+    pub fn with_laplace(self, name: impl Into<String>, location: f32, scale: f32) -> Self {
+        self.with_variable(
+            name,
+            InputDistribution::Shifted {
+                center: location,
+                noise: Distribution::Laplace(scale),
+            },
+        )
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<InputDistribution> {
+        self.variables.get(name).copied()
+    }
+
+    fn sample(&self, name: &str, index: usize) -> f32 {
+        let distribution = self
+            .get(name)
+            .unwrap_or_else(|| panic!("no distribution for variable {}", name));
+        let mut state = stream(stream(self.seed, index as u64), fnv1a(name.as_bytes()));
+        distribution.sample(&mut state)
+    }
+}
+
+// Rows handed to the batch backend at a time, so memory stays bounded
+// however many samples are asked for.
+const CHUNK: usize = 4096;
+
+// Compiles the expression once and runs it on `n_samples` rows of sampled
+// inputs through the batch backend. Every variable of the expression needs
+// a distribution. Welford's update keeps the running variance stable for
+// large sample counts.
+pub fn monte_carlo(
+    expr: impl Into<DynScalar>,
+    random_vars: &RandomVars,
+    n_samples: usize,
+) -> Estimate {
+    assert!(n_samples > 1, "monte carlo needs at least two samples");
+    let program = Program::compile(&[expr.into()]);
+    let variables = program.variables();
+    let mut mean = 0.;
+    let mut m2 = 0.;
+    let mut count = 0;
+    let mut rows: Vec<f32> = Vec::new();
+    for (chunk, first) in (0..n_samples).step_by(CHUNK).enumerate() {
+        let len = CHUNK.min(n_samples - first);
+        rows.clear();
+        for index in first..first + len {
+            rows.extend(variables.iter().map(|name| random_vars.sample(name, index)));
+        }
+        let row_refs: Vec<&[f32]> = if variables.is_empty() {
+            vec![&[]; len]
+        } else {
+            rows.chunks(variables.len()).collect()
+        };
+        let seed = stream(random_vars.seed, chunk as u64);
+        for value in program.run_batch_seeded(&row_refs, seed) {
+            let value = value as f64;
+            count += 1;
+            let delta = value - mean;
+            mean += delta / count as f64;
+            m2 += delta * (value - mean);
+        }
+    }
+    Estimate {
+        mean,
+        variance: m2 / (n_samples - 1) as f64,
+        samples: n_samples,
+    }
+}
@@ -1,121 +1,683 @@
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    mem::ManuallyDrop,
+    rc::Rc,
+};
 
+mod alias;
+mod analysis;
+mod batch;
+mod bytecode;
+mod c;
+mod cache;
+mod calibrate;
+mod canonical;
+mod compare;
+mod complex;
+mod context;
+mod cost;
+mod derivative;
+mod diff;
+mod dot;
+mod dual;
+mod dynamic;
+mod energy;
+mod environment;
+mod estimate;
+mod eval;
+#[cfg(feature = "rational")]
+mod exact;
+mod frozen;
+mod function;
+#[cfg(feature = "gpu")]
+mod gpu;
 mod instruction;
+#[cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+mod jit;
+mod limits;
+mod llvm;
+mod log;
+mod memo;
+pub mod optimize;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod parse;
+mod pattern;
+#[cfg(feature = "rational")]
+mod precision;
+mod pretty;
+mod program;
+mod provenance;
+mod quantize;
+mod register;
+mod registry;
+pub mod rules;
+mod rust;
+mod select;
+#[cfg(feature = "serde")]
+mod serialize;
+mod shader;
+mod simd;
+mod stats;
+mod structure;
+mod tape;
+#[cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+mod tiered;
+mod types;
+mod visit;
+mod wasm;
 
-#[derive(Clone)]
-pub struct Scalar<O: Operation> {
-    operation: Rc<RefCell<O>>,
+pub use alias::Alias;
+pub use analysis::Dominators;
+pub use bytecode::BytecodeError;
+pub(crate) use cache::fnv1a;
+pub use cache::ArtifactCache;
+pub use calibrate::{calibrate, Histogram, HISTOGRAM_BINS};
+pub use canonical::{CanonicalId, ParseCanonicalIdError};
+pub use compare::{Compare, Comparison};
+pub use complex::{CScalar, Complex, ComplexOp, ComplexProgram};
+pub use context::GraphContext;
+pub use cost::CostModel;
+pub use diff::Divergence;
+pub use dual::Dual;
+pub use dynamic::DynScalar;
+pub use energy::{EnergyReport, TargetSpec};
+pub use environment::Environment;
+pub use eval::{EvalContext, EvalError, EvalPolicy};
+pub use frozen::FrozenExpr;
+pub use function::Function;
+#[cfg(feature = "gpu")]
+pub use gpu::{GpuError, GpuProgram};
+pub use instruction::{Instruction, Opcode, Unsupported};
+#[cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+pub use jit::{Jit, JitError};
+pub use limits::{GraphLimits, LimitError};
+pub use log::{EvalLog, LogEntry};
+pub use memo::MemoizedExpr;
+#[cfg(feature = "parallel")]
+pub use parallel::PAR_THRESHOLD;
+pub use parse::{parse, ParseError};
+pub use pattern::{Match, Wildcard};
+#[cfg(feature = "rational")]
+pub use precision::{compare_precisions, NodePrecision, PrecisionReport};
+pub use program::{Program, ProgramError};
+pub use provenance::Provenance;
+pub use quantize::{Calibration, QuantParams, QuantType, QuantizeError, QuantizedProgram};
+pub use register::{
+    CompileError, CompileOptions, LinearScan, RegisterAllocator, RegisterStrategy, Sequential,
+    Spilling,
+};
+pub use registry::{Evaluator, Registry, RegistryError, SwapOptions};
+pub use rust::Closure;
+pub use select::Select;
+pub use simd::LANES;
+pub use stats::{ExecutionStats, StatsError};
+pub use tape::Tape;
+#[cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+pub use tiered::{Tier, TieredProgram};
+pub use types::{Signature, Type, TypeError};
+pub use visit::{Postorder, Preorder, Visitor};
+pub use wasm::WasmModule;
+
+type NodeRef = Rc<RefCell<dyn Operation>>;
+
+pub struct Scalar<O: Operation + ?Sized> {
+    operation: ManuallyDrop<Rc<RefCell<O>>>,
+    // The same node behind a type-erased pointer, taken while the concrete
+    // type is still known, so graph walks can hand out children as DynScalar.
+    node: ManuallyDrop<NodeRef>,
 }
 
-impl Scalar<Constant> {
-    pub fn new(value: f32) -> Self {
+impl<O: Operation + ?Sized> Scalar<O> {
+    fn from_parts(operation: Rc<RefCell<O>>, node: NodeRef) -> Self {
         Self {
-            operation: Rc::new(RefCell::new(Constant {
-                value,
-                compile_ret: None,
-            })),
+            operation: ManuallyDrop::new(operation),
+            node: ManuallyDrop::new(node),
         }
     }
 }
 
+impl<O: Operation + ?Sized> Clone for Scalar<O> {
+    fn clone(&self) -> Self {
+        Self::from_parts(Rc::clone(&self.operation), Rc::clone(&self.node))
+    }
+}
+
 impl<O: Operation> Scalar<O> {
+    fn from_operation(operation: O) -> Self {
+        let operation = Rc::new(RefCell::new(operation));
+        Self::from_parts(operation.clone(), operation)
+    }
+}
+
+thread_local! {
+    // Nodes waiting to be freed while a graph is being dropped, or None when
+    // no drop is under way.
+    static DROPPING: RefCell<Option<Vec<NodeRef>>> = const { RefCell::new(None) };
+}
+
+// Freeing a node frees the children it held last, so a plain drop recurses
+// as deep as the graph. Instead the outermost drop frees nodes one at a
+// time from a queue, and drops that happen meanwhile only add to it.
+impl<O: Operation + ?Sized> Drop for Scalar<O> {
+    fn drop(&mut self) {
+        // SAFETY: both fields are taken exactly once, here, and never used
+        // again.
+        let (operation, node) = unsafe {
+            (
+                ManuallyDrop::take(&mut self.operation),
+                ManuallyDrop::take(&mut self.node),
+            )
+        };
+        drop(operation);
+        if Rc::strong_count(&node) > 1 {
+            return;
+        }
+        let mut node = Some(node);
+        // During thread teardown the queue may already be gone, and the node
+        // is simply dropped in place.
+        let outermost = DROPPING.try_with(|dropping| {
+            let mut dropping = dropping.borrow_mut();
+            match &mut *dropping {
+                Some(queue) => {
+                    queue.extend(node.take());
+                    false
+                }
+                None => {
+                    *dropping = Some(Vec::new());
+                    true
+                }
+            }
+        });
+        if outermost != Ok(true) {
+            return;
+        }
+        while let Some(next) = node {
+            drop(next);
+            node = DROPPING.with(|dropping| dropping.borrow_mut().as_mut().unwrap().pop());
+        }
+        DROPPING.with(|dropping| *dropping.borrow_mut() = None);
+    }
+}
+
+impl Scalar<Constant> {
+    pub fn new(value: f32) -> Self {
+        Self::from_operation(Constant {
+            value,
+            compile_ret: None,
+        })
+    }
+
+    pub fn value(&self) -> f32 {
+        self.operation.borrow().value
+    }
+
+    // Every parent sees the new value on its next execute() or compile().
+    // Programs, frozen and memoized expressions already built from the graph
+    // keep the old one.
+    pub fn set(&self, value: f32) {
+        let mut constant = self.operation.borrow_mut();
+        constant.value = value;
+        constant.compile_ret = None;
+    }
+}
+
+impl Scalar<Variable> {
+    pub fn variable(name: impl Into<String>) -> Self {
+        Self::from_operation(Variable {
+            name: name.into(),
+            compile_ret: None,
+        })
+    }
+}
+
+impl Scalar<Symbolic> {
+    pub fn pi() -> Self {
+        Self::symbol(Symbol::Pi)
+    }
+
+    pub fn e() -> Self {
+        Self::symbol(Symbol::E)
+    }
+
+    fn symbol(symbol: Symbol) -> Self {
+        Self::from_operation(Symbolic {
+            symbol,
+            compile_ret: None,
+        })
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
     pub fn execute(&self) -> f32 {
-        self.operation.borrow().execute()
+        self.execute_with(&Environment::new())
     }
 
-    pub fn compile(self) -> Vec<instruction::Instruction> 
-    {
-        let mut operand_num_iterator = (0..).into_iter();
-        match self.operation.borrow_mut().compile(&mut operand_num_iterator) {
-            CompileResult::AlreadyCompiled(_) => unreachable!(),
-            CompileResult::Compiled(instructions, _) => instructions,
+    pub fn execute_with(&self, env: &Environment) -> f32 {
+        eval::evaluate(&self.clone().into_dyn(), env, None)
+            .expect("evaluation without a policy cannot fail")
+    }
+
+    pub fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.operation.borrow().execute_dual(env, wrt)
+    }
+
+    pub fn compile(self) -> Vec<instruction::Instruction> {
+        let (instructions, _) =
+            program::compile_graph(&[self.into_dyn()], &mut RegisterAllocator::new())
+                .expect("unbounded register allocator ran out");
+        optimize::eliminate_common_subexpressions(&instructions)
+    }
+}
+
+impl<O: Operation + ?Sized> Display for Scalar<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.operation.borrow())
+    }
+}
+
+pub trait Operation: Display + 'static {
+    fn execute(&self, env: &Environment) -> f32;
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual;
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError>;
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError>;
+    fn reset_compile(&mut self);
+
+    fn kind(&self) -> OpKind;
+
+    fn children(&self) -> Vec<DynScalar> {
+        Vec::new()
+    }
+
+    fn render(&self, _children: &[String]) -> String {
+        self.to_string()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+
+    // The id a noise node's stream is keyed by under a program seed.
+    fn noise_source(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpKind {
+    Constant(f32),
+    Variable(String),
+    Symbol(Symbol),
+    Noise(Distribution, Option<u64>),
+    Wildcard(String),
+    Alias,
+    Provenance(String),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Select,
+    Compare(Comparison),
+}
+
+impl Display for OpKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpKind::Constant(value) => write!(f, "{}", value),
+            OpKind::Variable(name) => write!(f, "{}", name),
+            OpKind::Symbol(symbol) => write!(f, "{}", symbol),
+            OpKind::Noise(distribution, _) => write!(f, "noise({})", distribution),
+            OpKind::Wildcard(name) => write!(f, "?{}", name),
+            OpKind::Alias => write!(f, "alias"),
+            OpKind::Provenance(note) => write!(f, "provenance({:?})", note),
+            OpKind::Add => write!(f, "+"),
+            OpKind::Sub => write!(f, "-"),
+            OpKind::Mul => write!(f, "*"),
+            OpKind::Div => write!(f, "/"),
+            OpKind::Select => write!(f, "select"),
+            OpKind::Compare(comparison) => write!(f, "{}", comparison),
         }
     }
 }
 
-impl<O: Operation> Display for Scalar<O> {
+#[derive(Clone)]
+pub struct Constant {
+    value: f32,
+    compile_ret: Option<usize>,
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Operation for Constant {
+    fn execute(&self, _env: &Environment) -> f32 {
+        self.value
+    }
+
+    fn try_execute(&self, _env: &Environment, _policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.value)
+    }
+
+    fn execute_dual(&self, _env: &Environment, _wrt: &str) -> Dual {
+        Dual::constant(self.value)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        match self.compile_ret {
+            Some(ret) => Ok(ret),
+            None => {
+                let ret = registers.alloc()?;
+                self.compile_ret = Some(ret);
+                instructions.push(instruction::constant(self.value, ret));
+                Ok(ret)
+            }
+        }
+    }
+
+    fn reset_compile(&mut self) {
+        self.compile_ret = None;
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Constant(self.value)
+    }
+}
+
+#[derive(Clone)]
+pub struct Variable {
+    name: String,
+    compile_ret: Option<usize>,
+}
+
+impl Display for Variable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.operation.borrow().to_string())
+        write!(f, "{}", self.name)
     }
 }
 
-pub enum CompileResult {
-    AlreadyCompiled(usize),
-    Compiled(Vec<instruction::Instruction>, usize),
+impl Operation for Variable {
+    fn execute(&self, env: &Environment) -> f32 {
+        env.get(&self.name)
+            .unwrap_or_else(|| panic!("unbound variable {}", self.name))
+    }
+
+    fn try_execute(&self, env: &Environment, _policy: EvalPolicy) -> Result<f32, EvalError> {
+        env.get(&self.name)
+            .ok_or_else(|| EvalError::UnboundVariable {
+                name: self.name.clone(),
+            })
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        let value = self.execute(env);
+        if self.name == wrt {
+            Dual::variable(value)
+        } else {
+            Dual::constant(value)
+        }
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        match self.compile_ret {
+            Some(ret) => Ok(ret),
+            None => {
+                let ret = registers.alloc()?;
+                self.compile_ret = Some(ret);
+                instructions.push(instruction::load(self.name.clone(), ret));
+                Ok(ret)
+            }
+        }
+    }
+
+    fn reset_compile(&mut self) {
+        self.compile_ret = None;
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Variable(self.name.clone())
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.name.capacity()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Symbol {
+    Pi,
+    E,
 }
 
-impl CompileResult {
-    fn get_ret(&self) -> usize {
+impl Symbol {
+    pub fn value(&self) -> f64 {
         match self {
-            CompileResult::AlreadyCompiled(ret) => *ret,
-            CompileResult::Compiled(_, ret) => *ret,
+            Symbol::Pi => std::f64::consts::PI,
+            Symbol::E => std::f64::consts::E,
         }
     }
+}
 
-    fn get_instructions(&self) -> Option<Vec<instruction::Instruction>> {
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CompileResult::AlreadyCompiled(_) => None,
-            CompileResult::Compiled(instructions, _) => Some(instructions.clone()),
+            Symbol::Pi => write!(f, "π"),
+            Symbol::E => write!(f, "e"),
         }
     }
 }
 
-impl Display for CompileResult {
+#[derive(Clone)]
+pub struct Symbolic {
+    symbol: Symbol,
+    compile_ret: Option<usize>,
+}
+
+impl Display for Symbolic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol)
+    }
+}
+
+impl Operation for Symbolic {
+    fn execute(&self, _env: &Environment) -> f32 {
+        self.symbol.value() as f32
+    }
+
+    fn try_execute(&self, env: &Environment, _policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.execute(env))
+    }
+
+    fn execute_dual(&self, env: &Environment, _wrt: &str) -> Dual {
+        Dual::constant(self.execute(env))
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        match self.compile_ret {
+            Some(ret) => Ok(ret),
+            None => {
+                let ret = registers.alloc()?;
+                self.compile_ret = Some(ret);
+                instructions.push(instruction::constant(self.symbol.value() as f32, ret));
+                Ok(ret)
+            }
+        }
+    }
+
+    fn reset_compile(&mut self) {
+        self.compile_ret = None;
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Symbol(self.symbol)
+    }
+}
+
+impl Scalar<Noise> {
+    pub fn laplace_noise(scale: f32, seed: Option<u64>) -> Self {
+        Self::noise(Distribution::Laplace(scale), seed)
+    }
+
+    pub fn gaussian_noise(sigma: f32, seed: Option<u64>) -> Self {
+        Self::noise(Distribution::Gaussian(sigma), seed)
+    }
+
+    fn noise(distribution: Distribution, seed: Option<u64>) -> Self {
+        Self::from_operation(Noise {
+            distribution,
+            seed,
+            source: seed.unwrap_or_else(entropy),
+            state: Cell::new(seed.unwrap_or_else(entropy)),
+            compile_ret: None,
+        })
+    }
+}
+
+fn entropy() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Distribution {
+    Laplace(f32),
+    Gaussian(f32),
+}
+
+impl Distribution {
+    pub(crate) fn sample(&self, state: &mut u64) -> f32 {
         match self {
-            CompileResult::AlreadyCompiled(_) => write!(f, "Compiled Already"),
-            CompileResult::Compiled(instructions, ret) => {
-                let mut s = String::new();
-                for instruction in instructions {
-                    s.push_str(&format!("{}\n", instruction));
-                }
-                write!(f, "{}ret {}", s, ret)
+            Distribution::Laplace(scale) => {
+                let u = uniform(state) - 0.5;
+                -scale * u.signum() * (1. - 2. * u.abs()).ln()
+            }
+            Distribution::Gaussian(sigma) => {
+                let (u1, u2) = (uniform(state), uniform(state));
+                sigma * (-2. * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
             }
         }
     }
 }
 
-pub trait Operation: Display + Clone {
-    fn execute(&self) -> f32;
-    fn compile<I>(&mut self, operand_num_iterator: &mut I) -> CompileResult
-    where
-        I: Iterator<Item = usize>;
+// splitmix64, mapped onto the open interval (0, 1). The state only counts
+// up, so the nth draw of a stream depends on nothing but its start.
+pub(crate) fn uniform(state: &mut u64) -> f32 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    ((mix(*state) >> 40) as f32 + 0.5) / (1u64 << 24) as f32
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Where the stream of noise source `id` starts under a program seed.
+pub(crate) fn stream(seed: u64, id: u64) -> u64 {
+    mix(mix(seed) ^ id)
+}
+
+impl Display for Distribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Distribution::Laplace(scale) => write!(f, "laplace {}", scale),
+            Distribution::Gaussian(sigma) => write!(f, "gaussian {}", sigma),
+        }
+    }
 }
 
 #[derive(Clone)]
-pub struct Constant {
-    value: f32,
+pub struct Noise {
+    distribution: Distribution,
+    seed: Option<u64>,
+    // The seed, or for unseeded noise an id drawn when the node is made, so
+    // it does not depend on where the node sits in any graph.
+    source: u64,
+    state: Cell<u64>,
     compile_ret: Option<usize>,
 }
 
-impl Display for Constant {
+impl Display for Noise {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.value)
+        write!(f, "noise({})", self.distribution)
     }
 }
 
-impl Operation for Constant {
-    fn execute(&self) -> f32 {
-        self.value
+impl Operation for Noise {
+    fn execute(&self, _env: &Environment) -> f32 {
+        let mut state = self.state.get();
+        let sample = self.distribution.sample(&mut state);
+        self.state.set(state);
+        sample
     }
 
-    fn compile<I>(&mut self, operand_num_iterator: &mut I) -> CompileResult
-    where
-        I: Iterator<Item = usize>,
-    {
+    fn try_execute(&self, env: &Environment, _policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.execute(env))
+    }
+
+    fn execute_dual(&self, env: &Environment, _wrt: &str) -> Dual {
+        Dual::constant(self.execute(env))
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
         match self.compile_ret {
-            Some(ret) => CompileResult::AlreadyCompiled(ret),
+            Some(ret) => Ok(ret),
             None => {
-                let ret = operand_num_iterator.next().unwrap();
+                let ret = registers.alloc()?;
                 self.compile_ret = Some(ret);
-                CompileResult::Compiled(vec![instruction::constant(self.value, ret)], ret)
+                instructions.push(instruction::sourced_noise(
+                    self.distribution,
+                    self.seed,
+                    self.source,
+                    ret,
+                ));
+                Ok(ret)
             }
         }
     }
+
+    fn reset_compile(&mut self) {
+        self.compile_ret = None;
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Noise(self.distribution, self.seed)
+    }
+
+    fn noise_source(&self) -> Option<u64> {
+        Some(self.source)
+    }
 }
 
 #[derive(Clone)]
-pub struct Add<T: Operation, U: Operation> {
+pub struct Add<T: Operation + ?Sized, U: Operation + ?Sized> {
     a: Scalar<T>,
     b: Scalar<U>,
     compile_ret: Option<usize>,
@@ -123,8 +685,8 @@ pub struct Add<T: Operation, U: Operation> {
 
 impl<T, U> Add<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn new(a: &Scalar<T>, b: &Scalar<U>) -> Self {
         Self {
@@ -135,21 +697,19 @@ where
     }
 }
 
-impl<O: Operation> Scalar<O> {
+impl<O: Operation + ?Sized> Scalar<O> {
     pub fn add<U>(&self, other: &Scalar<U>) -> Scalar<Add<O, U>>
     where
-        U: Operation,
+        U: Operation + ?Sized,
     {
-        Scalar {
-            operation: Rc::new(RefCell::new(Add::new(self, other))),
-        }
+        Scalar::from_operation(Add::new(self, other))
     }
 }
 
 impl<T, U> std::ops::Add<&Scalar<U>> for &Scalar<T>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     type Output = Scalar<Add<T, U>>;
 
@@ -160,8 +720,8 @@ where
 
 impl<T, U> Display for Add<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({} + {})", self.a, self.b)
@@ -170,36 +730,71 @@ where
 
 impl<T, U> Operation for Add<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
-    fn execute(&self) -> f32 {
-        self.a.operation.borrow().execute() + self.b.operation.borrow().execute()
+    fn execute(&self, env: &Environment) -> f32 {
+        self.a.operation.borrow().execute(env) + self.b.operation.borrow().execute(env)
     }
 
-    fn compile<I>(&mut self, operand_num_iterator: &mut I) -> CompileResult
-    where
-        I: Iterator<Item = usize>,
-    {
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.a.operation.borrow().try_execute(env, policy)?
+            + self.b.operation.borrow().try_execute(env, policy)?)
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.a.operation.borrow().execute_dual(env, wrt)
+            + self.b.operation.borrow().execute_dual(env, wrt)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
         match self.compile_ret {
-            Some(ret) => CompileResult::AlreadyCompiled(ret),
+            Some(ret) => Ok(ret),
             None => {
-                let a = self.a.operation.borrow_mut().compile(operand_num_iterator);
-                let b = self.b.operation.borrow_mut().compile(operand_num_iterator);
-                let mut instructions = Vec::new();
-                a.get_instructions().map(|i| instructions.extend(i));
-                b.get_instructions().map(|i| instructions.extend(i));
-                let ret = operand_num_iterator.next().unwrap();
+                let a = self
+                    .a
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let b = self
+                    .b
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let ret = registers.alloc()?;
                 self.compile_ret = Some(ret);
-                instructions.push(instruction::add(a.get_ret(), b.get_ret(), ret));
-                CompileResult::Compiled(instructions, ret)
+                instructions.push(instruction::add(a, b, ret));
+                Ok(ret)
             }
         }
     }
+
+    fn reset_compile(&mut self) {
+        if self.compile_ret.take().is_some() {
+            self.a.operation.borrow_mut().reset_compile();
+            self.b.operation.borrow_mut().reset_compile();
+        }
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Add
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.a.clone().into_dyn(), self.b.clone().into_dyn()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        format!("({} + {})", children[0], children[1])
+    }
 }
 
 #[derive(Clone)]
-pub struct Sub<T: Operation, U: Operation> {
+pub struct Sub<T: Operation + ?Sized, U: Operation + ?Sized> {
     a: Scalar<T>,
     b: Scalar<U>,
     compile_ret: Option<usize>,
@@ -207,8 +802,8 @@ pub struct Sub<T: Operation, U: Operation> {
 
 impl<T, U> Sub<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn new(a: &Scalar<T>, b: &Scalar<U>) -> Self {
         Self {
@@ -219,21 +814,19 @@ where
     }
 }
 
-impl<O: Operation> Scalar<O> {
+impl<O: Operation + ?Sized> Scalar<O> {
     pub fn sub<U>(&self, other: &Scalar<U>) -> Scalar<Sub<O, U>>
     where
-        U: Operation,
+        U: Operation + ?Sized,
     {
-        Scalar {
-            operation: Rc::new(RefCell::new(Sub::new(self, other))),
-        }
+        Scalar::from_operation(Sub::new(self, other))
     }
 }
 
 impl<T, U> std::ops::Sub<&Scalar<U>> for &Scalar<T>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     type Output = Scalar<Sub<T, U>>;
 
@@ -244,8 +837,8 @@ where
 
 impl<T, U> Display for Sub<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({} - {})", self.a, self.b)
@@ -254,36 +847,71 @@ where
 
 impl<T, U> Operation for Sub<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
-    fn execute(&self) -> f32 {
-        self.a.operation.borrow().execute() - self.b.operation.borrow().execute()
+    fn execute(&self, env: &Environment) -> f32 {
+        self.a.operation.borrow().execute(env) - self.b.operation.borrow().execute(env)
     }
 
-    fn compile<I>(&mut self, operand_num_iterator: &mut I) -> CompileResult
-    where
-        I: Iterator<Item = usize>,
-    {
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.a.operation.borrow().try_execute(env, policy)?
+            - self.b.operation.borrow().try_execute(env, policy)?)
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.a.operation.borrow().execute_dual(env, wrt)
+            - self.b.operation.borrow().execute_dual(env, wrt)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
         match self.compile_ret {
-            Some(ret) => CompileResult::AlreadyCompiled(ret),
+            Some(ret) => Ok(ret),
             None => {
-                let a = self.a.operation.borrow_mut().compile(operand_num_iterator);
-                let b = self.b.operation.borrow_mut().compile(operand_num_iterator);
-                let mut instructions = Vec::new();
-                a.get_instructions().map(|i| instructions.extend(i));
-                b.get_instructions().map(|i| instructions.extend(i));
-                let ret = operand_num_iterator.next().unwrap();
+                let a = self
+                    .a
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let b = self
+                    .b
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let ret = registers.alloc()?;
                 self.compile_ret = Some(ret);
-                instructions.push(instruction::sub(a.get_ret(), b.get_ret(), ret));
-                CompileResult::Compiled(instructions, ret)
+                instructions.push(instruction::sub(a, b, ret));
+                Ok(ret)
             }
         }
     }
+
+    fn reset_compile(&mut self) {
+        if self.compile_ret.take().is_some() {
+            self.a.operation.borrow_mut().reset_compile();
+            self.b.operation.borrow_mut().reset_compile();
+        }
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Sub
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.a.clone().into_dyn(), self.b.clone().into_dyn()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        format!("({} - {})", children[0], children[1])
+    }
 }
 
 #[derive(Clone)]
-pub struct Mul<T: Operation, U: Operation> {
+pub struct Mul<T: Operation + ?Sized, U: Operation + ?Sized> {
     a: Scalar<T>,
     b: Scalar<U>,
     compile_ret: Option<usize>,
@@ -291,8 +919,8 @@ pub struct Mul<T: Operation, U: Operation> {
 
 impl<T, U> Mul<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn new(a: &Scalar<T>, b: &Scalar<U>) -> Self {
         Self {
@@ -303,21 +931,19 @@ where
     }
 }
 
-impl<O: Operation> Scalar<O> {
+impl<O: Operation + ?Sized> Scalar<O> {
     pub fn mul<U>(&self, other: &Scalar<U>) -> Scalar<Mul<O, U>>
     where
-        U: Operation,
+        U: Operation + ?Sized,
     {
-        Scalar {
-            operation: Rc::new(RefCell::new(Mul::new(self, other))),
-        }
+        Scalar::from_operation(Mul::new(self, other))
     }
 }
 
 impl<T, U> std::ops::Mul<&Scalar<U>> for &Scalar<T>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     type Output = Scalar<Mul<T, U>>;
 
@@ -328,8 +954,8 @@ where
 
 impl<T, U> Display for Mul<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({} * {})", self.a, self.b)
@@ -338,36 +964,71 @@ where
 
 impl<T, U> Operation for Mul<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
-    fn execute(&self) -> f32 {
-        self.a.operation.borrow().execute() * self.b.operation.borrow().execute()
+    fn execute(&self, env: &Environment) -> f32 {
+        self.a.operation.borrow().execute(env) * self.b.operation.borrow().execute(env)
     }
 
-    fn compile<I>(&mut self, operand_num_iterator: &mut I) -> CompileResult
-    where
-        I: Iterator<Item = usize>,
-    {
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.a.operation.borrow().try_execute(env, policy)?
+            * self.b.operation.borrow().try_execute(env, policy)?)
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.a.operation.borrow().execute_dual(env, wrt)
+            * self.b.operation.borrow().execute_dual(env, wrt)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
         match self.compile_ret {
-            Some(ret) => CompileResult::AlreadyCompiled(ret),
+            Some(ret) => Ok(ret),
             None => {
-                let a = self.a.operation.borrow_mut().compile(operand_num_iterator);
-                let b = self.b.operation.borrow_mut().compile(operand_num_iterator);
-                let mut instructions = Vec::new();
-                a.get_instructions().map(|i| instructions.extend(i));
-                b.get_instructions().map(|i| instructions.extend(i));
-                let ret = operand_num_iterator.next().unwrap();
+                let a = self
+                    .a
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let b = self
+                    .b
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let ret = registers.alloc()?;
                 self.compile_ret = Some(ret);
-                instructions.push(instruction::mul(a.get_ret(), b.get_ret(), ret));
-                CompileResult::Compiled(instructions, ret)
+                instructions.push(instruction::mul(a, b, ret));
+                Ok(ret)
             }
         }
     }
+
+    fn reset_compile(&mut self) {
+        if self.compile_ret.take().is_some() {
+            self.a.operation.borrow_mut().reset_compile();
+            self.b.operation.borrow_mut().reset_compile();
+        }
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Mul
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.a.clone().into_dyn(), self.b.clone().into_dyn()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        format!("({} * {})", children[0], children[1])
+    }
 }
 
 #[derive(Clone)]
-pub struct Div<T: Operation, U: Operation> {
+pub struct Div<T: Operation + ?Sized, U: Operation + ?Sized> {
     a: Scalar<T>,
     b: Scalar<U>,
     compile_ret: Option<usize>,
@@ -375,8 +1036,8 @@ pub struct Div<T: Operation, U: Operation> {
 
 impl<T, U> Div<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn new(a: &Scalar<T>, b: &Scalar<U>) -> Self {
         Self {
@@ -387,21 +1048,19 @@ where
     }
 }
 
-impl<O: Operation> Scalar<O> {
+impl<O: Operation + ?Sized> Scalar<O> {
     pub fn div<U>(&self, other: &Scalar<U>) -> Scalar<Div<O, U>>
     where
-        U: Operation,
+        U: Operation + ?Sized,
     {
-        Scalar {
-            operation: Rc::new(RefCell::new(Div::new(self, other))),
-        }
+        Scalar::from_operation(Div::new(self, other))
     }
 }
 
 impl<T, U> std::ops::Div<&Scalar<U>> for &Scalar<T>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     type Output = Scalar<Div<T, U>>;
 
@@ -412,8 +1071,8 @@ where
 
 impl<T, U> Display for Div<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({} / {})", self.a, self.b)
@@ -422,30 +1081,68 @@ where
 
 impl<T, U> Operation for Div<T, U>
 where
-    T: Operation,
-    U: Operation,
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
 {
-    fn execute(&self) -> f32 {
-        self.a.operation.borrow().execute() / self.b.operation.borrow().execute()
+    fn execute(&self, env: &Environment) -> f32 {
+        self.a.operation.borrow().execute(env) / self.b.operation.borrow().execute(env)
     }
 
-    fn compile<I>(&mut self, operand_num_iterator: &mut I) -> CompileResult
-    where
-        I: Iterator<Item = usize>,
-    {
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        policy.divide(
+            self.a.operation.borrow().try_execute(env, policy)?,
+            self.b.operation.borrow().try_execute(env, policy)?,
+            || self.to_string(),
+        )
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.a.operation.borrow().execute_dual(env, wrt)
+            / self.b.operation.borrow().execute_dual(env, wrt)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
         match self.compile_ret {
-            Some(ret) => CompileResult::AlreadyCompiled(ret),
+            Some(ret) => Ok(ret),
             None => {
-                let a = self.a.operation.borrow_mut().compile(operand_num_iterator);
-                let b = self.b.operation.borrow_mut().compile(operand_num_iterator);
-                let mut instructions = Vec::new();
-                a.get_instructions().map(|i| instructions.extend(i));
-                b.get_instructions().map(|i| instructions.extend(i));
-                let ret = operand_num_iterator.next().unwrap();
+                let a = self
+                    .a
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let b = self
+                    .b
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let ret = registers.alloc()?;
                 self.compile_ret = Some(ret);
-                instructions.push(instruction::div(a.get_ret(), b.get_ret(), ret));
-                CompileResult::Compiled(instructions, ret)
+                instructions.push(instruction::div(a, b, ret));
+                Ok(ret)
             }
         }
     }
+
+    fn reset_compile(&mut self) {
+        if self.compile_ret.take().is_some() {
+            self.a.operation.borrow_mut().reset_compile();
+            self.b.operation.borrow_mut().reset_compile();
+        }
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Div
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.a.clone().into_dyn(), self.b.clone().into_dyn()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        format!("({} / {})", children[0], children[1])
+    }
 }
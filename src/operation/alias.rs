@@ -0,0 +1,87 @@
+use std::fmt::Display;
+
+use super::{
+    analysis::postorder, instruction, CompileError, Dual, DynScalar, Environment, EvalError,
+    EvalPolicy, OpKind, Operation, RegisterAllocator, Scalar,
+};
+
+// Forwards its target's value. Parents attach to the alias, so retargeting
+// it rewires every one of them at once. It compiles to no instruction of its
+// own.
+pub struct Alias {
+    target: DynScalar,
+}
+
+impl Scalar<Alias> {
+    pub fn alias(target: impl Into<DynScalar>) -> Self {
+        Self::from_operation(Alias {
+            target: target.into(),
+        })
+    }
+
+    pub fn target(&self) -> DynScalar {
+        self.operation.borrow().target.clone()
+    }
+
+    pub fn set_target(&self, target: impl Into<DynScalar>) {
+        let target = target.into();
+        let id = self.clone().into_dyn().id();
+        assert!(
+            postorder(&target).iter().all(|node| node.id() != id),
+            "alias cannot point into its own graph"
+        );
+        self.operation.borrow_mut().target = target;
+    }
+}
+
+impl Display for Alias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.target)
+    }
+}
+
+impl Operation for Alias {
+    fn execute(&self, env: &Environment) -> f32 {
+        self.target.scalar.operation.borrow().execute(env)
+    }
+
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        self.target
+            .scalar
+            .operation
+            .borrow()
+            .try_execute(env, policy)
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.target.scalar.operation.borrow().execute_dual(env, wrt)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        self.target
+            .scalar
+            .operation
+            .borrow_mut()
+            .compile(registers, instructions)
+    }
+
+    fn reset_compile(&mut self) {
+        self.target.scalar.operation.borrow_mut().reset_compile();
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Alias
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.target.clone()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        children[0].clone()
+    }
+}
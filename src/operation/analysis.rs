@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use super::{DynScalar, Operation, Scalar};
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn critical_path(&self) -> Vec<DynScalar> {
+        self.clone().into_dyn().critical_path()
+    }
+
+    pub fn dominators(&self) -> Dominators {
+        self.clone().into_dyn().dominators()
+    }
+}
+
+impl DynScalar {
+    // The longest chain of dependencies, from this node down to a leaf.
+    pub fn critical_path(&self) -> Vec<DynScalar> {
+        let order = postorder(self);
+        let mut heights: HashMap<usize, usize> = HashMap::new();
+        for node in &order {
+            let height = node
+                .children()
+                .iter()
+                .map(|child| heights[&child.id()])
+                .max()
+                .unwrap_or(0);
+            heights.insert(node.id(), height + 1);
+        }
+
+        let mut path = vec![self.clone()];
+        while let Some(next) = path
+            .last()
+            .unwrap()
+            .children()
+            .into_iter()
+            .max_by_key(|child| heights[&child.id()])
+        {
+            path.push(next);
+        }
+        path
+    }
+
+    pub fn dominators(&self) -> Dominators {
+        Dominators::new(self)
+    }
+}
+
+// Dominance over the use edges from the root: `a` dominates `b` when every
+// path from the root down to `b` passes through `a`, so `b` is only needed
+// to compute `a`.
+pub struct Dominators {
+    nodes: Vec<DynScalar>,
+    index: HashMap<usize, usize>,
+    idom: Vec<usize>,
+}
+
+impl Dominators {
+    // Cooper, Harvey and Kennedy's iterative algorithm over postorder numbers;
+    // the root is last in postorder.
+    fn new(root: &DynScalar) -> Self {
+        let nodes = postorder(root);
+        let index: HashMap<usize, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id(), i))
+            .collect();
+        let mut parents = vec![Vec::new(); nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            for child in node.children() {
+                parents[index[&child.id()]].push(i);
+            }
+        }
+
+        let root = nodes.len() - 1;
+        let mut idom = vec![usize::MAX; nodes.len()];
+        idom[root] = root;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for node in (0..root).rev() {
+                let mut processed = parents[node].iter().filter(|&&p| idom[p] != usize::MAX);
+                let mut new_idom = *processed.next().unwrap();
+                for &parent in processed {
+                    new_idom = intersect(&idom, parent, new_idom);
+                }
+                if idom[node] != new_idom {
+                    idom[node] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        Self { nodes, index, idom }
+    }
+
+    pub fn immediate_dominator(&self, node: &DynScalar) -> Option<DynScalar> {
+        let i = *self.index.get(&node.id())?;
+        let idom = self.idom[i];
+        (idom != i).then(|| self.nodes[idom].clone())
+    }
+
+    pub fn dominates(&self, a: &DynScalar, b: &DynScalar) -> bool {
+        let (Some(&a), Some(&(mut b))) = (self.index.get(&a.id()), self.index.get(&b.id())) else {
+            return false;
+        };
+        loop {
+            if a == b {
+                return true;
+            }
+            if self.idom[b] == b {
+                return false;
+            }
+            b = self.idom[b];
+        }
+    }
+}
+
+fn intersect(idom: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while a < b {
+            a = idom[a];
+        }
+        while b < a {
+            b = idom[b];
+        }
+    }
+    a
+}
+
+pub(super) fn postorder(root: &DynScalar) -> Vec<DynScalar> {
+    root.postorder().collect()
+}
@@ -0,0 +1,85 @@
+use super::{
+    simd::{LaneProgram, Lanes, LANES},
+    DynScalar, EvalContext, Operation, Program, Scalar,
+};
+
+impl Program {
+    // Runs the program on each row, with columns in the order of
+    // variables(), and returns the outputs one row after another. Rows go
+    // through the program LANES at a time, which gives the same results as
+    // running them one by one. Noise carries on from row to row.
+    pub fn run_batch(&self, rows: &[&[f32]]) -> Vec<f32> {
+        let lanes = LaneProgram::new(self);
+        let mut context = self.context();
+        let mut registers = Vec::new();
+        let mut results = Vec::with_capacity(rows.len() * self.outputs().len());
+        for chunk in rows.chunks(LANES) {
+            lanes.run(
+                &mut registers,
+                chunk,
+                |source, _, distribution| distribution.sample(&mut context.noise[source]),
+                &mut results,
+            );
+        }
+        results
+    }
+
+    // As run_batch(), but each row draws its noise from streams keyed by
+    // `seed` and the row's index, so a row's results don't depend on which
+    // rows ran before it. Parallel runs give the same results.
+    pub fn run_batch_seeded(&self, rows: &[&[f32]], seed: u64) -> Vec<f32> {
+        let lanes = LaneProgram::new(self);
+        let mut registers = Vec::new();
+        let mut results = Vec::with_capacity(rows.len() * self.outputs().len());
+        for (index, chunk) in rows.chunks(LANES).enumerate() {
+            self.run_chunk_seeded(
+                &lanes,
+                &mut registers,
+                chunk,
+                index * LANES,
+                seed,
+                &mut results,
+            );
+        }
+        results
+    }
+
+    // Runs up to LANES rows, the first of them row `first` of the batch.
+    pub(super) fn run_chunk_seeded(
+        &self,
+        lanes: &LaneProgram,
+        registers: &mut Vec<Lanes>,
+        chunk: &[&[f32]],
+        first: usize,
+        seed: u64,
+        results: &mut Vec<f32>,
+    ) {
+        let mut contexts: Vec<EvalContext> = (0..chunk.len())
+            .map(|lane| self.row_context(seed, first + lane))
+            .collect();
+        lanes.run(
+            registers,
+            chunk,
+            |source, lane, distribution| distribution.sample(&mut contexts[lane].noise[source]),
+            results,
+        );
+    }
+
+    pub(super) fn row_context(&self, seed: u64, row: usize) -> EvalContext {
+        self.context_with_seed(super::stream(seed, row as u64))
+    }
+}
+
+impl DynScalar {
+    // Compiles once and runs every row; columns are in the order of the
+    // compiled program's variables(), that is, of first use.
+    pub fn execute_batch(&self, rows: &[&[f32]]) -> Vec<f32> {
+        Program::compile(std::slice::from_ref(self)).run_batch(rows)
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn execute_batch(&self, rows: &[&[f32]]) -> Vec<f32> {
+        self.clone().into_dyn().execute_batch(rows)
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use super::{
+    cache::fnv1a,
+    instruction::{Instruction, Opcode},
+    Comparison, Distribution, Program,
+};
+
+// Layout, all integers LEB128 unless noted:
+//
+//   "LZBC" version:u8
+//   constant pool: count, f32 little endian each
+//   name table: count, (length, utf-8 bytes) each
+//   instructions: count, (opcode:u8, operands..., ret) each
+//   outputs: count, register each
+//   provenance (version 5): count, (instruction, length, utf-8 bytes) each
+//
+// Constants, noise scales and variable names are referenced by index into
+// the pool and name table.
+const MAGIC: &[u8; 4] = b"LZBC";
+// Version 2 added store and reload, version 3 select, version 4 the
+// comparisons, version 5 provenance. Programs are written
+// with the oldest version that has every opcode they use, so older readers
+// can still load them.
+const VERSION: u8 = 5;
+const PROVENANCE: u8 = 5;
+
+const CONSTANT: u8 = 0;
+const LOAD: u8 = 1;
+const LAPLACE: u8 = 2;
+const GAUSSIAN: u8 = 3;
+const ADD: u8 = 4;
+const SUB: u8 = 5;
+const MUL: u8 = 6;
+const DIV: u8 = 7;
+const STORE: u8 = 8;
+const RELOAD: u8 = 9;
+const SELECT: u8 = 10;
+const EQ: u8 = 11;
+const NE: u8 = 12;
+const LT: u8 = 13;
+const LE: u8 = 14;
+const GT: u8 = 15;
+const GE: u8 = 16;
+
+fn introduced(opcode: u8) -> u8 {
+    match opcode {
+        STORE | RELOAD => 2,
+        SELECT => 3,
+        EQ..=GE => 4,
+        _ => 1,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BytecodeError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidOpcode(u8),
+    InvalidIndex,
+    InvalidRegister,
+    InvalidName,
+}
+
+impl std::fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BytecodeError::BadMagic => write!(f, "not a bytecode program"),
+            BytecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported bytecode version {}", version)
+            }
+            BytecodeError::Truncated => write!(f, "bytecode ends unexpectedly"),
+            BytecodeError::InvalidOpcode(opcode) => write!(f, "invalid opcode {}", opcode),
+            BytecodeError::InvalidIndex => write!(f, "constant or name index out of range"),
+            BytecodeError::InvalidRegister => {
+                write!(f, "register out of range or read before it is written")
+            }
+            BytecodeError::InvalidName => write!(f, "name or provenance is not utf-8"),
+        }
+    }
+}
+
+impl std::error::Error for BytecodeError {}
+
+impl Program {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut constants = Pool::default();
+        let mut names: Vec<String> = Vec::new();
+        let mut code = Vec::new();
+        let mut version = 1;
+        for instruction in self.instructions() {
+            let start = code.len();
+            match instruction.opcode() {
+                Opcode::Constant(value) => {
+                    code.push(CONSTANT);
+                    write_varint(&mut code, constants.index(value));
+                }
+                Opcode::Load(name) => {
+                    let index = match names.iter().position(|n| *n == name) {
+                        Some(index) => index,
+                        None => {
+                            names.push(name);
+                            names.len() - 1
+                        }
+                    };
+                    code.push(LOAD);
+                    write_varint(&mut code, index as u64);
+                }
+                Opcode::Noise(distribution, seed) => {
+                    let (opcode, scale) = match distribution {
+                        Distribution::Laplace(scale) => (LAPLACE, scale),
+                        Distribution::Gaussian(sigma) => (GAUSSIAN, sigma),
+                    };
+                    code.push(opcode);
+                    write_varint(&mut code, constants.index(scale));
+                    // 0 for entropy, seed + 1 otherwise.
+                    write_varint(&mut code, seed.map_or(0, |seed| seed as u128 + 1));
+                }
+                Opcode::Add(a, b) => binary(&mut code, ADD, a, b),
+                Opcode::Sub(a, b) => binary(&mut code, SUB, a, b),
+                Opcode::Mul(a, b) => binary(&mut code, MUL, a, b),
+                Opcode::Div(a, b) => binary(&mut code, DIV, a, b),
+                Opcode::Store(a) => {
+                    code.push(STORE);
+                    write_varint(&mut code, a as u64);
+                }
+                Opcode::Reload(a) => {
+                    code.push(RELOAD);
+                    write_varint(&mut code, a as u64);
+                }
+                Opcode::Select(c, a, b) => {
+                    code.push(SELECT);
+                    for operand in [c, a, b] {
+                        write_varint(&mut code, operand as u64);
+                    }
+                }
+                Opcode::Compare(comparison, a, b) => {
+                    let opcode = match comparison {
+                        Comparison::Eq => EQ,
+                        Comparison::Ne => NE,
+                        Comparison::Lt => LT,
+                        Comparison::Le => LE,
+                        Comparison::Gt => GT,
+                        Comparison::Ge => GE,
+                    };
+                    binary(&mut code, opcode, a, b)
+                }
+            }
+            version = version.max(introduced(code[start]));
+            write_varint(&mut code, instruction.ret() as u64);
+        }
+
+        let notes: Vec<(usize, &str)> = self
+            .instructions()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| Some((i, instruction.provenance()?)))
+            .collect();
+        if !notes.is_empty() {
+            version = PROVENANCE;
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(version);
+        write_varint(&mut bytes, constants.values.len() as u64);
+        for value in &constants.values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        write_varint(&mut bytes, names.len() as u64);
+        for name in &names {
+            write_varint(&mut bytes, name.len() as u64);
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        write_varint(&mut bytes, self.instructions().len() as u64);
+        bytes.extend(code);
+        write_varint(&mut bytes, self.outputs().len() as u64);
+        for &output in self.outputs() {
+            write_varint(&mut bytes, output as u64);
+        }
+        if version >= PROVENANCE {
+            write_varint(&mut bytes, notes.len() as u64);
+            for (i, note) in notes {
+                write_varint(&mut bytes, i as u64);
+                write_varint(&mut bytes, note.len() as u64);
+                bytes.extend_from_slice(note.as_bytes());
+            }
+        }
+        bytes
+    }
+
+    // FNV-1a over the encoding, so it is the same in every process and
+    // build and can key anything kept outside of one.
+    pub fn fingerprint(&self) -> u64 {
+        fnv1a(&self.to_bytes())
+    }
+
+    // Rejects anything that could not have come from to_bytes(), including
+    // programs that read a register before writing it, so a loaded program
+    // is always safe to run.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, BytecodeError> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(BytecodeError::BadMagic);
+        }
+        let version = reader.take(1)?[0];
+        if !(1..=VERSION).contains(&version) {
+            return Err(BytecodeError::UnsupportedVersion(version));
+        }
+
+        let constants = (0..reader.count()?)
+            .map(|_| Ok(f32::from_le_bytes(reader.take(4)?.try_into().unwrap())))
+            .collect::<Result<Vec<f32>, _>>()?;
+        let names = (0..reader.count()?)
+            .map(|_| reader.string())
+            .collect::<Result<Vec<String>, _>>()?;
+        let constant = |index: usize| constants.get(index).copied();
+
+        // Register numbers may be sparse, as after folding or in programs
+        // built with Program::new(); which ones are read before they are
+        // written is checked once the whole program is read.
+        let mut instructions = Vec::new();
+        for _ in 0..reader.count()? {
+            let opcode = reader.take(1)?[0];
+            let operand = |reader: &mut Reader| reader.count();
+            if introduced(opcode) > version {
+                return Err(BytecodeError::InvalidOpcode(opcode));
+            }
+            let opcode = match opcode {
+                CONSTANT => {
+                    Opcode::Constant(constant(reader.count()?).ok_or(BytecodeError::InvalidIndex)?)
+                }
+                LOAD => Opcode::Load(
+                    names
+                        .get(reader.count()?)
+                        .ok_or(BytecodeError::InvalidIndex)?
+                        .clone(),
+                ),
+                LAPLACE | GAUSSIAN => {
+                    let scale = constant(reader.count()?).ok_or(BytecodeError::InvalidIndex)?;
+                    let distribution = if opcode == LAPLACE {
+                        Distribution::Laplace(scale)
+                    } else {
+                        Distribution::Gaussian(scale)
+                    };
+                    let seed = match reader.varint()? {
+                        0 => None,
+                        seed => {
+                            Some(u64::try_from(seed - 1).map_err(|_| BytecodeError::InvalidIndex)?)
+                        }
+                    };
+                    Opcode::Noise(distribution, seed)
+                }
+                ADD => Opcode::Add(operand(&mut reader)?, operand(&mut reader)?),
+                SUB => Opcode::Sub(operand(&mut reader)?, operand(&mut reader)?),
+                MUL => Opcode::Mul(operand(&mut reader)?, operand(&mut reader)?),
+                DIV => Opcode::Div(operand(&mut reader)?, operand(&mut reader)?),
+                STORE => Opcode::Store(operand(&mut reader)?),
+                RELOAD => Opcode::Reload(operand(&mut reader)?),
+                SELECT => Opcode::Select(
+                    operand(&mut reader)?,
+                    operand(&mut reader)?,
+                    operand(&mut reader)?,
+                ),
+                EQ..=GE => {
+                    let comparison = match opcode {
+                        EQ => Comparison::Eq,
+                        NE => Comparison::Ne,
+                        LT => Comparison::Lt,
+                        LE => Comparison::Le,
+                        GT => Comparison::Gt,
+                        _ => Comparison::Ge,
+                    };
+                    Opcode::Compare(comparison, operand(&mut reader)?, operand(&mut reader)?)
+                }
+                opcode => return Err(BytecodeError::InvalidOpcode(opcode)),
+            };
+            instructions.push(Instruction::from_opcode(opcode, reader.count()?));
+        }
+
+        let outputs = (0..reader.count()?)
+            .map(|_| reader.count())
+            .collect::<Result<Vec<usize>, _>>()?;
+        if version >= PROVENANCE {
+            for _ in 0..reader.count()? {
+                let instruction = instructions
+                    .get_mut(reader.count()?)
+                    .ok_or(BytecodeError::InvalidIndex)?;
+                instruction.annotate(&reader.string()?);
+            }
+        }
+        Program::new(instructions, outputs).map_err(|_| BytecodeError::InvalidRegister)
+    }
+}
+
+#[derive(Default)]
+struct Pool {
+    values: Vec<f32>,
+    indices: HashMap<u32, u64>,
+}
+
+impl Pool {
+    fn index(&mut self, value: f32) -> u64 {
+        let values = &mut self.values;
+        *self.indices.entry(value.to_bits()).or_insert_with(|| {
+            values.push(value);
+            values.len() as u64 - 1
+        })
+    }
+}
+
+fn binary(code: &mut Vec<u8>, opcode: u8, a: usize, b: usize) {
+    code.push(opcode);
+    write_varint(code, a as u64);
+    write_varint(code, b as u64);
+}
+
+fn write_varint(bytes: &mut Vec<u8>, value: impl Into<u128>) {
+    let mut value = value.into();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BytecodeError> {
+        if self.bytes.len() < len {
+            return Err(BytecodeError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn varint(&mut self) -> Result<u128, BytecodeError> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BytecodeError::InvalidIndex)
+    }
+
+    fn string(&mut self) -> Result<String, BytecodeError> {
+        let len = self.count()?;
+        let string =
+            std::str::from_utf8(self.take(len)?).map_err(|_| BytecodeError::InvalidName)?;
+        Ok(string.to_string())
+    }
+
+    // Counts, lengths and indices, which must fit in memory.
+    fn count(&mut self) -> Result<usize, BytecodeError> {
+        usize::try_from(self.varint()?).map_err(|_| BytecodeError::InvalidIndex)
+    }
+}
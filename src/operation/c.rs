@@ -0,0 +1,87 @@
+use std::fmt::Write;
+
+use super::{
+    instruction::{Opcode, Unsupported},
+    shader::is_parameter,
+    Program,
+};
+
+impl Program {
+    // A single output is returned; several are written to the trailing
+    // `out` array instead.
+    pub fn to_c(&self) -> Result<String, Unsupported> {
+        let mut parameters: Vec<String> = Vec::new();
+        let mut needs_math = false;
+        let mut body = String::new();
+        for instruction in self.instructions() {
+            let value = match instruction.opcode() {
+                Opcode::Constant(value) if value.is_nan() => {
+                    needs_math = true;
+                    "NAN".to_string()
+                }
+                Opcode::Constant(value) if value.is_infinite() => {
+                    needs_math = true;
+                    if value > 0. { "INFINITY" } else { "-INFINITY" }.to_string()
+                }
+                Opcode::Constant(value) => format!("{:?}f", value),
+                Opcode::Load(name) if is_parameter(&name) && name != "out" => {
+                    if !parameters.contains(&name) {
+                        parameters.push(name.clone());
+                    }
+                    name
+                }
+                Opcode::Load(_) | Opcode::Noise(..) => {
+                    return Err(Unsupported {
+                        instruction: instruction.to_string(),
+                    })
+                }
+                Opcode::Add(a, b) => format!("t{} + t{}", a, b),
+                Opcode::Sub(a, b) => format!("t{} - t{}", a, b),
+                Opcode::Mul(a, b) => format!("t{} * t{}", a, b),
+                Opcode::Div(a, b) => format!("t{} / t{}", a, b),
+                Opcode::Store(a) | Opcode::Reload(a) => format!("t{}", a),
+                Opcode::Select(c, a, b) => format!("t{} != 0.0f ? t{} : t{}", c, a, b),
+                Opcode::Compare(comparison, a, b) => {
+                    format!("(float)(t{} {} t{})", a, comparison, b)
+                }
+            };
+            writeln!(body, "    t{} = {};", instruction.ret(), value).unwrap();
+        }
+
+        let mut parameters: Vec<String> = parameters
+            .iter()
+            .map(|name| format!("float {}", name))
+            .collect();
+        let outputs = self.outputs();
+        let ty = if outputs.len() == 1 {
+            writeln!(body, "    return t{};", outputs[0]).unwrap();
+            "float"
+        } else {
+            if !outputs.is_empty() {
+                parameters.push(format!("float out[{}]", outputs.len()));
+            }
+            for (i, ret) in outputs.iter().enumerate() {
+                writeln!(body, "    out[{}] = t{};", i, ret).unwrap();
+            }
+            "void"
+        };
+        if parameters.is_empty() {
+            parameters.push("void".to_string());
+        }
+        let registers: Vec<String> = (0..self.register_count())
+            .map(|ret| format!("t{}", ret))
+            .collect();
+
+        let mut source = String::new();
+        if needs_math {
+            source.push_str("#include <math.h>\n\n");
+        }
+        writeln!(source, "{} eval({}) {{", ty, parameters.join(", ")).unwrap();
+        if !registers.is_empty() {
+            writeln!(source, "    float {};", registers.join(", ")).unwrap();
+        }
+        source.push_str(&body);
+        source.push_str("}\n");
+        Ok(source)
+    }
+}
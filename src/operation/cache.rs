@@ -0,0 +1,90 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::Program;
+
+// Compiled artifacts on disk, one file per program fingerprint, target and
+// kind of artifact. Each file starts with a checksum of the rest, then holds
+// the program's encoding ahead of the payload, since fingerprints can
+// collide; an entry that is missing, unreadable, corrupt or for another
+// program is simply a miss. Backends
+// decide what goes in an entry; anything that loads native code from here
+// trusts whoever can write to the directory.
+#[derive(Clone, Debug)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+}
+
+impl ArtifactCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, program: &Program, target: &str, kind: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{:016x}.{}.{}",
+            program.fingerprint(),
+            target,
+            kind
+        ))
+    }
+
+    pub fn load(&self, program: &Program, target: &str, kind: &str) -> Option<Vec<u8>> {
+        let bytes = fs::read(self.path(program, target, kind)).ok()?;
+        if bytes.len() < 16 {
+            return None;
+        }
+        let (checksum, entry) = bytes.split_at(8);
+        if checksum != fnv1a(entry).to_le_bytes() {
+            return None;
+        }
+        let (length, rest) = entry.split_at(8);
+        let length = usize::try_from(u64::from_le_bytes(length.try_into().unwrap())).ok()?;
+        if length > rest.len() {
+            return None;
+        }
+        let (encoding, payload) = rest.split_at(length);
+        if encoding != program.to_bytes() {
+            return None;
+        }
+        Some(payload.to_vec())
+    }
+
+    // Writes to a temporary file first and renames it into place, so a
+    // process reading concurrently never sees half an entry.
+    pub fn store(
+        &self,
+        program: &Program,
+        target: &str,
+        kind: &str,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(program, target, kind);
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(format!(".{}.tmp", std::process::id()));
+        let encoding = program.to_bytes();
+        let mut entry = Vec::with_capacity(8 + encoding.len() + payload.len());
+        entry.extend_from_slice(&(encoding.len() as u64).to_le_bytes());
+        entry.extend_from_slice(&encoding);
+        entry.extend_from_slice(payload);
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(&fnv1a(&entry).to_le_bytes())?;
+        file.write_all(&entry)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)
+    }
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,105 @@
+use super::{Calibration, Environment, Program};
+use crate::vm;
+
+// Bins per histogram, spread evenly over the observed range.
+pub const HISTOGRAM_BINS: usize = 2048;
+
+// How the finite values one instruction produced are spread over its range.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Histogram {
+    min: f32,
+    max: f32,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn new(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            counts: vec![0; HISTOGRAM_BINS],
+        }
+    }
+
+    fn record(&mut self, value: f32) {
+        let width = self.max - self.min;
+        let bin = if width > 0. {
+            ((value - self.min) / width * HISTOGRAM_BINS as f32) as usize
+        } else {
+            0
+        };
+        self.counts[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    pub fn min(&self) -> f32 {
+        self.min
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // The narrowest run of whole bins holding `coverage` of the values,
+    // dropping no more than half of the remainder from either end.
+    pub fn range(&self, coverage: f32) -> (f32, f32) {
+        let tail = ((1. - coverage.clamp(0., 1.)) / 2. * self.total() as f32) as u64;
+        let edge =
+            |bin: usize| self.min + (self.max - self.min) * bin as f32 / HISTOGRAM_BINS as f32;
+        let mut low = 0;
+        let mut dropped = 0;
+        while low < HISTOGRAM_BINS - 1 && dropped + self.counts[low] <= tail {
+            dropped += self.counts[low];
+            low += 1;
+        }
+        let mut high = HISTOGRAM_BINS;
+        let mut dropped = 0;
+        while high > low + 1 && dropped + self.counts[high - 1] <= tail {
+            dropped += self.counts[high - 1];
+            high -= 1;
+        }
+        (edge(low), edge(high))
+    }
+}
+
+// Runs the program on every sample, recording each instruction's results:
+// a first pass finds the ranges and a second fills a histogram over each.
+// NaN and infinite results are left out. The calibration has the full
+// ranges; Calibration::clipped() trims outliers using the histograms.
+pub fn calibrate(program: &Program, samples: &[Environment]) -> Calibration {
+    let instructions = program.instructions();
+    let slot_count = program.register_count();
+    let mut context = program.context();
+    let mut ranges = vec![(f32::INFINITY, f32::NEG_INFINITY); instructions.len()];
+    for env in samples {
+        vm::execute_observed(&mut context, instructions, slot_count, env, |i, value| {
+            if value.is_finite() {
+                ranges[i] = (ranges[i].0.min(value), ranges[i].1.max(value));
+            }
+        });
+    }
+    let ranges: Vec<(f32, f32)> = ranges
+        .into_iter()
+        .map(|(min, max)| if min <= max { (min, max) } else { (0., 0.) })
+        .collect();
+    let mut histograms: Vec<Histogram> = ranges
+        .iter()
+        .map(|&(min, max)| Histogram::new(min, max))
+        .collect();
+    let mut context = program.context();
+    for env in samples {
+        vm::execute_observed(&mut context, instructions, slot_count, env, |i, value| {
+            if value.is_finite() {
+                histograms[i].record(value);
+            }
+        });
+    }
+    Calibration::with_histograms(ranges, histograms)
+}
@@ -0,0 +1,108 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use super::{cache::fnv1a, DynScalar, Operation, Scalar};
+
+// A name for a node of a graph that depends only on the graph's content,
+// so it is the same in every process and after a serde round trip. The
+// hash covers the node's kind and its operands' hashes. Nodes that hash
+// alike, like two unseeded noise sources or an unshared repeat of a
+// subexpression, are told apart by the disambiguator, their order of
+// appearance in postorder.
+// The hash of a subexpression is the same in any graph; the disambiguator
+// only means something within one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CanonicalId {
+    pub hash: u64,
+    pub disambiguator: u32,
+}
+
+impl Display for CanonicalId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}:{}", self.hash, self.disambiguator)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseCanonicalIdError(String);
+
+impl Display for ParseCanonicalIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is not a canonical id", self.0)
+    }
+}
+
+impl std::error::Error for ParseCanonicalIdError {}
+
+impl FromStr for CanonicalId {
+    type Err = ParseCanonicalIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseCanonicalIdError(s.to_string());
+        let (hash, disambiguator) = s.split_once(':').ok_or_else(error)?;
+        if hash.len() != 16 {
+            return Err(error());
+        }
+        Ok(CanonicalId {
+            hash: u64::from_str_radix(hash, 16).map_err(|_| error())?,
+            disambiguator: disambiguator.parse().map_err(|_| error())?,
+        })
+    }
+}
+
+impl DynScalar {
+    // The root never repeats within its own graph, so its disambiguator is
+    // always 0.
+    pub fn canonical_id(&self) -> CanonicalId {
+        self.canonical_ids().pop().unwrap().0
+    }
+
+    // Every distinct node with its id, in postorder.
+    pub fn canonical_ids(&self) -> Vec<(CanonicalId, DynScalar)> {
+        let mut hashes: HashMap<usize, u64> = HashMap::new();
+        let mut nodes = Vec::new();
+        for node in self.postorder() {
+            // Kinds go in by their Debug form, as for structural equality;
+            // constants keep every digit. Operands go in by hash alone, so
+            // where a repeat sits does not change its parents' hashes.
+            let mut bytes = format!("{:?}", node.kind()).into_bytes();
+            for child in node.children() {
+                bytes.push(0);
+                bytes.extend_from_slice(&hashes[&child.id()].to_le_bytes());
+            }
+            let hash = fnv1a(&bytes);
+            hashes.insert(node.id(), hash);
+            nodes.push((hash, node));
+        }
+        let mut seen: HashMap<u64, u32> = HashMap::new();
+        nodes
+            .into_iter()
+            .map(|(hash, node)| {
+                let count = seen.entry(hash).or_insert(0);
+                let id = CanonicalId {
+                    hash,
+                    disambiguator: *count,
+                };
+                *count += 1;
+                (id, node)
+            })
+            .collect()
+    }
+
+    pub fn find_canonical(&self, id: CanonicalId) -> Option<DynScalar> {
+        self.canonical_ids()
+            .into_iter()
+            .find(|(candidate, _)| *candidate == id)
+            .map(|(_, node)| node)
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn canonical_id(&self) -> CanonicalId {
+        self.clone().into_dyn().canonical_id()
+    }
+
+    pub fn canonical_ids(&self) -> Vec<(CanonicalId, DynScalar)> {
+        self.clone().into_dyn().canonical_ids()
+    }
+}
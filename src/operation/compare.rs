@@ -0,0 +1,202 @@
+use std::fmt::Display;
+
+use super::{
+    instruction, CompileError, Dual, DynScalar, Environment, EvalError, EvalPolicy, OpKind,
+    Operation, RegisterAllocator, Scalar,
+};
+
+// Comparisons evaluate to 1 when they hold and 0 otherwise, so they can
+// feed select() or be multiplied in as masks. As with f32, every
+// comparison but `!=` is false when either side is NaN.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn holds<T: PartialOrd>(&self, a: T, b: T) -> bool {
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+        }
+    }
+
+    // The instruction mnemonic.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "eq",
+            Comparison::Ne => "ne",
+            Comparison::Lt => "lt",
+            Comparison::Le => "le",
+            Comparison::Gt => "gt",
+            Comparison::Ge => "ge",
+        }
+    }
+
+    pub fn apply(&self, a: f32, b: f32) -> f32 {
+        if self.holds(a, b) {
+            1.
+        } else {
+            0.
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Comparison::Eq => write!(f, "=="),
+            Comparison::Ne => write!(f, "!="),
+            Comparison::Lt => write!(f, "<"),
+            Comparison::Le => write!(f, "<="),
+            Comparison::Gt => write!(f, ">"),
+            Comparison::Ge => write!(f, ">="),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Compare<T: Operation + ?Sized, U: Operation + ?Sized> {
+    comparison: Comparison,
+    a: Scalar<T>,
+    b: Scalar<U>,
+    compile_ret: Option<usize>,
+}
+
+impl<T, U> Compare<T, U>
+where
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
+{
+    pub(super) fn new(comparison: Comparison, a: &Scalar<T>, b: &Scalar<U>) -> Self {
+        Self {
+            comparison,
+            a: a.clone(),
+            b: b.clone(),
+            compile_ret: None,
+        }
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn compare<U>(&self, comparison: Comparison, other: &Scalar<U>) -> Scalar<Compare<O, U>>
+    where
+        U: Operation + ?Sized,
+    {
+        Scalar::from_operation(Compare::new(comparison, self, other))
+    }
+
+    pub fn eq<U: Operation + ?Sized>(&self, other: &Scalar<U>) -> Scalar<Compare<O, U>> {
+        self.compare(Comparison::Eq, other)
+    }
+
+    pub fn ne<U: Operation + ?Sized>(&self, other: &Scalar<U>) -> Scalar<Compare<O, U>> {
+        self.compare(Comparison::Ne, other)
+    }
+
+    pub fn lt<U: Operation + ?Sized>(&self, other: &Scalar<U>) -> Scalar<Compare<O, U>> {
+        self.compare(Comparison::Lt, other)
+    }
+
+    pub fn le<U: Operation + ?Sized>(&self, other: &Scalar<U>) -> Scalar<Compare<O, U>> {
+        self.compare(Comparison::Le, other)
+    }
+
+    pub fn gt<U: Operation + ?Sized>(&self, other: &Scalar<U>) -> Scalar<Compare<O, U>> {
+        self.compare(Comparison::Gt, other)
+    }
+
+    pub fn ge<U: Operation + ?Sized>(&self, other: &Scalar<U>) -> Scalar<Compare<O, U>> {
+        self.compare(Comparison::Ge, other)
+    }
+}
+
+impl<T, U> Display for Compare<T, U>
+where
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} {} {})", self.a, self.comparison, self.b)
+    }
+}
+
+impl<T, U> Operation for Compare<T, U>
+where
+    T: Operation + ?Sized,
+    U: Operation + ?Sized,
+{
+    fn execute(&self, env: &Environment) -> f32 {
+        self.comparison.apply(
+            self.a.operation.borrow().execute(env),
+            self.b.operation.borrow().execute(env),
+        )
+    }
+
+    // A step function: flat on both sides of the threshold.
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        Ok(self.comparison.apply(
+            self.a.operation.borrow().try_execute(env, policy)?,
+            self.b.operation.borrow().try_execute(env, policy)?,
+        ))
+    }
+
+    fn execute_dual(&self, env: &Environment, _wrt: &str) -> Dual {
+        Dual::constant(self.execute(env))
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        match self.compile_ret {
+            Some(ret) => Ok(ret),
+            None => {
+                let a = self
+                    .a
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let b = self
+                    .b
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let ret = registers.alloc()?;
+                self.compile_ret = Some(ret);
+                instructions.push(instruction::compare(self.comparison, a, b, ret));
+                Ok(ret)
+            }
+        }
+    }
+
+    fn reset_compile(&mut self) {
+        if self.compile_ret.take().is_some() {
+            self.a.operation.borrow_mut().reset_compile();
+            self.b.operation.borrow_mut().reset_compile();
+        }
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Compare(self.comparison)
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.a.clone().into_dyn(), self.b.clone().into_dyn()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        format!("({} {} {})", children[0], self.comparison, children[1])
+    }
+}
@@ -0,0 +1,415 @@
+use std::{collections::HashMap, fmt::Display, rc::Rc};
+
+use super::{DynScalar, Environment, Program, Scalar};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    pub fn real(re: f32) -> Self {
+        Self::new(re, 0.)
+    }
+
+    pub fn i() -> Self {
+        Self::new(0., 1.)
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn abs(self) -> f32 {
+        self.re.hypot(self.im)
+    }
+}
+
+impl From<f32> for Complex {
+    fn from(re: f32) -> Self {
+        Self::real(re)
+    }
+}
+
+impl Display for Complex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.im.is_sign_negative() { '-' } else { '+' };
+        write!(f, "{}{}{}i", self.re, sign, self.im.abs())
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+// The textbook formula rather than Smith's, so lowered graphs, which are
+// built the same way, give the same results.
+impl std::ops::Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let denominator = other.norm_sqr();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+}
+
+impl Environment {
+    // Binds the parts of complex variable `name`, which are the real
+    // variables `name.re` and `name.im`.
+    pub fn set_complex(&mut self, name: &str, value: Complex) -> &mut Self {
+        self.set(format!("{}.re", name), value.re)
+            .set(format!("{}.im", name), value.im)
+    }
+
+    pub fn get_complex(&self, name: &str) -> Option<Complex> {
+        Some(Complex::new(
+            self.get(&format!("{}.re", name))?,
+            self.get(&format!("{}.im", name))?,
+        ))
+    }
+}
+
+enum Node {
+    Constant(Complex),
+    Variable(String),
+    Parts(DynScalar, DynScalar),
+    Add(CScalar, CScalar),
+    Sub(CScalar, CScalar),
+    Mul(CScalar, CScalar),
+    Div(CScalar, CScalar),
+    Conj(CScalar),
+}
+
+// A lazy complex-valued expression. Its leaves are complex constants,
+// complex variables and pairs of real expressions, so real graphs, noise
+// included, can feed complex arithmetic. It compiles to a ComplexProgram or
+// lowers to a pair of real graphs for the real backends.
+#[derive(Clone)]
+pub struct CScalar {
+    node: Rc<Node>,
+}
+
+impl CScalar {
+    fn from_node(node: Node) -> Self {
+        Self {
+            node: Rc::new(node),
+        }
+    }
+
+    pub fn new(value: impl Into<Complex>) -> Self {
+        Self::from_node(Node::Constant(value.into()))
+    }
+
+    pub fn variable(name: impl Into<String>) -> Self {
+        Self::from_node(Node::Variable(name.into()))
+    }
+
+    pub fn from_parts(re: impl Into<DynScalar>, im: impl Into<DynScalar>) -> Self {
+        Self::from_node(Node::Parts(re.into(), im.into()))
+    }
+
+    pub fn real(re: impl Into<DynScalar>) -> Self {
+        Self::from_parts(re, Scalar::new(0.))
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::from_node(Node::Conj(self.clone()))
+    }
+
+    fn id(&self) -> usize {
+        Rc::as_ptr(&self.node) as usize
+    }
+
+    fn children(&self) -> Vec<&CScalar> {
+        match &*self.node {
+            Node::Constant(_) | Node::Variable(_) | Node::Parts(..) => Vec::new(),
+            Node::Add(a, b) | Node::Sub(a, b) | Node::Mul(a, b) | Node::Div(a, b) => vec![a, b],
+            Node::Conj(a) => vec![a],
+        }
+    }
+
+    // Children before parents, each node once.
+    fn postorder(&self) -> Vec<&CScalar> {
+        let mut seen = std::collections::HashSet::new();
+        let mut order = Vec::new();
+        let mut stack = vec![(self, false)];
+        while let Some((node, expanded)) = stack.pop() {
+            if expanded {
+                order.push(node);
+            } else if seen.insert(node.id()) {
+                stack.push((node, true));
+                stack.extend(
+                    node.children()
+                        .into_iter()
+                        .rev()
+                        .map(|child| (child, false)),
+                );
+            }
+        }
+        order
+    }
+
+    // The real and imaginary parts as real graphs, sharing a node for each
+    // shared complex node.
+    pub fn parts(&self) -> (DynScalar, DynScalar) {
+        let mut lowered: HashMap<usize, (DynScalar, DynScalar)> = HashMap::new();
+        for node in self.postorder() {
+            let part = |child: &CScalar| lowered[&child.id()].clone();
+            let parts = match &*node.node {
+                Node::Constant(value) => (
+                    Scalar::new(value.re).into_dyn(),
+                    Scalar::new(value.im).into_dyn(),
+                ),
+                Node::Variable(name) => (
+                    Scalar::variable(format!("{}.re", name)).into_dyn(),
+                    Scalar::variable(format!("{}.im", name)).into_dyn(),
+                ),
+                Node::Parts(re, im) => (re.clone(), im.clone()),
+                Node::Add(a, b) => {
+                    let ((a, b), (c, d)) = (part(a), part(b));
+                    (&a + &c, &b + &d)
+                }
+                Node::Sub(a, b) => {
+                    let ((a, b), (c, d)) = (part(a), part(b));
+                    (&a - &c, &b - &d)
+                }
+                Node::Mul(a, b) => {
+                    let ((a, b), (c, d)) = (part(a), part(b));
+                    (&(&a * &c) - &(&b * &d), &(&a * &d) + &(&b * &c))
+                }
+                Node::Div(a, b) => {
+                    let ((a, b), (c, d)) = (part(a), part(b));
+                    let denominator = &(&c * &c) + &(&d * &d);
+                    (
+                        &(&(&a * &c) + &(&b * &d)) / &denominator,
+                        &(&(&b * &c) - &(&a * &d)) / &denominator,
+                    )
+                }
+                Node::Conj(a) => {
+                    let (re, im) = part(a);
+                    (re, &Scalar::new(0.).into_dyn() - &im)
+                }
+            };
+            lowered.insert(node.id(), parts);
+        }
+        lowered.remove(&self.id()).unwrap()
+    }
+
+    pub fn re(&self) -> DynScalar {
+        self.parts().0
+    }
+
+    pub fn im(&self) -> DynScalar {
+        self.parts().1
+    }
+
+    pub fn compile(&self) -> ComplexProgram {
+        let mut registers = HashMap::new();
+        let mut instructions = Vec::new();
+        let mut lifted = Vec::new();
+        for node in self.postorder() {
+            let register = |child: &CScalar| registers[&child.id()];
+            let op = match &*node.node {
+                Node::Constant(value) => ComplexOp::Constant(*value),
+                Node::Variable(name) => ComplexOp::Load(name.clone()),
+                Node::Parts(re, im) => {
+                    lifted.extend([re.clone(), im.clone()]);
+                    ComplexOp::Lift(lifted.len() - 2, lifted.len() - 1)
+                }
+                Node::Add(a, b) => ComplexOp::Add(register(a), register(b)),
+                Node::Sub(a, b) => ComplexOp::Sub(register(a), register(b)),
+                Node::Mul(a, b) => ComplexOp::Mul(register(a), register(b)),
+                Node::Div(a, b) => ComplexOp::Div(register(a), register(b)),
+                Node::Conj(a) => ComplexOp::Conj(register(a)),
+            };
+            registers.insert(node.id(), instructions.len());
+            instructions.push(op);
+        }
+        ComplexProgram {
+            real: (!lifted.is_empty()).then(|| Program::compile(&lifted)),
+            instructions,
+        }
+    }
+
+    pub fn execute(&self) -> Complex {
+        self.execute_with(&Environment::new())
+    }
+
+    pub fn execute_with(&self, env: &Environment) -> Complex {
+        self.compile().run_with(env)
+    }
+}
+
+impl From<Complex> for CScalar {
+    fn from(value: Complex) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Display for CScalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &*self.node {
+            Node::Constant(value) if value.im == 0. => write!(f, "{}", value.re),
+            Node::Constant(value) => write!(f, "({})", value),
+            Node::Variable(name) => write!(f, "{}", name),
+            Node::Parts(re, im) => write!(f, "complex({}, {})", re, im),
+            Node::Add(a, b) => write!(f, "({} + {})", a, b),
+            Node::Sub(a, b) => write!(f, "({} - {})", a, b),
+            Node::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Node::Div(a, b) => write!(f, "({} / {})", a, b),
+            Node::Conj(a) => write!(f, "conj({})", a),
+        }
+    }
+}
+
+impl std::ops::Add<&CScalar> for &CScalar {
+    type Output = CScalar;
+
+    fn add(self, other: &CScalar) -> CScalar {
+        CScalar::from_node(Node::Add(self.clone(), other.clone()))
+    }
+}
+
+impl std::ops::Sub<&CScalar> for &CScalar {
+    type Output = CScalar;
+
+    fn sub(self, other: &CScalar) -> CScalar {
+        CScalar::from_node(Node::Sub(self.clone(), other.clone()))
+    }
+}
+
+impl std::ops::Mul<&CScalar> for &CScalar {
+    type Output = CScalar;
+
+    fn mul(self, other: &CScalar) -> CScalar {
+        CScalar::from_node(Node::Mul(self.clone(), other.clone()))
+    }
+}
+
+impl std::ops::Div<&CScalar> for &CScalar {
+    type Output = CScalar;
+
+    fn div(self, other: &CScalar) -> CScalar {
+        CScalar::from_node(Node::Div(self.clone(), other.clone()))
+    }
+}
+
+// Complex instructions, one register per instruction. Lift takes its parts
+// from outputs of the program's real part.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ComplexOp {
+    Constant(Complex),
+    Load(String),
+    Lift(usize, usize),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Conj(usize),
+}
+
+impl Display for ComplexOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ComplexOp::Constant(value) => write!(f, "cconstant {}", value),
+            ComplexOp::Load(name) => write!(f, "cload {}", name),
+            ComplexOp::Lift(re, im) => write!(f, "lift ${} ${}", re, im),
+            ComplexOp::Add(a, b) => write!(f, "cadd %{} %{}", a, b),
+            ComplexOp::Sub(a, b) => write!(f, "csub %{} %{}", a, b),
+            ComplexOp::Mul(a, b) => write!(f, "cmul %{} %{}", a, b),
+            ComplexOp::Div(a, b) => write!(f, "cdiv %{} %{}", a, b),
+            ComplexOp::Conj(a) => write!(f, "conj %{}", a),
+        }
+    }
+}
+
+// A compiled complex expression: a real program for the real expressions
+// it lifts, if any, and complex instructions over their results. The last
+// instruction's register holds the result; Display shows only the complex
+// instructions.
+#[derive(Clone)]
+pub struct ComplexProgram {
+    real: Option<Program>,
+    instructions: Vec<ComplexOp>,
+}
+
+impl ComplexProgram {
+    pub fn real(&self) -> Option<&Program> {
+        self.real.as_ref()
+    }
+
+    pub fn instructions(&self) -> &[ComplexOp] {
+        &self.instructions
+    }
+
+    pub fn run_with(&self, env: &Environment) -> Complex {
+        let real = match &self.real {
+            Some(program) => program.run_with(env),
+            None => Vec::new(),
+        };
+        let mut registers: Vec<Complex> = Vec::with_capacity(self.instructions.len());
+        for op in &self.instructions {
+            let r = &registers;
+            let value = match op {
+                ComplexOp::Constant(value) => *value,
+                ComplexOp::Load(name) => env
+                    .get_complex(name)
+                    .unwrap_or_else(|| panic!("unbound variable {}", name)),
+                ComplexOp::Lift(re, im) => Complex::new(real[*re], real[*im]),
+                ComplexOp::Add(a, b) => r[*a] + r[*b],
+                ComplexOp::Sub(a, b) => r[*a] - r[*b],
+                ComplexOp::Mul(a, b) => r[*a] * r[*b],
+                ComplexOp::Div(a, b) => r[*a] / r[*b],
+                ComplexOp::Conj(a) => r[*a].conj(),
+            };
+            registers.push(value);
+        }
+        *registers
+            .last()
+            .expect("a program has at least one instruction")
+    }
+}
+
+impl Display for ComplexProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (register, op) in self.instructions.iter().enumerate() {
+            writeln!(f, "%{}: {}", register, op)?;
+        }
+        write!(f, "ret %{}", self.instructions.len() - 1)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use super::{DynScalar, GraphLimits, LimitError, Scalar};
+
+// One place to build graphs from: each variable name and constant value maps
+// to a single shared node, and every expression built through the context
+// is checked against its limits.
+#[derive(Clone, Default)]
+pub struct GraphContext {
+    limits: GraphLimits,
+    variables: RefCell<HashMap<String, DynScalar>>,
+    constants: RefCell<HashMap<u32, DynScalar>>,
+    interned: RefCell<HashSet<DynScalar>>,
+}
+
+impl GraphContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(mut self, limits: GraphLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &GraphLimits {
+        &self.limits
+    }
+
+    // Declares the variable on first use; later calls return the same node.
+    pub fn var(&self, name: impl Into<String>) -> DynScalar {
+        let name = name.into();
+        self.variables
+            .borrow_mut()
+            .entry(name.clone())
+            .or_insert_with(|| Scalar::variable(name).into_dyn())
+            .clone()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<DynScalar> {
+        self.variables.borrow().get(name).cloned()
+    }
+
+    pub fn variables(&self) -> Vec<String> {
+        self.variables.borrow().keys().cloned().collect()
+    }
+
+    // Constants are interned by bit pattern, so 0 and -0 stay distinct.
+    pub fn constant(&self, value: f32) -> DynScalar {
+        self.constants
+            .borrow_mut()
+            .entry(value.to_bits())
+            .or_insert_with(|| Scalar::new(value).into_dyn())
+            .clone()
+    }
+
+    pub fn add(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.limits.add(a, b)
+    }
+
+    pub fn sub(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.limits.sub(a, b)
+    }
+
+    pub fn mul(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.limits.mul(a, b)
+    }
+
+    pub fn div(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.limits.div(a, b)
+    }
+    // Returns an earlier interned expression structurally equal to `expr`,
+    // or keeps `expr` as the one later equal expressions get.
+    pub fn intern(&self, expr: DynScalar) -> DynScalar {
+        let mut interned = self.interned.borrow_mut();
+        match interned.get(&expr) {
+            Some(existing) => existing.clone(),
+            None => {
+                interned.insert(expr.clone());
+                expr
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::{
+    analysis::postorder,
+    instruction::{Instruction, Opcode},
+    DynScalar, OpKind, Operation, Program, Scalar,
+};
+
+// Relative cost of each operation, for comparing equivalent forms of an
+// expression. Override single fields with struct update syntax:
+// `CostModel { div: 4, ..CostModel::default() }`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CostModel {
+    pub constant: u32,
+    pub variable: u32,
+    pub noise: u32,
+    pub add: u32,
+    pub sub: u32,
+    pub mul: u32,
+    pub div: u32,
+    pub select: u32,
+    pub compare: u32,
+    pub spill: u32,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            constant: 0,
+            variable: 0,
+            noise: 20,
+            add: 1,
+            sub: 1,
+            mul: 2,
+            div: 8,
+            select: 1,
+            compare: 1,
+            spill: 4,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn of(&self, kind: &OpKind) -> u32 {
+        match kind {
+            OpKind::Constant(_) | OpKind::Symbol(_) | OpKind::Wildcard(_) => self.constant,
+            OpKind::Alias | OpKind::Provenance(_) => 0,
+            OpKind::Variable(_) => self.variable,
+            OpKind::Noise(..) => self.noise,
+            OpKind::Add => self.add,
+            OpKind::Sub => self.sub,
+            OpKind::Mul => self.mul,
+            OpKind::Div => self.div,
+            OpKind::Select => self.select,
+            OpKind::Compare(_) => self.compare,
+        }
+    }
+
+    pub(super) fn of_instruction(&self, instruction: &Instruction) -> u32 {
+        match instruction.opcode() {
+            Opcode::Constant(_) => self.constant,
+            Opcode::Load(_) => self.variable,
+            Opcode::Noise(..) => self.noise,
+            Opcode::Add(..) => self.add,
+            Opcode::Sub(..) => self.sub,
+            Opcode::Mul(..) => self.mul,
+            Opcode::Div(..) => self.div,
+            Opcode::Select(..) => self.select,
+            Opcode::Compare(..) => self.compare,
+            Opcode::Store(_) | Opcode::Reload(_) => self.spill,
+        }
+    }
+}
+
+impl DynScalar {
+    // Shared nodes are counted once, as they are computed once.
+    pub fn cost(&self, model: &CostModel) -> u64 {
+        postorder(self)
+            .iter()
+            .map(|node| model.of(&node.kind()) as u64)
+            .sum()
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn cost(&self, model: &CostModel) -> u64 {
+        self.clone().into_dyn().cost(model)
+    }
+}
+
+impl Program {
+    pub fn cost(&self, model: &CostModel) -> u64 {
+        self.instructions()
+            .iter()
+            .map(|instruction| model.of_instruction(instruction) as u64)
+            .sum()
+    }
+}
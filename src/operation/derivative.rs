@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use super::{DynScalar, OpKind, Operation, Scalar};
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn derivative<V: Operation + ?Sized>(&self, var: &Scalar<V>) -> DynScalar {
+        self.clone().into_dyn().derivative(&var.clone().into_dyn())
+    }
+}
+
+impl DynScalar {
+    // A new graph for d(self)/d(var), built by the sum, product and quotient
+    // rules over the original nodes, which it shares. Terms that are known to
+    // vanish are left out. As with dual numbers, comparisons and noise have
+    // no derivative and select passes on the derivative of its branches.
+    pub fn derivative(&self, var: &DynScalar) -> DynScalar {
+        let OpKind::Variable(wrt) = var.kind() else {
+            panic!(
+                "can only differentiate with respect to a variable, not {}",
+                var
+            );
+        };
+        // None stands for a derivative that is zero.
+        let mut derivatives: HashMap<usize, Option<DynScalar>> = HashMap::new();
+        for node in self.postorder() {
+            let children = node.children();
+            let d: Vec<Option<DynScalar>> = children
+                .iter()
+                .map(|child| derivatives[&child.id()].clone())
+                .collect();
+            let derivative = match node.kind() {
+                OpKind::Variable(name) if name == wrt => Some(Scalar::new(1.).into_dyn()),
+                OpKind::Constant(_)
+                | OpKind::Variable(_)
+                | OpKind::Symbol(_)
+                | OpKind::Noise(..)
+                | OpKind::Compare(_) => None,
+                OpKind::Wildcard(name) => panic!("cannot differentiate pattern wildcard ?{}", name),
+                OpKind::Alias | OpKind::Provenance(_) => d[0].clone(),
+                OpKind::Add => match (&d[0], &d[1]) {
+                    (Some(da), Some(db)) => Some(da + db),
+                    (da, db) => da.clone().or(db.clone()),
+                },
+                OpKind::Sub => match (&d[0], &d[1]) {
+                    (Some(da), Some(db)) => Some(da - db),
+                    (da, Some(db)) if da.is_none() => Some(&Scalar::new(0.).into_dyn() - db),
+                    (da, _) => da.clone(),
+                },
+                OpKind::Mul => {
+                    let (a, b) = (&children[0], &children[1]);
+                    match (&d[0], &d[1]) {
+                        (Some(da), Some(db)) => Some(&times(da, b) + &times(db, a)),
+                        (Some(da), None) => Some(times(da, b)),
+                        (None, Some(db)) => Some(times(db, a)),
+                        (None, None) => None,
+                    }
+                }
+                OpKind::Div => {
+                    let (a, b) = (&children[0], &children[1]);
+                    match (&d[0], &d[1]) {
+                        (da, None) => da.as_ref().map(|da| da / b),
+                        (da, Some(db)) => {
+                            let numerator = match da {
+                                Some(da) => &times(da, b) - &times(db, a),
+                                None => &Scalar::new(0.).into_dyn() - &times(db, a),
+                            };
+                            Some(&numerator / &(b * b))
+                        }
+                    }
+                }
+                OpKind::Select => match (&d[1], &d[2]) {
+                    (None, None) => None,
+                    (dt, de) => {
+                        let zero = || Scalar::new(0.).into_dyn();
+                        let dt = dt.clone().unwrap_or_else(zero);
+                        let de = de.clone().unwrap_or_else(zero);
+                        Some(children[0].select(&dt, &de))
+                    }
+                },
+            };
+            derivatives.insert(node.id(), derivative);
+        }
+        derivatives
+            .remove(&self.id())
+            .unwrap()
+            .unwrap_or_else(|| Scalar::new(0.).into_dyn())
+    }
+}
+
+// d * x, without the multiplication when d is the constant 1.
+fn times(d: &DynScalar, x: &DynScalar) -> DynScalar {
+    match d.kind() {
+        OpKind::Constant(1.) => x.clone(),
+        _ => d * x,
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use super::EvalLog;
+
+// A node whose value differs between two logs while its operands' values
+// agree, so the difference starts there.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Divergence {
+    pub id: usize,
+    pub label: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+}
+
+impl EvalLog {
+    // Compares two logs of the same graph node by node and returns where
+    // values first part by more than `tolerance`, relative to the larger of
+    // the two, in postorder. Nodes downstream of a divergence are left out
+    // unless they diverge on their own account, i.e. all of their operands
+    // agree. NaN agrees with NaN, and a node evaluated in one log but
+    // skipped in the other diverges.
+    pub fn diff(&self, other: &EvalLog, tolerance: f64) -> Vec<Divergence> {
+        assert!(
+            self.entries().len() == other.entries().len()
+                && self
+                    .entries()
+                    .iter()
+                    .zip(other.entries())
+                    .all(|(a, b)| { a.label == b.label && a.operands == b.operands }),
+            "logs are of different graphs"
+        );
+        let differs: Vec<bool> = self
+            .entries()
+            .iter()
+            .zip(other.entries())
+            .map(|(a, b)| !agree(a.value, b.value, tolerance))
+            .collect();
+        self.entries()
+            .iter()
+            .zip(other.entries())
+            .filter(|(entry, _)| {
+                differs[entry.id] && entry.operands.iter().all(|&operand| !differs[operand])
+            })
+            .map(|(before, after)| Divergence {
+                id: before.id,
+                label: before.label.clone(),
+                before: before.value,
+                after: after.value,
+            })
+            .collect()
+    }
+
+    pub fn first_divergence(&self, other: &EvalLog, tolerance: f64) -> Option<Divergence> {
+        self.diff(other, tolerance).into_iter().next()
+    }
+}
+
+fn agree(a: Option<f64>, b: Option<f64>, tolerance: f64) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            a == b
+                || (a.is_nan() && b.is_nan())
+                || (a - b).abs() <= tolerance * a.abs().max(b.abs())
+        }
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |value: Option<f64>| value.map_or("skipped".to_string(), |v| v.to_string());
+        write!(
+            f,
+            "#{} {}: {} -> {}",
+            self.id,
+            self.label,
+            value(self.before),
+            value(self.after)
+        )
+    }
+}
@@ -0,0 +1,38 @@
+use std::{collections::HashMap, fmt::Write};
+
+use super::{DynScalar, Operation, Scalar};
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn to_dot(&self) -> String {
+        self.clone().into_dyn().to_dot()
+    }
+}
+
+impl DynScalar {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n    ordering=out;\n");
+        let mut names = HashMap::new();
+        write_node(self, &mut names, &mut dot);
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn write_node(node: &DynScalar, names: &mut HashMap<usize, String>, dot: &mut String) -> String {
+    if let Some(name) = names.get(&node.id()) {
+        return name.clone();
+    }
+    let name = format!("n{}", names.len());
+    names.insert(node.id(), name.clone());
+    let label = node
+        .kind()
+        .to_string()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    writeln!(dot, "    {} [label=\"{}\"];", name, label).unwrap();
+    for child in node.children() {
+        let child = write_node(&child, names, dot);
+        writeln!(dot, "    {} -> {};", name, child).unwrap();
+    }
+    name
+}
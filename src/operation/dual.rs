@@ -0,0 +1,72 @@
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Dual {
+    pub value: f32,
+    pub derivative: f32,
+}
+
+impl Dual {
+    pub fn constant(value: f32) -> Self {
+        Self {
+            value,
+            derivative: 0.,
+        }
+    }
+
+    pub fn variable(value: f32) -> Self {
+        Self {
+            value,
+            derivative: 1.,
+        }
+    }
+}
+
+impl std::fmt::Display for Dual {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} + {}ε", self.value, self.derivative)
+    }
+}
+
+impl std::ops::Add for Dual {
+    type Output = Dual;
+
+    fn add(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value + other.value,
+            derivative: self.derivative + other.derivative,
+        }
+    }
+}
+
+impl std::ops::Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value - other.value,
+            derivative: self.derivative - other.derivative,
+        }
+    }
+}
+
+impl std::ops::Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value * other.value,
+            derivative: self.derivative * other.value + self.value * other.derivative,
+        }
+    }
+}
+
+impl std::ops::Div for Dual {
+    type Output = Dual;
+
+    fn div(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value / other.value,
+            derivative: (self.derivative * other.value - self.value * other.derivative)
+                / (other.value * other.value),
+        }
+    }
+}
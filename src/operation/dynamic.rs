@@ -0,0 +1,199 @@
+use std::{fmt::Display, rc::Rc};
+
+use super::{
+    instruction, Add, Compare, Comparison, Div, Dual, Environment, Mul, OpKind, Operation, Scalar,
+    Select, Sub,
+};
+
+#[derive(Clone)]
+pub struct DynScalar {
+    pub(super) scalar: Scalar<dyn Operation>,
+}
+
+impl DynScalar {
+    fn from_operation<O: Operation>(operation: O) -> Self {
+        Scalar::from_operation(operation).into_dyn()
+    }
+
+    // Builds a fresh node of the given kind over new children; the inverse of
+    // kind() and children().
+    pub(crate) fn from_kind(kind: OpKind, children: &[DynScalar]) -> Self {
+        match kind {
+            OpKind::Constant(value) => Scalar::new(value).into_dyn(),
+            OpKind::Variable(name) => Scalar::variable(name).into_dyn(),
+            OpKind::Symbol(symbol) => Scalar::symbol(symbol).into_dyn(),
+            OpKind::Noise(distribution, seed) => Scalar::noise(distribution, seed).into_dyn(),
+            OpKind::Wildcard(name) => Scalar::wildcard(name).into_dyn(),
+            OpKind::Alias => Scalar::alias(children[0].clone()).into_dyn(),
+            OpKind::Provenance(note) => children[0].with_provenance(note).into_dyn(),
+            OpKind::Add => children[0].add(&children[1]),
+            OpKind::Sub => children[0].sub(&children[1]),
+            OpKind::Mul => children[0].mul(&children[1]),
+            OpKind::Div => children[0].div(&children[1]),
+            OpKind::Select => children[0].select(&children[1], &children[2]),
+            OpKind::Compare(comparison) => children[0].compare(comparison, &children[1]),
+        }
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        Rc::as_ptr(&self.scalar.node) as *const () as usize
+    }
+
+    pub fn kind(&self) -> OpKind {
+        self.scalar.node.borrow().kind()
+    }
+
+    pub(crate) fn noise_source(&self) -> Option<u64> {
+        self.scalar.node.borrow().noise_source()
+    }
+
+    pub fn children(&self) -> Vec<DynScalar> {
+        self.scalar.node.borrow().children()
+    }
+
+    pub fn execute(&self) -> f32 {
+        self.scalar.execute()
+    }
+
+    pub fn execute_with(&self, env: &Environment) -> f32 {
+        self.scalar.execute_with(env)
+    }
+
+    pub fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.scalar.execute_dual(env, wrt)
+    }
+
+    pub fn compile(self) -> Vec<instruction::Instruction> {
+        self.scalar.compile()
+    }
+
+    pub fn add(&self, other: &DynScalar) -> DynScalar {
+        Self::from_operation(Add::new(&self.scalar, &other.scalar))
+    }
+
+    pub fn sub(&self, other: &DynScalar) -> DynScalar {
+        Self::from_operation(Sub::new(&self.scalar, &other.scalar))
+    }
+
+    pub fn mul(&self, other: &DynScalar) -> DynScalar {
+        Self::from_operation(Mul::new(&self.scalar, &other.scalar))
+    }
+
+    pub fn div(&self, other: &DynScalar) -> DynScalar {
+        Self::from_operation(Div::new(&self.scalar, &other.scalar))
+    }
+
+    pub fn select(&self, then: &DynScalar, otherwise: &DynScalar) -> DynScalar {
+        Self::from_operation(Select::new(&self.scalar, &then.scalar, &otherwise.scalar))
+    }
+
+    pub fn compare(&self, comparison: Comparison, other: &DynScalar) -> DynScalar {
+        Self::from_operation(Compare::new(comparison, &self.scalar, &other.scalar))
+    }
+
+    pub fn eq(&self, other: &DynScalar) -> DynScalar {
+        self.compare(Comparison::Eq, other)
+    }
+
+    pub fn ne(&self, other: &DynScalar) -> DynScalar {
+        self.compare(Comparison::Ne, other)
+    }
+
+    pub fn lt(&self, other: &DynScalar) -> DynScalar {
+        self.compare(Comparison::Lt, other)
+    }
+
+    pub fn le(&self, other: &DynScalar) -> DynScalar {
+        self.compare(Comparison::Le, other)
+    }
+
+    pub fn gt(&self, other: &DynScalar) -> DynScalar {
+        self.compare(Comparison::Gt, other)
+    }
+
+    pub fn ge(&self, other: &DynScalar) -> DynScalar {
+        self.compare(Comparison::Ge, other)
+    }
+
+    pub fn sum(terms: &[DynScalar]) -> DynScalar {
+        match terms {
+            [] => Scalar::new(0.).into_dyn(),
+            _ => reduce(terms, DynScalar::add),
+        }
+    }
+
+    pub fn product(factors: &[DynScalar]) -> DynScalar {
+        match factors {
+            [] => Scalar::new(1.).into_dyn(),
+            _ => reduce(factors, DynScalar::mul),
+        }
+    }
+}
+
+const CHUNK: usize = 8;
+
+// Short runs are folded left to right, longer ones split in half and joined,
+// so a wide sum compiles to a balanced tree of short chains: rounding error
+// grows with log(n) instead of n, the halves are independent, and at most
+// about log2(n / CHUNK) + 2 registers are live at once.
+fn reduce(terms: &[DynScalar], op: fn(&DynScalar, &DynScalar) -> DynScalar) -> DynScalar {
+    if terms.len() <= CHUNK {
+        return terms[1..]
+            .iter()
+            .fold(terms[0].clone(), |acc, term| op(&acc, term));
+    }
+    let (left, right) = terms.split_at(terms.len() / 2);
+    op(&reduce(left, op), &reduce(right, op))
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn into_dyn(self) -> DynScalar {
+        self.into()
+    }
+}
+
+impl<O: Operation + ?Sized> From<Scalar<O>> for DynScalar {
+    fn from(scalar: Scalar<O>) -> Self {
+        Self {
+            scalar: Scalar::from_parts(Rc::clone(&scalar.node), Rc::clone(&scalar.node)),
+        }
+    }
+}
+
+impl Display for DynScalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.scalar)
+    }
+}
+
+impl std::ops::Add<&DynScalar> for &DynScalar {
+    type Output = DynScalar;
+
+    fn add(self, other: &DynScalar) -> Self::Output {
+        DynScalar::add(self, other)
+    }
+}
+
+impl std::ops::Sub<&DynScalar> for &DynScalar {
+    type Output = DynScalar;
+
+    fn sub(self, other: &DynScalar) -> Self::Output {
+        DynScalar::sub(self, other)
+    }
+}
+
+impl std::ops::Mul<&DynScalar> for &DynScalar {
+    type Output = DynScalar;
+
+    fn mul(self, other: &DynScalar) -> Self::Output {
+        DynScalar::mul(self, other)
+    }
+}
+
+impl std::ops::Div<&DynScalar> for &DynScalar {
+    type Output = DynScalar;
+
+    fn div(self, other: &DynScalar) -> Self::Output {
+        DynScalar::div(self, other)
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use super::{CostModel, Program};
+
+// A processor to budget for: the cycles and the energy, in picojoules, each
+// operation takes on it, and the clock it runs at. The presets are rough
+// figures for comparing variants of a formula, not datasheet values;
+// measure the part and fill in your own where it matters.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TargetSpec {
+    pub name: String,
+    pub clock_hz: u64,
+    pub cycles: CostModel,
+    pub picojoules: CostModel,
+}
+
+impl TargetSpec {
+    // A Cortex-M4 with its single precision FPU at 64 MHz.
+    pub fn cortex_m4f() -> Self {
+        let cycles = CostModel {
+            constant: 1,
+            variable: 2,
+            noise: 60,
+            add: 1,
+            sub: 1,
+            mul: 1,
+            div: 14,
+            select: 2,
+            compare: 3,
+            spill: 2,
+        };
+        Self {
+            name: "cortex-m4f".to_string(),
+            clock_hz: 64_000_000,
+            cycles,
+            picojoules: scaled(&cycles, 150),
+        }
+    }
+
+    // A Cortex-M0+ without an FPU, doing float arithmetic in software, at
+    // 48 MHz.
+    pub fn cortex_m0plus() -> Self {
+        let cycles = CostModel {
+            constant: 2,
+            variable: 2,
+            noise: 400,
+            add: 60,
+            sub: 60,
+            mul: 50,
+            div: 150,
+            select: 4,
+            compare: 30,
+            spill: 2,
+        };
+        Self {
+            name: "cortex-m0+".to_string(),
+            clock_hz: 48_000_000,
+            cycles,
+            picojoules: scaled(&cycles, 60),
+        }
+    }
+}
+
+// For targets where every cycle costs about the same.
+fn scaled(cycles: &CostModel, picojoules_per_cycle: u32) -> CostModel {
+    CostModel {
+        constant: cycles.constant * picojoules_per_cycle,
+        variable: cycles.variable * picojoules_per_cycle,
+        noise: cycles.noise * picojoules_per_cycle,
+        add: cycles.add * picojoules_per_cycle,
+        sub: cycles.sub * picojoules_per_cycle,
+        mul: cycles.mul * picojoules_per_cycle,
+        div: cycles.div * picojoules_per_cycle,
+        select: cycles.select * picojoules_per_cycle,
+        compare: cycles.compare * picojoules_per_cycle,
+        spill: cycles.spill * picojoules_per_cycle,
+    }
+}
+
+// What one evaluation of a program costs on a target. Programs run every
+// instruction once, so these are exact for the instruction stream; what a
+// backend's compiler does with it is not modelled.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EnergyReport {
+    pub target: String,
+    pub ops: BTreeMap<&'static str, usize>,
+    pub cycles: u64,
+    pub picojoules: u64,
+    pub seconds: f64,
+}
+
+impl Program {
+    pub fn energy_report(&self, target: &TargetSpec) -> EnergyReport {
+        let mut ops = BTreeMap::new();
+        for instruction in self.instructions() {
+            *ops.entry(instruction.opcode().name()).or_insert(0) += 1;
+        }
+        let cycles = self.cost(&target.cycles);
+        EnergyReport {
+            target: target.name.clone(),
+            ops,
+            cycles,
+            picojoules: self.cost(&target.picojoules),
+            seconds: cycles as f64 / target.clock_hz as f64,
+        }
+    }
+}
+
+impl EnergyReport {
+    pub fn instructions(&self) -> usize {
+        self.ops.values().sum()
+    }
+
+    // Evaluations a budget of `watts` sustains each second.
+    pub fn evaluations_per_second(&self, watts: f64) -> f64 {
+        (watts / (self.picojoules as f64 * 1e-12)).min(1. / self.seconds)
+    }
+}
+
+impl Display for EnergyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ops: Vec<String> = self
+            .ops
+            .iter()
+            .map(|(name, count)| format!("{} {}", count, name))
+            .collect();
+        write!(
+            f,
+            "{}: {} cycles, {:.3} µs, {:.3} nJ per evaluation ({})",
+            self.target,
+            self.cycles,
+            self.seconds * 1e6,
+            self.picojoules as f64 / 1e3,
+            ops.join(", ")
+        )
+    }
+}
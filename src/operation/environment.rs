@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Default, Debug)]
+pub struct Environment {
+    bindings: HashMap<String, f32>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: f32) -> &mut Self {
+        self.bindings.insert(name.into(), value);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.bindings.get(name).copied()
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, f32)> for Environment {
+    fn from_iter<T: IntoIterator<Item = (S, f32)>>(iter: T) -> Self {
+        Self {
+            bindings: iter
+                .into_iter()
+                .map(|(name, value)| (name.into(), value))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    collections::HashSet,
+    mem::{size_of, size_of_val},
+};
+
+use super::{instruction::Instruction, DynScalar, Operation, Program, Scalar};
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + node_bytes(&self.clone().into_dyn())
+    }
+}
+
+impl DynScalar {
+    pub fn estimated_bytes(&self) -> usize {
+        size_of::<Self>() + node_bytes(self)
+    }
+}
+
+impl Program {
+    pub fn estimated_bytes(&self) -> usize {
+        size_of::<Self>()
+            + size_of_val(self.instructions())
+            + self
+                .instructions()
+                .iter()
+                .map(Instruction::heap_bytes)
+                .sum::<usize>()
+            + size_of_val(self.outputs())
+    }
+}
+
+// Each distinct node is one Rc allocation: the strong and weak counts, the
+// RefCell borrow flag and the operation itself, plus anything it owns on the
+// heap. Operand handles are part of their parent's operation.
+fn node_bytes(root: &DynScalar) -> usize {
+    let mut visited = HashSet::new();
+    let mut stack = vec![root.clone()];
+    let mut bytes = 0;
+    while let Some(node) = stack.pop() {
+        if !visited.insert(node.id()) {
+            continue;
+        }
+        bytes += 2 * size_of::<usize>()
+            + size_of_val(&*node.scalar.node)
+            + node.scalar.node.borrow().heap_bytes();
+        stack.extend(node.children());
+    }
+    bytes
+}
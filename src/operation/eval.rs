@@ -0,0 +1,262 @@
+use super::{DynScalar, Environment, OpKind, Operation, Scalar};
+
+// What try_execute does when a divisor is zero. Error stops evaluation,
+// PropagateNaN yields NaN in place of an infinity, and Clamp yields the
+// largest finite value of the right sign, or 0 for 0 / 0.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EvalPolicy {
+    #[default]
+    Error,
+    PropagateNaN,
+    Clamp,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EvalError {
+    DivisionByZero { expression: String },
+    UnboundVariable { name: String },
+    NotRational { expression: String },
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::DivisionByZero { expression } => {
+                write!(f, "division by zero in {}", expression)
+            }
+            EvalError::UnboundVariable { name } => write!(f, "unbound variable {}", name),
+            EvalError::NotRational { expression } => {
+                write!(f, "{} has no exact rational value", expression)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+// Everything that changes while a Program or FrozenExpr runs: the value
+// buffer and one stream per noise source, in the order the sources run.
+// Neither holds any of it themselves, so threads can share one and run it
+// at the same time, each with a context of its own.
+#[derive(Clone, Debug, Default)]
+pub struct EvalContext {
+    pub(crate) values: Vec<f32>,
+    pub(crate) noise: Vec<u64>,
+}
+
+impl EvalContext {
+    // Seeded streams start from their seed, the others from fresh entropy.
+    pub(crate) fn new(seeds: impl IntoIterator<Item = Option<u64>>) -> Self {
+        Self {
+            values: Vec::new(),
+            noise: seeds
+                .into_iter()
+                .map(|seed| seed.unwrap_or_else(super::entropy))
+                .collect(),
+        }
+    }
+
+    // Under a program seed each stream is keyed by that seed and the id of
+    // its source: the source's own seed, or for an unseeded one the id its
+    // node drew when it was made. Neither depends on the source's place in
+    // the graph, so a source draws the same samples whatever else the graph
+    // holds.
+    pub(crate) fn seeded(seed: u64, sources: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            values: Vec::new(),
+            noise: sources
+                .into_iter()
+                .map(|source| super::stream(seed, source))
+                .collect(),
+        }
+    }
+
+    pub fn noise_sources(&self) -> usize {
+        self.noise.len()
+    }
+}
+
+impl EvalPolicy {
+    // `expression` renders the division for the error and is only called
+    // when there is one.
+    pub(super) fn divide(
+        self,
+        a: f32,
+        b: f32,
+        expression: impl FnOnce() -> String,
+    ) -> Result<f32, EvalError> {
+        if b != 0. {
+            return Ok(a / b);
+        }
+        match self {
+            EvalPolicy::Error => Err(EvalError::DivisionByZero {
+                expression: expression(),
+            }),
+            EvalPolicy::PropagateNaN => Ok(f32::NAN),
+            EvalPolicy::Clamp if a == 0. => Ok(0.),
+            EvalPolicy::Clamp => Ok((a / b).clamp(-f32::MAX, f32::MAX)),
+        }
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn try_execute(&self) -> Result<f32, EvalError> {
+        self.try_execute_with(&Environment::new(), EvalPolicy::default())
+    }
+
+    pub fn try_execute_with(
+        &self,
+        env: &Environment,
+        policy: EvalPolicy,
+    ) -> Result<f32, EvalError> {
+        evaluate(&self.clone().into_dyn(), env, Some(policy))
+    }
+}
+
+impl DynScalar {
+    pub fn try_execute(&self) -> Result<f32, EvalError> {
+        self.scalar.try_execute()
+    }
+
+    pub fn try_execute_with(
+        &self,
+        env: &Environment,
+        policy: EvalPolicy,
+    ) -> Result<f32, EvalError> {
+        self.scalar.try_execute_with(env, policy)
+    }
+}
+
+enum Task {
+    Visit(DynScalar),
+    Apply(DynScalar),
+    // A select whose condition is on top of the value stack.
+    Choose(DynScalar, DynScalar),
+    // A select whose value, the chosen arm's, is on top of the value stack.
+    Chosen(DynScalar),
+}
+
+// The arithmetic a graph can be evaluated in. Leaves and binary operations
+// are up to the value type; the evaluator handles selects and aliases.
+pub(super) trait Value: Clone {
+    fn leaf(
+        node: &DynScalar,
+        env: &Environment,
+        policy: Option<EvalPolicy>,
+    ) -> Result<Self, EvalError>;
+    fn binary(
+        kind: OpKind,
+        a: Self,
+        b: Self,
+        node: &DynScalar,
+        policy: Option<EvalPolicy>,
+    ) -> Result<Self, EvalError>;
+    // Whether a select condition picks its first arm.
+    fn holds(&self) -> bool;
+}
+
+impl Value for f32 {
+    // Without a policy an unbound variable panics, as in execute().
+    fn leaf(
+        node: &DynScalar,
+        env: &Environment,
+        policy: Option<EvalPolicy>,
+    ) -> Result<Self, EvalError> {
+        let operation = node.scalar.operation.borrow();
+        match policy {
+            Some(policy) => operation.try_execute(env, policy),
+            None => Ok(operation.execute(env)),
+        }
+    }
+
+    // Without a policy division follows f32.
+    fn binary(
+        kind: OpKind,
+        a: Self,
+        b: Self,
+        node: &DynScalar,
+        policy: Option<EvalPolicy>,
+    ) -> Result<Self, EvalError> {
+        Ok(match kind {
+            OpKind::Add => a + b,
+            OpKind::Sub => a - b,
+            OpKind::Mul => a * b,
+            OpKind::Div => match policy {
+                Some(policy) => policy.divide(a, b, || node.to_string())?,
+                None => a / b,
+            },
+            OpKind::Compare(comparison) => comparison.apply(a, b),
+            kind => unreachable!("{} is not a binary operation", kind),
+        })
+    }
+
+    fn holds(&self) -> bool {
+        *self != 0.
+    }
+}
+
+// Evaluates with an explicit stack, so depth is limited by the heap rather
+// than the call stack. Nodes are visited in the order the ops' own execute()
+// visits them, including evaluating only the select arm that is taken and
+// shared nodes once per use.
+pub(super) fn evaluate<V: Value>(
+    root: &DynScalar,
+    env: &Environment,
+    policy: Option<EvalPolicy>,
+) -> Result<V, EvalError> {
+    evaluate_observed(root, env, policy, |_, _| {})
+}
+
+// As evaluate, handing `observe` each node and its value as it is computed.
+pub(super) fn evaluate_observed<V: Value>(
+    root: &DynScalar,
+    env: &Environment,
+    policy: Option<EvalPolicy>,
+    mut observe: impl FnMut(&DynScalar, &V),
+) -> Result<V, EvalError> {
+    let mut tasks = vec![Task::Visit(root.clone())];
+    let mut values: Vec<V> = Vec::new();
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(node) => {
+                let mut children = node.children();
+                if children.is_empty() {
+                    let value = V::leaf(&node, env, policy)?;
+                    observe(&node, &value);
+                    values.push(value);
+                } else if node.kind() == OpKind::Select {
+                    let otherwise = children.pop().unwrap();
+                    let then = children.pop().unwrap();
+                    tasks.push(Task::Chosen(node));
+                    tasks.push(Task::Choose(then, otherwise));
+                    tasks.push(Task::Visit(children.pop().unwrap()));
+                } else {
+                    tasks.push(Task::Apply(node));
+                    tasks.extend(children.into_iter().rev().map(Task::Visit));
+                }
+            }
+            Task::Choose(then, otherwise) => {
+                let condition = values.pop().unwrap();
+                tasks.push(Task::Visit(if condition.holds() {
+                    then
+                } else {
+                    otherwise
+                }));
+            }
+            Task::Chosen(node) => observe(&node, values.last().unwrap()),
+            Task::Apply(node) => {
+                let b = values.pop().unwrap();
+                let value = match node.kind() {
+                    OpKind::Alias | OpKind::Provenance(_) => b,
+                    kind => {
+                        let a = values.pop().unwrap();
+                        V::binary(kind, a, b, &node, policy)?
+                    }
+                };
+                observe(&node, &value);
+                values.push(value);
+            }
+        }
+    }
+    Ok(values.pop().unwrap())
+}
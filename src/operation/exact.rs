@@ -0,0 +1,88 @@
+use num_rational::BigRational;
+use num_traits::{One, Zero};
+
+use super::{
+    eval::{evaluate, Value},
+    DynScalar, Environment, EvalError, EvalPolicy, OpKind, Operation, Scalar,
+};
+
+// Every finite f32 is a rational, so constants and inputs convert exactly
+// and nothing after that rounds: 1 / 3 * 3 is 1. Symbols like pi have no
+// rational value and fail, as do infinite and NaN inputs. Division by zero
+// is always an error; there is no policy for it.
+impl Value for BigRational {
+    fn leaf(
+        node: &DynScalar,
+        env: &Environment,
+        _policy: Option<EvalPolicy>,
+    ) -> Result<Self, EvalError> {
+        let value = match node.kind() {
+            OpKind::Variable(name) => env.get(&name).ok_or(EvalError::UnboundVariable { name })?,
+            OpKind::Symbol(_) => {
+                return Err(EvalError::NotRational {
+                    expression: node.to_string(),
+                })
+            }
+            _ => node.scalar.operation.borrow().execute(env),
+        };
+        BigRational::from_float(value).ok_or_else(|| EvalError::NotRational {
+            expression: node.to_string(),
+        })
+    }
+
+    fn binary(
+        kind: OpKind,
+        a: Self,
+        b: Self,
+        node: &DynScalar,
+        _policy: Option<EvalPolicy>,
+    ) -> Result<Self, EvalError> {
+        Ok(match kind {
+            OpKind::Add => a + b,
+            OpKind::Sub => a - b,
+            OpKind::Mul => a * b,
+            OpKind::Div if b.is_zero() => {
+                return Err(EvalError::DivisionByZero {
+                    expression: node.to_string(),
+                })
+            }
+            OpKind::Div => a / b,
+            OpKind::Compare(comparison) if comparison.holds(&a, &b) => BigRational::one(),
+            OpKind::Compare(_) => BigRational::zero(),
+            kind => unreachable!("{} is not a binary operation", kind),
+        })
+    }
+
+    fn holds(&self) -> bool {
+        !self.is_zero()
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn execute_exact(&self) -> BigRational {
+        self.execute_exact_with(&Environment::new())
+    }
+
+    pub fn execute_exact_with(&self, env: &Environment) -> BigRational {
+        self.try_execute_exact_with(env)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_execute_exact_with(&self, env: &Environment) -> Result<BigRational, EvalError> {
+        evaluate(&self.clone().into_dyn(), env, None)
+    }
+}
+
+impl DynScalar {
+    pub fn execute_exact(&self) -> BigRational {
+        self.scalar.execute_exact()
+    }
+
+    pub fn execute_exact_with(&self, env: &Environment) -> BigRational {
+        self.scalar.execute_exact_with(env)
+    }
+
+    pub fn try_execute_exact_with(&self, env: &Environment) -> Result<BigRational, EvalError> {
+        self.scalar.try_execute_exact_with(env)
+    }
+}
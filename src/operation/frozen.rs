@@ -0,0 +1,233 @@
+use std::{cell::RefCell, collections::HashMap, slice::IterMut, sync::Mutex};
+
+use super::{
+    analysis::postorder, Comparison, Distribution, DynScalar, Environment, EvalContext, OpKind,
+    Operation, Scalar,
+};
+
+// An immutable snapshot of a graph: plain nodes in evaluation order, with
+// no RefCell to borrow and no pointers to chase. Symbols are resolved to
+// constants, aliases to their targets, and noise restarts from its seed as
+// it does when compiled. call() evaluates in a buffer of the calling
+// thread's own, so threads never wait on each other, except that noise
+// streams carry on from call to call and are kept behind a lock; threads
+// sampling noisy expressions at the same time should each hold a context
+// and use call_in().
+pub struct FrozenExpr {
+    pub(super) nodes: Vec<Node>,
+    variables: Vec<String>,
+    pub(super) root: usize,
+    // None when there are no noise nodes.
+    streams: Option<Mutex<Vec<u64>>>,
+}
+
+thread_local! {
+    // The value buffer call() evaluates in, reused from call to call.
+    static CONTEXT: RefCell<EvalContext> = RefCell::default();
+}
+
+pub(super) enum Node {
+    Constant(f32),
+    Variable(usize),
+    // The distribution, seed and source id, as for a noise instruction.
+    Noise(Distribution, Option<u64>, u64),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Select(usize, usize, usize),
+    Compare(Comparison, usize, usize),
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn freeze(&self) -> FrozenExpr {
+        self.clone().into_dyn().freeze()
+    }
+}
+
+impl DynScalar {
+    pub fn freeze(&self) -> FrozenExpr {
+        let (nodes, variables, root) = flatten(self);
+        let mut frozen = FrozenExpr {
+            nodes,
+            variables,
+            root,
+            streams: None,
+        };
+        let EvalContext { noise, .. } = frozen.context();
+        if !noise.is_empty() {
+            frozen.streams = Some(Mutex::new(noise));
+        }
+        frozen
+    }
+}
+
+impl Node {
+    pub(super) fn operands(&self) -> Vec<usize> {
+        match *self {
+            Node::Add(a, b) | Node::Sub(a, b) | Node::Mul(a, b) | Node::Div(a, b) => vec![a, b],
+            Node::Select(c, a, b) => vec![c, a, b],
+            Node::Compare(_, a, b) => vec![a, b],
+            _ => Vec::new(),
+        }
+    }
+
+    // Noise draws from the next of `noise`, the streams of the noise nodes
+    // still to come.
+    pub(super) fn evaluate(&self, values: &[f32], args: &[f32], noise: &mut IterMut<u64>) -> f32 {
+        match *self {
+            Node::Constant(value) => value,
+            Node::Variable(i) => args[i],
+            Node::Noise(distribution, ..) => {
+                distribution.sample(noise.next().expect("context is for another expression"))
+            }
+            Node::Add(a, b) => values[a] + values[b],
+            Node::Sub(a, b) => values[a] - values[b],
+            Node::Mul(a, b) => values[a] * values[b],
+            Node::Div(a, b) => values[a] / values[b],
+            Node::Select(c, a, b) => select(values[c], values[a], values[b]),
+            Node::Compare(comparison, a, b) => comparison.apply(values[a], values[b]),
+        }
+    }
+}
+
+pub(super) fn select(condition: f32, then: f32, otherwise: f32) -> f32 {
+    if condition != 0. {
+        then
+    } else {
+        otherwise
+    }
+}
+
+// The distinct nodes in evaluation order, the variables in order of first
+// use, and the index of the root.
+pub(super) fn flatten(expr: &DynScalar) -> (Vec<Node>, Vec<String>, usize) {
+    let mut index: HashMap<usize, usize> = HashMap::new();
+    let mut nodes = Vec::new();
+    let mut variables: Vec<String> = Vec::new();
+    for node in postorder(expr) {
+        let operands: Vec<usize> = node
+            .children()
+            .iter()
+            .map(|child| index[&child.id()])
+            .collect();
+        let frozen = match node.kind() {
+            OpKind::Alias | OpKind::Provenance(_) => {
+                index.insert(node.id(), operands[0]);
+                continue;
+            }
+            OpKind::Constant(value) => Node::Constant(value),
+            OpKind::Symbol(symbol) => Node::Constant(symbol.value() as f32),
+            OpKind::Variable(name) => match variables.iter().position(|v| *v == name) {
+                Some(i) => Node::Variable(i),
+                None => {
+                    variables.push(name);
+                    Node::Variable(variables.len() - 1)
+                }
+            },
+            OpKind::Noise(distribution, seed) => Node::Noise(
+                distribution,
+                seed,
+                node.noise_source().expect("noise nodes have a source"),
+            ),
+            OpKind::Wildcard(name) => panic!("cannot freeze pattern wildcard ?{}", name),
+            OpKind::Add => Node::Add(operands[0], operands[1]),
+            OpKind::Sub => Node::Sub(operands[0], operands[1]),
+            OpKind::Mul => Node::Mul(operands[0], operands[1]),
+            OpKind::Div => Node::Div(operands[0], operands[1]),
+            OpKind::Select => Node::Select(operands[0], operands[1], operands[2]),
+            OpKind::Compare(comparison) => Node::Compare(comparison, operands[0], operands[1]),
+        };
+        index.insert(node.id(), nodes.len());
+        nodes.push(frozen);
+    }
+    let root = index[&expr.id()];
+    (nodes, variables, root)
+}
+
+impl FrozenExpr {
+    // Variables in the order call() takes them.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn call(&self, args: &[f32]) -> f32 {
+        self.with_context(|context| self.call_in(context, args))
+    }
+
+    // Lends out the calling thread's buffer, with the expression's noise
+    // streams in it for as long as `f` runs.
+    pub(super) fn with_context<R>(&self, f: impl FnOnce(&mut EvalContext) -> R) -> R {
+        CONTEXT.with(|context| {
+            let mut context = context.borrow_mut();
+            let Some(streams) = &self.streams else {
+                return f(&mut context);
+            };
+            let mut streams = streams.lock().unwrap();
+            std::mem::swap(&mut context.noise, &mut streams);
+            let result = f(&mut context);
+            std::mem::swap(&mut context.noise, &mut streams);
+            result
+        })
+    }
+
+    pub fn context(&self) -> EvalContext {
+        EvalContext::new(self.noise_seeds())
+    }
+
+    // As Program::context_with_seed().
+    pub fn context_with_seed(&self, seed: u64) -> EvalContext {
+        EvalContext::seeded(
+            seed,
+            self.nodes.iter().filter_map(|node| match *node {
+                Node::Noise(_, _, source) => Some(source),
+                _ => None,
+            }),
+        )
+    }
+
+    fn noise_seeds(&self) -> impl Iterator<Item = Option<u64>> + '_ {
+        self.nodes.iter().filter_map(|node| match *node {
+            Node::Noise(_, seed, _) => Some(seed),
+            _ => None,
+        })
+    }
+
+    pub fn call_in(&self, context: &mut EvalContext, args: &[f32]) -> f32 {
+        assert_eq!(
+            args.len(),
+            self.variables.len(),
+            "expected values for {:?}",
+            self.variables
+        );
+        let EvalContext { values, noise } = context;
+        let mut streams = noise.iter_mut();
+        values.clear();
+        for node in &self.nodes {
+            values.push(node.evaluate(values, args, &mut streams));
+        }
+        values[self.root]
+    }
+
+    pub fn execute_in(&self, context: &mut EvalContext, env: &Environment) -> f32 {
+        self.call_in(context, &self.arguments(env))
+    }
+
+    pub fn execute(&self) -> f32 {
+        self.execute_with(&Environment::new())
+    }
+
+    pub fn execute_with(&self, env: &Environment) -> f32 {
+        self.call(&self.arguments(env))
+    }
+
+    pub(super) fn arguments(&self, env: &Environment) -> Vec<f32> {
+        self.variables
+            .iter()
+            .map(|name| {
+                env.get(name)
+                    .unwrap_or_else(|| panic!("unbound variable {}", name))
+            })
+            .collect()
+    }
+}
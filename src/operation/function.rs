@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{DynScalar, OpKind, Operation, Scalar};
+
+#[derive(Clone)]
+pub struct Function {
+    parameters: Vec<String>,
+    inputs: Vec<DynScalar>,
+    body: DynScalar,
+}
+
+impl Function {
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    // The expressions that fed each parameter in the graph the function was
+    // extracted from; calling with them rebuilds the original region.
+    pub fn inputs(&self) -> &[DynScalar] {
+        &self.inputs
+    }
+
+    pub fn body(&self) -> &DynScalar {
+        &self.body
+    }
+
+    pub fn call(&self, arguments: &[DynScalar]) -> DynScalar {
+        assert_eq!(
+            arguments.len(),
+            self.parameters.len(),
+            "function takes {} arguments",
+            self.parameters.len()
+        );
+        let bindings: HashMap<&str, &DynScalar> = self
+            .parameters
+            .iter()
+            .map(String::as_str)
+            .zip(arguments)
+            .collect();
+        inline(&self.body, &bindings, &mut HashMap::new())
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn extract_subgraph(&self, nodes: &[DynScalar]) -> Option<Function> {
+        self.clone().into_dyn().extract_subgraph(nodes)
+    }
+}
+
+impl DynScalar {
+    // Outlines the selected nodes into a Function. The selection must have a
+    // single root reachable from self; every edge leaving the selection
+    // becomes a parameter.
+    pub fn extract_subgraph(&self, nodes: &[DynScalar]) -> Option<Function> {
+        let selected: HashSet<usize> = nodes.iter().map(DynScalar::id).collect();
+        let consumed: HashSet<usize> = nodes
+            .iter()
+            .flat_map(|node| node.children())
+            .map(|child| child.id())
+            .filter(|id| selected.contains(id))
+            .collect();
+        let mut roots = nodes.iter().filter(|node| !consumed.contains(&node.id()));
+        let root = roots.next()?;
+        if roots.any(|other| other.id() != root.id()) || !reaches(self, root.id()) {
+            return None;
+        }
+
+        let mut taken = HashSet::new();
+        for node in nodes {
+            if let OpKind::Variable(name) = node.kind() {
+                taken.insert(name);
+            }
+        }
+        let mut outliner = Outliner {
+            selected,
+            taken,
+            parameters: Vec::new(),
+            inputs: Vec::new(),
+            copies: HashMap::new(),
+        };
+        let body = outliner.copy(root);
+        Some(Function {
+            parameters: outliner.parameters,
+            inputs: outliner.inputs,
+            body,
+        })
+    }
+}
+
+struct Outliner {
+    selected: HashSet<usize>,
+    taken: HashSet<String>,
+    parameters: Vec<String>,
+    inputs: Vec<DynScalar>,
+    copies: HashMap<usize, DynScalar>,
+}
+
+impl Outliner {
+    fn copy(&mut self, node: &DynScalar) -> DynScalar {
+        if let Some(copy) = self.copies.get(&node.id()) {
+            return copy.clone();
+        }
+        let copy = if self.selected.contains(&node.id()) {
+            let children: Vec<DynScalar> = node.children().iter().map(|c| self.copy(c)).collect();
+            DynScalar::from_kind(node.kind(), &children)
+        } else {
+            let name = (0..)
+                .map(|n| format!("arg{}", n))
+                .find(|name| !self.taken.contains(name))
+                .unwrap();
+            self.taken.insert(name.clone());
+            self.parameters.push(name.clone());
+            self.inputs.push(node.clone());
+            Scalar::variable(name).into_dyn()
+        };
+        self.copies.insert(node.id(), copy.clone());
+        copy
+    }
+}
+
+fn reaches(node: &DynScalar, target: usize) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        if node.id() == target {
+            return true;
+        }
+        if visited.insert(node.id()) {
+            stack.extend(node.children());
+        }
+    }
+    false
+}
+
+fn inline(
+    node: &DynScalar,
+    bindings: &HashMap<&str, &DynScalar>,
+    copies: &mut HashMap<usize, DynScalar>,
+) -> DynScalar {
+    if let Some(copy) = copies.get(&node.id()) {
+        return copy.clone();
+    }
+    let kind = node.kind();
+    let copy = match &kind {
+        OpKind::Variable(name) if bindings.contains_key(name.as_str()) => {
+            bindings[name.as_str()].clone()
+        }
+        _ => {
+            let children: Vec<DynScalar> = node
+                .children()
+                .iter()
+                .map(|child| inline(child, bindings, copies))
+                .collect();
+            DynScalar::from_kind(kind, &children)
+        }
+    };
+    copies.insert(node.id(), copy.clone());
+    copy
+}
@@ -0,0 +1,237 @@
+use std::fmt::Write;
+
+use wgpu::util::DeviceExt;
+
+use super::Program;
+
+const WORKGROUP_SIZE: u32 = 64;
+// The most workgroups a dispatch may have along one dimension.
+const MAX_GROUPS: u32 = 65535;
+
+// A program compiled to a WGSL compute pipeline, one invocation per row.
+// Rows and results are laid out as for Program::run_batch(). The GPU's
+// float arithmetic is not IEEE exact: division may be off by a few ulps and
+// NaN and infinities need not be preserved, so results can differ from the
+// CPU's in the last bits.
+pub struct GpuProgram {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    variables: Vec<String>,
+    outputs: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GpuError {
+    Unsupported(String),
+    NoAdapter,
+    Device(String),
+}
+
+impl std::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuError::Unsupported(instruction) => write!(f, "cannot lower `{}`", instruction),
+            GpuError::NoAdapter => write!(f, "no GPU adapter available"),
+            GpuError::Device(message) => write!(f, "GPU device error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+fn device_error(error: impl std::fmt::Display) -> GpuError {
+    GpuError::Device(error.to_string())
+}
+
+// `eval` from to_wgsl(), called by an entry point that reads its row of
+// `inputs` and writes its row of `outputs`. Large batches use a second
+// dispatch dimension.
+fn kernel(program: &Program, width: usize) -> Result<String, GpuError> {
+    let eval = program
+        .to_wgsl()
+        .map_err(|unsupported| GpuError::Unsupported(unsupported.instruction))?;
+    let outputs = program.outputs().len();
+    let arguments: Vec<String> = (0..width)
+        .map(|i| format!("inputs[row * {}u + {}u]", width, i))
+        .collect();
+    let mut shader = eval;
+    write!(
+        shader,
+        "
+@group(0) @binding(0) var<storage, read> inputs: array<f32>;
+@group(0) @binding(1) var<storage, read_write> outputs: array<f32>;
+
+@compute @workgroup_size({size})
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {{
+    let row = id.y * groups.x * {size}u + id.x;
+    // Keeps `inputs` in the layout when the program has no variables.
+    _ = arrayLength(&inputs);
+    if row >= arrayLength(&outputs) / {outputs}u {{
+        return;
+    }}
+    let result = eval({arguments});
+",
+        size = WORKGROUP_SIZE,
+        outputs = outputs,
+        arguments = arguments.join(", ")
+    )
+    .unwrap();
+    if outputs == 1 {
+        writeln!(shader, "    outputs[row] = result;").unwrap();
+    } else {
+        for i in 0..outputs {
+            writeln!(
+                shader,
+                "    outputs[row * {}u + {}u] = result[{}];",
+                outputs, i, i
+            )
+            .unwrap();
+        }
+    }
+    shader.push_str("}\n");
+    Ok(shader)
+}
+
+impl GpuProgram {
+    // Fails without touching the GPU if the program cannot be expressed in
+    // WGSL, e.g. because it samples noise.
+    pub fn new(program: &Program) -> Result<Self, GpuError> {
+        let variables = program.variables();
+        let source = kernel(program, variables.len())?;
+        pollster::block_on(async {
+            let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions::default())
+                .await
+                .ok_or(GpuError::NoAdapter)?;
+            let (device, queue) = adapter
+                .request_device(&wgpu::DeviceDescriptor::default(), None)
+                .await
+                .map_err(device_error)?;
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("eval"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("eval"),
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+            if let Some(error) = device.pop_error_scope().await {
+                return Err(device_error(error));
+            }
+            Ok(Self {
+                device,
+                queue,
+                pipeline,
+                variables,
+                outputs: program.outputs().len(),
+            })
+        })
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    // Splits the batch into as few dispatches as the device's buffer limits
+    // allow.
+    pub fn run_batch(&self, rows: &[&[f32]]) -> Result<Vec<f32>, GpuError> {
+        let width = self.variables.len();
+        for row in rows {
+            assert_eq!(row.len(), width, "expected {} columns", width);
+        }
+        if self.outputs == 0 || rows.is_empty() {
+            return Ok(Vec::new());
+        }
+        let limit = self.device.limits().max_storage_buffer_binding_size as usize;
+        let per_dispatch = (limit / (4 * width.max(self.outputs))).max(1);
+        let mut results = Vec::with_capacity(rows.len() * self.outputs);
+        for chunk in rows.chunks(per_dispatch) {
+            results.extend(self.dispatch(chunk)?);
+        }
+        Ok(results)
+    }
+
+    fn dispatch(&self, rows: &[&[f32]]) -> Result<Vec<f32>, GpuError> {
+        // Bindings cannot be empty, so programs without variables still get
+        // an input buffer.
+        let mut inputs: Vec<u8> = rows
+            .iter()
+            .flat_map(|row| row.iter().flat_map(|value| value.to_le_bytes()))
+            .collect();
+        if inputs.is_empty() {
+            inputs.resize(4, 0);
+        }
+        let size = (rows.len() * self.outputs * 4) as u64;
+        let input = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("inputs"),
+                contents: &inputs,
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let output = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("outputs"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("eval"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: input.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: output.as_entire_binding(),
+                },
+            ],
+        });
+
+        let groups = (rows.len() as u32).div_ceil(WORKGROUP_SIZE);
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups.min(MAX_GROUPS), groups.div_ceil(MAX_GROUPS), 1);
+        }
+        encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(device_error)?
+            .map_err(device_error)?;
+        let results = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        readback.unmap();
+        Ok(results)
+    }
+}
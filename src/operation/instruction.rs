@@ -1,66 +1,357 @@
+use std::sync::Arc;
+
+use super::{Comparison, Distribution, Environment};
+
 #[derive(Clone)]
 pub struct Instruction {
     op: Box<dyn Op>,
     ret: usize,
+    // Where the value came from, copied from the graph's provenance nodes.
+    provenance: Option<Arc<str>>,
+}
+
+// What an instruction does, with the registers it reads; the register it
+// writes is the instruction's ret(). Store writes and Reload reads a memory
+// cell, numbered after the registers.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Opcode {
+    Constant(f32),
+    Load(String),
+    Noise(Distribution, Option<u64>),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Store(usize),
+    Reload(usize),
+    Select(usize, usize, usize),
+    Compare(Comparison, usize, usize),
+}
+
+impl Opcode {
+    // As instructions print it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Opcode::Constant(_) => "constant",
+            Opcode::Load(_) => "load",
+            Opcode::Noise(..) => "noise",
+            Opcode::Add(..) => "add",
+            Opcode::Sub(..) => "sub",
+            Opcode::Mul(..) => "mul",
+            Opcode::Div(..) => "div",
+            Opcode::Store(_) => "store",
+            Opcode::Reload(_) => "reload",
+            Opcode::Select(..) => "select",
+            Opcode::Compare(comparison, ..) => comparison.name(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<usize> {
+        match *self {
+            Opcode::Constant(_) | Opcode::Load(_) | Opcode::Noise(..) => Vec::new(),
+            Opcode::Store(a) | Opcode::Reload(a) => vec![a],
+            Opcode::Add(a, b)
+            | Opcode::Sub(a, b)
+            | Opcode::Mul(a, b)
+            | Opcode::Div(a, b)
+            | Opcode::Compare(_, a, b) => vec![a, b],
+            Opcode::Select(c, a, b) => vec![c, a, b],
+        }
+    }
+}
+
+impl Instruction {
+    pub fn from_opcode(opcode: Opcode, ret: usize) -> Instruction {
+        match opcode {
+            Opcode::Constant(value) => constant(value, ret),
+            Opcode::Load(name) => load(name, ret),
+            Opcode::Noise(distribution, seed) => noise(distribution, seed, ret),
+            Opcode::Add(a, b) => add(a, b, ret),
+            Opcode::Sub(a, b) => sub(a, b, ret),
+            Opcode::Mul(a, b) => mul(a, b, ret),
+            Opcode::Div(a, b) => div(a, b, ret),
+            Opcode::Store(a) => store(a, ret),
+            Opcode::Reload(a) => reload(a, ret),
+            Opcode::Select(c, a, b) => select(c, a, b, ret),
+            Opcode::Compare(comparison, a, b) => compare(comparison, a, b, ret),
+        }
+    }
+
+    pub fn opcode(&self) -> Opcode {
+        self.op.opcode()
+    }
+
+    pub fn ret(&self) -> usize {
+        self.ret
+    }
+
+    pub(crate) fn execute(&self, slots: &[f32], env: &Environment) -> f32 {
+        self.op.execute(slots, env)
+    }
+
+    pub fn operands(&self) -> Vec<usize> {
+        self.op.operands()
+    }
+
+    pub(crate) fn constant(&self) -> Option<f32> {
+        self.op.constant()
+    }
+
+    pub(crate) fn foldable(&self) -> bool {
+        self.op.foldable()
+    }
+
+    pub(crate) fn deterministic(&self) -> bool {
+        self.op.deterministic()
+    }
+
+    // The distribution and seed of a noise instruction.
+    pub(crate) fn noise(&self) -> Option<(Distribution, Option<u64>)> {
+        self.op.noise()
+    }
+
+    // The id of the node a noise instruction was compiled from, which keys
+    // its stream under a program seed.
+    pub(crate) fn noise_source(&self) -> Option<u64> {
+        self.op.noise_source()
+    }
+
+    pub(crate) fn spill(&self) -> Option<Spill> {
+        self.op.spill()
+    }
+
+    // An op prints its opcode, operands and payload, which is exactly what
+    // makes two instructions compute the same value.
+    pub(crate) fn key(&self) -> String {
+        self.op.to_string()
+    }
+
+    // A note shared between instructions is counted with each of them.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        std::mem::size_of_val(&*self.op)
+            + self.op.heap_bytes()
+            + self.provenance.as_ref().map_or(0, |note| note.len())
+    }
+
+    pub(crate) fn remap(&self, registers: impl Fn(usize) -> usize) -> Instruction {
+        Instruction {
+            op: self.op.remap(&registers),
+            ret: registers(self.ret),
+            provenance: self.provenance.clone(),
+        }
+    }
+
+    // For when the result may land in one of the operand registers.
+    pub(crate) fn rename(&self, operands: impl Fn(usize) -> usize, ret: usize) -> Instruction {
+        Instruction {
+            op: self.op.remap(&operands),
+            ret,
+            provenance: self.provenance.clone(),
+        }
+    }
+
+    pub fn provenance(&self) -> Option<&str> {
+        self.provenance.as_deref()
+    }
+
+    // Notes from several nodes that compiled to the same instruction are
+    // kept in the order they were added, separated by "; ".
+    pub(crate) fn annotate(&mut self, provenance: &str) {
+        self.provenance = Some(match &self.provenance {
+            Some(existing) if existing.split("; ").any(|note| note == provenance) => return,
+            Some(existing) => format!("{}; {}", existing, provenance).into(),
+            None => provenance.into(),
+        });
+    }
+
+    pub(crate) fn with_provenance_of(mut self, other: &Instruction) -> Instruction {
+        self.provenance = other.provenance.clone();
+        self
+    }
 }
 
+// The register a store reads or the memory cell a reload reads, for
+// interpreters that keep memory apart from the registers. Cheaper than
+// building an Opcode for every instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Spill {
+    Store(usize),
+    Reload(usize),
+}
+
+// Returned by code generators for instructions the target cannot express.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Unsupported {
+    pub instruction: String,
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot lower `{}`", self.instruction)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "%{}: {}", self.ret, self.op)
+        write!(f, "%{}: {}", self.ret, self.op)?;
+        match &self.provenance {
+            Some(provenance) => write!(f, "  ; {}", provenance),
+            None => Ok(()),
+        }
     }
 }
 
 pub fn constant(value: f32, ret: usize) -> Instruction {
     Instruction {
-        op: Box::new(ConstantOp {
-            value,
+        op: Box::new(ConstantOp { value }),
+        ret,
+        provenance: None,
+    }
+}
+
+pub fn load(name: String, ret: usize) -> Instruction {
+    Instruction {
+        op: Box::new(LoadOp { name }),
+        ret,
+        provenance: None,
+    }
+}
+
+// Noise that did not come from a graph node is a source of its own.
+pub fn noise(distribution: Distribution, seed: Option<u64>, ret: usize) -> Instruction {
+    sourced_noise(distribution, seed, seed.unwrap_or_else(super::entropy), ret)
+}
+
+pub fn sourced_noise(
+    distribution: Distribution,
+    seed: Option<u64>,
+    source: u64,
+    ret: usize,
+) -> Instruction {
+    Instruction {
+        op: Box::new(NoiseOp {
+            distribution,
+            seed,
+            source,
         }),
-        ret
+        ret,
+        provenance: None,
     }
 }
 
 pub fn add(a: usize, b: usize, ret: usize) -> Instruction {
     Instruction {
-        op: Box::new(AddOp {
-            a,
-            b,
-        }),
-        ret
+        op: Box::new(AddOp { a, b }),
+        ret,
+        provenance: None,
     }
 }
 
 pub fn sub(a: usize, b: usize, ret: usize) -> Instruction {
     Instruction {
-        op: Box::new(SubOp {
-            a,
-            b,
-        }),
-        ret
+        op: Box::new(SubOp { a, b }),
+        ret,
+        provenance: None,
     }
 }
 
 pub fn mul(a: usize, b: usize, ret: usize) -> Instruction {
     Instruction {
-        op: Box::new(MulOp {
-            a,
-            b,
-        }),
-        ret
+        op: Box::new(MulOp { a, b }),
+        ret,
+        provenance: None,
     }
 }
 
 pub fn div(a: usize, b: usize, ret: usize) -> Instruction {
     Instruction {
-        op: Box::new(DivOp {
-            a,
-            b,
+        op: Box::new(DivOp { a, b }),
+        ret,
+        provenance: None,
+    }
+}
+
+pub fn select(condition: usize, then: usize, otherwise: usize, ret: usize) -> Instruction {
+    Instruction {
+        op: Box::new(SelectOp {
+            condition,
+            then,
+            otherwise,
         }),
-        ret
+        ret,
+        provenance: None,
+    }
+}
+
+pub fn compare(comparison: Comparison, a: usize, b: usize, ret: usize) -> Instruction {
+    Instruction {
+        op: Box::new(CompareOp { comparison, a, b }),
+        ret,
+        provenance: None,
+    }
+}
+
+// Spill code. Memory cells are numbered after the registers, so to anything
+// that does not care where a value lives these are plain copies.
+pub fn store(a: usize, ret: usize) -> Instruction {
+    Instruction {
+        op: Box::new(StoreOp { a }),
+        ret,
+        provenance: None,
     }
 }
 
-trait Op : std::fmt::Display {
+pub fn reload(a: usize, ret: usize) -> Instruction {
+    Instruction {
+        op: Box::new(ReloadOp { a }),
+        ret,
+        provenance: None,
+    }
+}
+
+trait Op: std::fmt::Display + Send + Sync {
     fn clone_box(&self) -> Box<dyn Op>;
+    fn execute(&self, slots: &[f32], env: &Environment) -> f32;
+    fn opcode(&self) -> Opcode;
+
+    fn operands(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    fn constant(&self) -> Option<f32> {
+        None
+    }
+
+    fn foldable(&self) -> bool {
+        true
+    }
+
+    fn deterministic(&self) -> bool {
+        true
+    }
+
+    fn noise(&self) -> Option<(Distribution, Option<u64>)> {
+        None
+    }
+
+    fn noise_source(&self) -> Option<u64> {
+        None
+    }
+
+    fn spill(&self) -> Option<Spill> {
+        None
+    }
+
+    fn remap(&self, _registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        self.clone_box()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        0
+    }
 }
 
 impl Clone for Box<dyn Op> {
@@ -84,6 +375,101 @@ impl Op for ConstantOp {
     fn clone_box(&self) -> Box<dyn Op> {
         Box::new(self.clone())
     }
+
+    fn execute(&self, _slots: &[f32], _env: &Environment) -> f32 {
+        self.value
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Constant(self.value)
+    }
+
+    fn constant(&self) -> Option<f32> {
+        Some(self.value)
+    }
+}
+
+#[derive(Clone)]
+struct LoadOp {
+    name: String,
+}
+
+impl std::fmt::Display for LoadOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "load {}", self.name)
+    }
+}
+
+impl Op for LoadOp {
+    fn clone_box(&self) -> Box<dyn Op> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, _slots: &[f32], env: &Environment) -> f32 {
+        env.get(&self.name)
+            .unwrap_or_else(|| panic!("unbound variable {}", self.name))
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Load(self.name.clone())
+    }
+
+    fn foldable(&self) -> bool {
+        false
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.name.capacity()
+    }
+}
+
+#[derive(Clone)]
+struct NoiseOp {
+    distribution: Distribution,
+    seed: Option<u64>,
+    source: u64,
+}
+
+impl std::fmt::Display for NoiseOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.seed {
+            Some(seed) => write!(f, "noise {} seed {}", self.distribution, seed),
+            None => write!(f, "noise {} entropy", self.distribution),
+        }
+    }
+}
+
+impl Op for NoiseOp {
+    fn clone_box(&self) -> Box<dyn Op> {
+        Box::new(self.clone())
+    }
+
+    // The first sample of a fresh stream. Runners that keep noise going from
+    // run to run sample through an EvalContext instead.
+    fn execute(&self, _slots: &[f32], _env: &Environment) -> f32 {
+        let mut state = self.seed.unwrap_or_else(super::entropy);
+        self.distribution.sample(&mut state)
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Noise(self.distribution, self.seed)
+    }
+
+    fn noise(&self) -> Option<(Distribution, Option<u64>)> {
+        Some((self.distribution, self.seed))
+    }
+
+    fn noise_source(&self) -> Option<u64> {
+        Some(self.source)
+    }
+
+    fn foldable(&self) -> bool {
+        false
+    }
+
+    fn deterministic(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Clone)]
@@ -102,6 +488,25 @@ impl Op for AddOp {
     fn clone_box(&self) -> Box<dyn Op> {
         Box::new(self.clone())
     }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        slots[self.a] + slots[self.b]
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Add(self.a, self.b)
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a, self.b]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(AddOp {
+            a: registers(self.a),
+            b: registers(self.b),
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -120,6 +525,25 @@ impl Op for SubOp {
     fn clone_box(&self) -> Box<dyn Op> {
         Box::new(self.clone())
     }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        slots[self.a] - slots[self.b]
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Sub(self.a, self.b)
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a, self.b]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(SubOp {
+            a: registers(self.a),
+            b: registers(self.b),
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -138,6 +562,25 @@ impl Op for MulOp {
     fn clone_box(&self) -> Box<dyn Op> {
         Box::new(self.clone())
     }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        slots[self.a] * slots[self.b]
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Mul(self.a, self.b)
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a, self.b]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(MulOp {
+            a: registers(self.a),
+            b: registers(self.b),
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -156,4 +599,197 @@ impl Op for DivOp {
     fn clone_box(&self) -> Box<dyn Op> {
         Box::new(self.clone())
     }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        slots[self.a] / slots[self.b]
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Div(self.a, self.b)
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a, self.b]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(DivOp {
+            a: registers(self.a),
+            b: registers(self.b),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct CompareOp {
+    comparison: Comparison,
+    a: usize,
+    b: usize,
+}
+
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} %{} %{}", self.comparison.name(), self.a, self.b)
+    }
+}
+
+impl Op for CompareOp {
+    fn clone_box(&self) -> Box<dyn Op> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        self.comparison.apply(slots[self.a], slots[self.b])
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Compare(self.comparison, self.a, self.b)
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a, self.b]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(CompareOp {
+            comparison: self.comparison,
+            a: registers(self.a),
+            b: registers(self.b),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct StoreOp {
+    a: usize,
+}
+
+impl std::fmt::Display for StoreOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "store %{}", self.a)
+    }
+}
+
+impl Op for StoreOp {
+    fn clone_box(&self) -> Box<dyn Op> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        slots[self.a]
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Store(self.a)
+    }
+
+    // A constant must still be written to memory, not to a register
+    // numbered like a memory cell.
+    fn foldable(&self) -> bool {
+        false
+    }
+
+    fn spill(&self) -> Option<Spill> {
+        Some(Spill::Store(self.a))
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(StoreOp {
+            a: registers(self.a),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct ReloadOp {
+    a: usize,
+}
+
+impl std::fmt::Display for ReloadOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "reload %{}", self.a)
+    }
+}
+
+impl Op for ReloadOp {
+    fn clone_box(&self) -> Box<dyn Op> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        slots[self.a]
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Reload(self.a)
+    }
+
+    fn foldable(&self) -> bool {
+        false
+    }
+
+    fn spill(&self) -> Option<Spill> {
+        Some(Spill::Reload(self.a))
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.a]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(ReloadOp {
+            a: registers(self.a),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct SelectOp {
+    condition: usize,
+    then: usize,
+    otherwise: usize,
+}
+
+impl std::fmt::Display for SelectOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "select %{} %{} %{}",
+            self.condition, self.then, self.otherwise
+        )
+    }
+}
+
+impl Op for SelectOp {
+    fn clone_box(&self) -> Box<dyn Op> {
+        Box::new(self.clone())
+    }
+
+    fn execute(&self, slots: &[f32], _env: &Environment) -> f32 {
+        if slots[self.condition] != 0. {
+            slots[self.then]
+        } else {
+            slots[self.otherwise]
+        }
+    }
+
+    fn opcode(&self) -> Opcode {
+        Opcode::Select(self.condition, self.then, self.otherwise)
+    }
+
+    fn operands(&self) -> Vec<usize> {
+        vec![self.condition, self.then, self.otherwise]
+    }
+
+    fn remap(&self, registers: &dyn Fn(usize) -> usize) -> Box<dyn Op> {
+        Box::new(SelectOp {
+            condition: registers(self.condition),
+            then: registers(self.then),
+            otherwise: registers(self.otherwise),
+        })
+    }
 }
@@ -0,0 +1,297 @@
+use cranelift::{
+    codegen::{
+        self,
+        ir::UserFuncName,
+        isa::{OwnedTargetIsa, TargetIsa},
+    },
+    jit::{JITBuilder, JITModule},
+    module::{default_libcall_names, FuncId, Linkage, Module},
+    prelude::*,
+};
+
+use super::{
+    cache::fnv1a,
+    instruction::{Instruction, Opcode},
+    ArtifactCache, Comparison, Environment, Program,
+};
+
+type Entry = extern "C" fn(*const f32) -> f32;
+
+// Native code for an instruction stream. Variables are read from the slice
+// passed to call(), in the order given by variables().
+pub struct Jit {
+    module: Option<JITModule>,
+    entry: Entry,
+    variables: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum JitError {
+    Unsupported(String),
+    Codegen(String),
+    Empty,
+    Unwritten { register: usize },
+}
+
+impl std::fmt::Display for JitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JitError::Unsupported(instruction) => write!(f, "cannot compile `{}`", instruction),
+            JitError::Codegen(message) => write!(f, "code generation failed: {}", message),
+            JitError::Empty => write!(f, "cannot compile an empty program"),
+            JitError::Unwritten { register } => {
+                write!(f, "%{} is read before it is written", register)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JitError {}
+
+fn codegen(error: impl std::fmt::Display) -> JitError {
+    JitError::Codegen(error.to_string())
+}
+
+fn isa() -> Result<OwnedTargetIsa, JitError> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").map_err(codegen)?;
+    cranelift::native::builder()
+        .map_err(codegen)?
+        .finish(settings::Flags::new(flags))
+        .map_err(codegen)
+}
+
+// The triple alone is not enough to reuse code: the native builder enables
+// whatever CPU features the host has, so those are part of the key too.
+fn target(isa: &dyn TargetIsa) -> String {
+    let flags: Vec<String> = isa
+        .isa_flags()
+        .iter()
+        .map(|flag| flag.to_string())
+        .collect();
+    let flags = format!("{}\n{}", isa.flags(), flags.join("\n"));
+    format!("{}-{:016x}", isa.triple(), fnv1a(flags.as_bytes()))
+}
+
+// A module holding the `eval` declaration, and a context whose function
+// has its signature.
+fn declare() -> Result<(JITModule, codegen::Context, FuncId), JitError> {
+    let mut module = JITModule::new(JITBuilder::with_isa(isa()?, default_libcall_names()));
+    let mut ctx = module.make_context();
+    let pointer = module.target_config().pointer_type();
+    ctx.func.signature.params.push(AbiParam::new(pointer));
+    ctx.func.signature.returns.push(AbiParam::new(types::F32));
+    let id = module
+        .declare_function("eval", Linkage::Local, &ctx.func.signature)
+        .map_err(codegen)?;
+    ctx.func.name = UserFuncName::user(0, id.as_u32());
+    Ok((module, ctx, id))
+}
+
+// The generated function returns the value of the last instruction, so
+// that has to be the program's only output.
+pub(super) fn check_outputs(program: &Program) -> Result<(), JitError> {
+    let last = program.instructions().last().map(|i| i.ret());
+    if program.outputs().len() != 1 || last != Some(program.outputs()[0]) {
+        let outputs: Vec<String> = program
+            .outputs()
+            .iter()
+            .map(|r| format!("%{}", r))
+            .collect();
+        return Err(JitError::Unsupported(format!("ret {}", outputs.join(", "))));
+    }
+    Ok(())
+}
+
+impl Jit {
+    pub fn compile(instructions: &[Instruction]) -> Result<Self, JitError> {
+        Self::generate(instructions).map(|(jit, _)| jit)
+    }
+
+    // Reuses native code cached by an earlier process for the same program
+    // and host, and caches what it compiles otherwise. Like compile(), the
+    // program's single output must be its last instruction.
+    pub fn compile_cached(program: &Program, cache: &ArtifactCache) -> Result<Self, JitError> {
+        check_outputs(program)?;
+        let target = target(&*isa()?);
+        if let Some(jit) = cache
+            .load(program, &target, "jit")
+            .and_then(|payload| Self::load(&payload, program.variables()).ok())
+        {
+            return Ok(jit);
+        }
+        let (jit, payload) = Self::generate(program.instructions())?;
+        if let Some(payload) = payload {
+            // A failed write only costs the next process a compile.
+            let _ = cache.store(program, &target, "jit", &payload);
+        }
+        Ok(jit)
+    }
+
+    // Also hands back the machine code, after its alignment as a u64, when
+    // it has no relocations, which is when it can be copied anywhere and
+    // still run. load() takes the same layout.
+    fn generate(instructions: &[Instruction]) -> Result<(Self, Option<Vec<u8>>), JitError> {
+        let result = instructions.last().ok_or(JitError::Empty)?.ret();
+        let (mut module, mut ctx, id) = declare()?;
+
+        let mut variables: Vec<String> = Vec::new();
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        let args = builder.block_params(block)[0];
+
+        // Registers are reused after allocation, but each write simply
+        // rebinds the register to a fresh SSA value.
+        let slot_count = instructions.iter().map(|i| i.ret() + 1).max().unwrap_or(0);
+        let mut registers: Vec<Option<Value>> = vec![None; slot_count];
+        let read = |registers: &[Option<Value>], register: usize| {
+            registers
+                .get(register)
+                .copied()
+                .flatten()
+                .ok_or(JitError::Unwritten { register })
+        };
+        for instruction in instructions {
+            let value = match instruction.opcode() {
+                Opcode::Constant(value) => builder.ins().f32const(value),
+                Opcode::Load(name) => {
+                    let index = match variables.iter().position(|v| *v == name) {
+                        Some(index) => index,
+                        None => {
+                            variables.push(name);
+                            variables.len() - 1
+                        }
+                    };
+                    let offset = (index * std::mem::size_of::<f32>()) as i32;
+                    builder
+                        .ins()
+                        .load(types::F32, MemFlags::trusted(), args, offset)
+                }
+                Opcode::Noise(..) => return Err(JitError::Unsupported(instruction.to_string())),
+                Opcode::Add(a, b) => {
+                    let (a, b) = (read(&registers, a)?, read(&registers, b)?);
+                    builder.ins().fadd(a, b)
+                }
+                Opcode::Sub(a, b) => {
+                    let (a, b) = (read(&registers, a)?, read(&registers, b)?);
+                    builder.ins().fsub(a, b)
+                }
+                Opcode::Mul(a, b) => {
+                    let (a, b) = (read(&registers, a)?, read(&registers, b)?);
+                    builder.ins().fmul(a, b)
+                }
+                Opcode::Div(a, b) => {
+                    let (a, b) = (read(&registers, a)?, read(&registers, b)?);
+                    builder.ins().fdiv(a, b)
+                }
+                Opcode::Store(a) | Opcode::Reload(a) => read(&registers, a)?,
+                Opcode::Select(c, a, b) => {
+                    let (c, a, b) = (
+                        read(&registers, c)?,
+                        read(&registers, a)?,
+                        read(&registers, b)?,
+                    );
+                    let zero = builder.ins().f32const(0.);
+                    let taken = builder.ins().fcmp(FloatCC::NotEqual, c, zero);
+                    builder.ins().select(taken, a, b)
+                }
+                Opcode::Compare(comparison, a, b) => {
+                    let (a, b) = (read(&registers, a)?, read(&registers, b)?);
+                    let cc = match comparison {
+                        Comparison::Eq => FloatCC::Equal,
+                        Comparison::Ne => FloatCC::NotEqual,
+                        Comparison::Lt => FloatCC::LessThan,
+                        Comparison::Le => FloatCC::LessThanOrEqual,
+                        Comparison::Gt => FloatCC::GreaterThan,
+                        Comparison::Ge => FloatCC::GreaterThanOrEqual,
+                    };
+                    let holds = builder.ins().fcmp(cc, a, b);
+                    let one = builder.ins().f32const(1.);
+                    let zero = builder.ins().f32const(0.);
+                    builder.ins().select(holds, one, zero)
+                }
+            };
+            registers[instruction.ret()] = Some(value);
+        }
+        let result = read(&registers, result)?;
+        builder.ins().return_(&[result]);
+        builder.seal_all_blocks();
+        builder.finalize();
+
+        module.define_function(id, &mut ctx).map_err(codegen)?;
+        let payload = ctx
+            .compiled_code()
+            .filter(|compiled| compiled.buffer.relocs().is_empty())
+            .map(|compiled| {
+                let mut payload = (compiled.buffer.alignment as u64).to_le_bytes().to_vec();
+                payload.extend_from_slice(compiled.code_buffer());
+                payload
+            });
+        module.clear_context(&mut ctx);
+        Ok((Self::finish(module, id, variables)?, payload))
+    }
+
+    fn load(payload: &[u8], variables: Vec<String>) -> Result<Self, JitError> {
+        if payload.len() < 8 {
+            return Err(JitError::Codegen("truncated artifact".to_string()));
+        }
+        let (alignment, code) = payload.split_at(8);
+        let alignment = u64::from_le_bytes(alignment.try_into().unwrap());
+        let (mut module, mut ctx, id) = declare()?;
+        module
+            .define_function_bytes(id, &ctx.func, alignment, code, &[])
+            .map_err(codegen)?;
+        module.clear_context(&mut ctx);
+        Self::finish(module, id, variables)
+    }
+
+    fn finish(mut module: JITModule, id: FuncId, variables: Vec<String>) -> Result<Self, JitError> {
+        module.finalize_definitions().map_err(codegen)?;
+        let code = module.get_finalized_function(id);
+        // SAFETY: the function was declared with exactly this signature.
+        let entry = unsafe { std::mem::transmute::<*const u8, Entry>(code) };
+        Ok(Self {
+            module: Some(module),
+            entry,
+            variables,
+        })
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn call(&self, args: &[f32]) -> f32 {
+        assert_eq!(
+            args.len(),
+            self.variables.len(),
+            "expected values for {:?}",
+            self.variables
+        );
+        (self.entry)(args.as_ptr())
+    }
+
+    pub fn call_with(&self, env: &Environment) -> f32 {
+        let args: Vec<f32> = self
+            .variables
+            .iter()
+            .map(|name| {
+                env.get(name)
+                    .unwrap_or_else(|| panic!("unbound variable {}", name))
+            })
+            .collect();
+        self.call(&args)
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            // SAFETY: entry is private and cannot outlive self.
+            unsafe { module.free_memory() }
+        }
+    }
+}
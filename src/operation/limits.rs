@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use super::DynScalar;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GraphLimits {
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LimitError {
+    TooDeep { limit: usize },
+    TooManyNodes { limit: usize },
+}
+
+impl std::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitError::TooDeep { limit } => write!(f, "graph is deeper than {} nodes", limit),
+            LimitError::TooManyNodes { limit } => {
+                write!(f, "graph has more than {} nodes", limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl GraphLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    // The walk stops as soon as either limit is exceeded, so a check never
+    // costs much more than the limits themselves, however large the graph.
+    // Nodes are counted as they are first reached and the path from the
+    // root is an explicit stack, so its length is the current depth; the
+    // heights of finished nodes are kept so shared ones are walked once.
+    pub fn check(&self, expr: &DynScalar) -> Result<(), LimitError> {
+        let mut heights: HashMap<usize, usize> = HashMap::new();
+        let mut reached = 0;
+        // Each node on the path, its operands, the next one to walk and the
+        // tallest operand so far.
+        let mut path: Vec<(usize, Vec<DynScalar>, usize, usize)> = Vec::new();
+        let mut next = Some(expr.clone());
+        loop {
+            if let Some(node) = next.take() {
+                reached += 1;
+                if let Some(limit) = self.max_nodes.filter(|&limit| reached > limit) {
+                    return Err(LimitError::TooManyNodes { limit });
+                }
+                if let Some(limit) = self.max_depth.filter(|&limit| path.len() >= limit) {
+                    return Err(LimitError::TooDeep { limit });
+                }
+                path.push((node.id(), node.children(), 0, 0));
+            }
+            let depth = path.len();
+            let (id, children, index, height) = path.last_mut().unwrap();
+            if let Some(child) = children.get(*index) {
+                *index += 1;
+                match heights.get(&child.id()) {
+                    Some(&child_height) => {
+                        *height = (*height).max(child_height);
+                        if let Some(limit) =
+                            self.max_depth.filter(|&limit| depth + child_height > limit)
+                        {
+                            return Err(LimitError::TooDeep { limit });
+                        }
+                    }
+                    None => next = Some(child.clone()),
+                }
+                continue;
+            }
+            let (id, height) = (*id, *height + 1);
+            heights.insert(id, height);
+            path.pop();
+            match path.last_mut() {
+                Some((_, _, _, parent)) => *parent = (*parent).max(height),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    pub fn add(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.checked(a.add(b))
+    }
+
+    pub fn sub(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.checked(a.sub(b))
+    }
+
+    pub fn mul(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.checked(a.mul(b))
+    }
+
+    pub fn div(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, LimitError> {
+        self.checked(a.div(b))
+    }
+
+    fn checked(&self, expr: DynScalar) -> Result<DynScalar, LimitError> {
+        self.check(&expr)?;
+        Ok(expr)
+    }
+}
@@ -0,0 +1,176 @@
+use std::fmt::Write;
+
+use super::{
+    instruction::{Opcode, Unsupported},
+    Comparison, Program,
+};
+
+impl Program {
+    // Renders `define @eval` taking the variables as float parameters.
+    // Registers are reused after allocation, so each write gets a fresh SSA
+    // value and loads and constants are used in place.
+    pub fn to_llvm_ir(&self) -> Result<String, Unsupported> {
+        let mut values: Vec<String> = vec![String::new(); self.register_count()];
+        let mut next = 0;
+        let mut body = String::new();
+        let mut notes: Vec<&str> = Vec::new();
+        for instruction in self.instructions() {
+            // Provenance goes on the LLVM instruction that defines the value,
+            // as !provenance metadata.
+            let attachment = match instruction.provenance() {
+                Some(note) => {
+                    notes.push(note);
+                    format!(", !provenance !{}", notes.len() - 1)
+                }
+                None => String::new(),
+            };
+            let (opcode, a, b) = match instruction.opcode() {
+                Opcode::Constant(value) => {
+                    values[instruction.ret()] = constant(value);
+                    continue;
+                }
+                Opcode::Load(name) => {
+                    values[instruction.ret()] = local(&name);
+                    continue;
+                }
+                Opcode::Noise(..) => {
+                    return Err(Unsupported {
+                        instruction: instruction.to_string(),
+                    })
+                }
+                Opcode::Select(c, a, b) => {
+                    // une is true for NaN too, matching `!= 0.` elsewhere.
+                    writeln!(body, "  %{} = fcmp une float {}, 0.0", next, values[c]).unwrap();
+                    writeln!(
+                        body,
+                        "  %{} = select i1 %{}, float {}, float {}{}",
+                        next + 1,
+                        next,
+                        values[a],
+                        values[b],
+                        attachment
+                    )
+                    .unwrap();
+                    values[instruction.ret()] = format!("%{}", next + 1);
+                    next += 2;
+                    continue;
+                }
+                Opcode::Compare(comparison, a, b) => {
+                    let predicate = match comparison {
+                        Comparison::Eq => "oeq",
+                        Comparison::Ne => "une",
+                        Comparison::Lt => "olt",
+                        Comparison::Le => "ole",
+                        Comparison::Gt => "ogt",
+                        Comparison::Ge => "oge",
+                    };
+                    writeln!(
+                        body,
+                        "  %{} = fcmp {} float {}, {}",
+                        next, predicate, values[a], values[b]
+                    )
+                    .unwrap();
+                    writeln!(
+                        body,
+                        "  %{} = uitofp i1 %{} to float{}",
+                        next + 1,
+                        next,
+                        attachment
+                    )
+                    .unwrap();
+                    values[instruction.ret()] = format!("%{}", next + 1);
+                    next += 2;
+                    continue;
+                }
+                Opcode::Store(a) | Opcode::Reload(a) => {
+                    values[instruction.ret()] = values[a].clone();
+                    continue;
+                }
+                Opcode::Add(a, b) => ("fadd", a, b),
+                Opcode::Sub(a, b) => ("fsub", a, b),
+                Opcode::Mul(a, b) => ("fmul", a, b),
+                Opcode::Div(a, b) => ("fdiv", a, b),
+            };
+            writeln!(
+                body,
+                "  %{} = {} float {}, {}{}",
+                next, opcode, values[a], values[b], attachment
+            )
+            .unwrap();
+            values[instruction.ret()] = format!("%{}", next);
+            next += 1;
+        }
+
+        let outputs = self.outputs();
+        let ty = match outputs.len() {
+            1 => {
+                writeln!(body, "  ret float {}", values[outputs[0]]).unwrap();
+                "float".to_string()
+            }
+            n => {
+                let ty = format!("[{} x float]", n);
+                let mut aggregate = "poison".to_string();
+                for (i, &ret) in outputs.iter().enumerate() {
+                    writeln!(
+                        body,
+                        "  %{} = insertvalue {} {}, float {}, {}",
+                        next, ty, aggregate, values[ret], i
+                    )
+                    .unwrap();
+                    aggregate = format!("%{}", next);
+                    next += 1;
+                }
+                writeln!(body, "  ret {} {}", ty, aggregate).unwrap();
+                ty
+            }
+        };
+        let parameters: Vec<String> = self
+            .variables()
+            .iter()
+            .map(|name| format!("float {}", local(name)))
+            .collect();
+        let mut ir = format!(
+            "define {} @eval({}) {{\nentry:\n{}}}\n",
+            ty,
+            parameters.join(", "),
+            body
+        );
+        for (i, note) in notes.iter().enumerate() {
+            writeln!(ir, "!{} = !{{!{}}}", i, quoted(note)).unwrap();
+        }
+        Ok(ir)
+    }
+}
+
+// LLVM spells float constants as the hex of the equivalent double, which is
+// exact for every f32 including infinities and NaN.
+fn constant(value: f32) -> String {
+    format!("0x{:016X}", (value as f64).to_bits())
+}
+
+// Names that are not plain LLVM identifiers are quoted, with quotes and
+// backslashes escaped as hex.
+fn local(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || "-$._".contains(c))
+        && chars.all(|c| c.is_ascii_alphanumeric() || "-$._".contains(c));
+    if plain {
+        return format!("%{}", name);
+    }
+    format!("%{}", quoted(name))
+}
+
+fn quoted(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => write!(quoted, "\\{:02X}", c as u8).unwrap(),
+            c if c.is_ascii_control() => write!(quoted, "\\{:02X}", c as u8).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,121 @@
+use std::{collections::HashMap, fmt::Display};
+
+use super::{analysis::postorder, eval, DynScalar, Environment, OpKind, Operation, Scalar};
+
+// One node of a logged evaluation. Ids are positions in the graph's
+// postorder, the same as in its serialized form, so they are stable for a
+// graph across processes. The value is None for nodes the evaluation
+// skipped, i.e. untaken select arms; a node evaluated more than once, like
+// noise used twice, keeps its first value.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogEntry {
+    pub id: usize,
+    pub label: String,
+    pub operands: Vec<usize>,
+    pub value: Option<f64>,
+}
+
+// Every node's value from one evaluation, root last, for looking into a
+// result after the fact.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvalLog {
+    entries: Vec<LogEntry>,
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn execute_logged(&self, env: &Environment) -> EvalLog {
+        self.clone().into_dyn().execute_logged(env)
+    }
+}
+
+impl DynScalar {
+    // Evaluates as execute_with() does, drawing the same noise, and logs
+    // each node's value. Nodes are labelled with their provenance note if
+    // they are provenance nodes and with their kind otherwise, so variables
+    // go by name.
+    pub fn execute_logged(&self, env: &Environment) -> EvalLog {
+        let nodes = postorder(self);
+        let index: HashMap<usize, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id(), i))
+            .collect();
+        let mut values: Vec<Option<f64>> = vec![None; nodes.len()];
+        eval::evaluate_observed(self, env, None, |node, &value: &f32| {
+            values[index[&node.id()]].get_or_insert(value as f64);
+        })
+        .expect("evaluation without a policy does not fail");
+        EvalLog::from_nodes(&nodes, values)
+    }
+}
+
+impl EvalLog {
+    // A log of `nodes`, a graph's postorder, with the value of each.
+    pub(super) fn from_nodes(nodes: &[DynScalar], values: Vec<Option<f64>>) -> Self {
+        let index: HashMap<usize, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id(), i))
+            .collect();
+        let entries = nodes
+            .iter()
+            .zip(values)
+            .enumerate()
+            .map(|(id, (node, value))| LogEntry {
+                id,
+                label: match node.kind() {
+                    OpKind::Provenance(note) => note,
+                    kind => kind.to_string(),
+                },
+                operands: node.children().iter().map(|c| index[&c.id()]).collect(),
+                value,
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: usize) -> Option<&LogEntry> {
+        self.entries.get(id)
+    }
+
+    pub fn value(&self, id: usize) -> Option<f64> {
+        self.get(id)?.value
+    }
+
+    // Every node with the label, in postorder.
+    pub fn labelled<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a LogEntry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.label == label)
+    }
+
+    pub fn result(&self) -> f64 {
+        self.entries
+            .last()
+            .and_then(|entry| entry.value)
+            .expect("a log has its root's value")
+    }
+}
+
+impl Display for EvalLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            let operands: Vec<String> = entry.operands.iter().map(|i| format!("#{}", i)).collect();
+            write!(f, "#{} {}", entry.id, entry.label)?;
+            if !operands.is_empty() {
+                write!(f, " {}", operands.join(" "))?;
+            }
+            match entry.value {
+                Some(value) => writeln!(f, " = {}", value)?,
+                None => writeln!(f, " skipped")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+
+use super::{
+    frozen::{flatten, Node},
+    DynScalar, Environment, Operation, Scalar,
+};
+
+// Keeps the last value of every node and re-evaluates only what a change
+// can reach. Setting a variable marks it dirty; execute() then walks the
+// dirty set in evaluation order, and a node whose value comes out
+// unchanged stops the invalidation from spreading past it. Noise is
+// resampled on every call, so its ancestors are never cached.
+pub struct MemoizedExpr {
+    nodes: Vec<Node>,
+    parents: Vec<Vec<usize>>,
+    values: Vec<f32>,
+    variables: Vec<String>,
+    args: Vec<Option<f32>>,
+    noise: Vec<usize>,
+    streams: Vec<u64>,
+    dirty: BTreeSet<usize>,
+    root: usize,
+    recomputed: usize,
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn memoize(&self) -> MemoizedExpr {
+        self.clone().into_dyn().memoize()
+    }
+}
+
+impl DynScalar {
+    pub fn memoize(&self) -> MemoizedExpr {
+        let (nodes, variables, root) = flatten(self);
+        let mut parents = vec![Vec::new(); nodes.len()];
+        let mut noise = Vec::new();
+        let mut streams = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            for operand in node.operands() {
+                if !parents[operand].contains(&i) {
+                    parents[operand].push(i);
+                }
+            }
+            if let Node::Noise(_, seed, _) = *node {
+                noise.push(i);
+                streams.push(seed.unwrap_or_else(super::entropy));
+            }
+        }
+        MemoizedExpr {
+            values: vec![0.; nodes.len()],
+            dirty: (0..nodes.len()).collect(),
+            args: vec![None; variables.len()],
+            nodes,
+            parents,
+            variables,
+            noise,
+            streams,
+            root,
+            recomputed: 0,
+        }
+    }
+}
+
+impl MemoizedExpr {
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    // Returns false if the expression does not use `name`.
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        let Some(i) = self.variables.iter().position(|v| v == name) else {
+            return false;
+        };
+        if self.args[i].map(f32::to_bits) != Some(value.to_bits()) {
+            self.args[i] = Some(value);
+            for (index, node) in self.nodes.iter().enumerate() {
+                if let Node::Variable(v) = *node {
+                    if v == i {
+                        self.dirty.insert(index);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    pub fn set_all(&mut self, env: &Environment) {
+        let variables = self.variables.clone();
+        for name in &variables {
+            if let Some(value) = env.get(name) {
+                self.set(name, value);
+            }
+        }
+    }
+
+    pub fn execute(&mut self) -> f32 {
+        if let Some(i) = self.args.iter().position(Option::is_none) {
+            panic!("unbound variable {}", self.variables[i]);
+        }
+        self.dirty.extend(self.noise.iter().copied());
+        let args: Vec<f32> = self.args.iter().map(|arg| arg.unwrap()).collect();
+        self.recomputed = 0;
+        // Parents always come after their operands, so popping the lowest
+        // index never visits a node before something it depends on. Every
+        // noise node is dirty, so they take their streams in order.
+        let mut streams = self.streams.iter_mut();
+        while let Some(i) = self.dirty.pop_first() {
+            let value = self.nodes[i].evaluate(&self.values, &args, &mut streams);
+            self.recomputed += 1;
+            if value.to_bits() != self.values[i].to_bits() {
+                self.values[i] = value;
+                self.dirty.extend(self.parents[i].iter().copied());
+            }
+        }
+        self.values[self.root]
+    }
+
+    pub fn execute_with(&mut self, env: &Environment) -> f32 {
+        self.set_all(env);
+        self.execute()
+    }
+
+    // How many nodes the last execute() evaluated.
+    pub fn recomputed(&self) -> usize {
+        self.recomputed
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+};
+
+use super::{
+    instruction::{self, Instruction},
+    CompileError, Environment, Opcode, RegisterAllocator,
+};
+
+pub fn fold_constants(instructions: &[Instruction]) -> Vec<Instruction> {
+    let outputs: Vec<usize> = instructions.last().map(|i| i.ret()).into_iter().collect();
+    fold(instructions, &outputs)
+}
+
+// Evaluates every instruction whose operands are all known constants and
+// replaces it with a constant, then drops the constants nothing reads anymore.
+pub(super) fn fold(instructions: &[Instruction], outputs: &[usize]) -> Vec<Instruction> {
+    let slot_count = instructions.iter().map(|i| i.ret() + 1).max().unwrap_or(0);
+    let mut slots = vec![0.; slot_count];
+    let mut known = vec![false; slot_count];
+    let env = Environment::new();
+
+    let folded: Vec<Instruction> = instructions
+        .iter()
+        .map(|instruction| {
+            let ret = instruction.ret();
+            if let Some(value) = instruction.constant() {
+                slots[ret] = value;
+                known[ret] = true;
+                return instruction.clone();
+            }
+            let operands = instruction.operands();
+            if instruction.foldable() && operands.iter().all(|&operand| known[operand]) {
+                let value = instruction.execute(&slots, &env);
+                slots[ret] = value;
+                known[ret] = true;
+                instruction::constant(value, ret).with_provenance_of(instruction)
+            } else {
+                known[ret] = false;
+                instruction.clone()
+            }
+        })
+        .collect();
+
+    let mut live: HashSet<usize> = outputs.iter().copied().collect();
+    let mut kept = Vec::new();
+    for instruction in folded.into_iter().rev() {
+        if instruction.constant().is_some() && !live.contains(&instruction.ret()) {
+            continue;
+        }
+        live.remove(&instruction.ret());
+        live.extend(instruction.operands());
+        kept.push(instruction);
+    }
+    kept.reverse();
+    kept
+}
+
+pub fn peephole(instructions: &[Instruction]) -> Vec<Instruction> {
+    let outputs: Vec<usize> = instructions.last().map(|i| i.ret()).into_iter().collect();
+    simplify(instructions, &outputs)
+}
+
+// Folds constant arithmetic, forwards values some register already holds,
+// which covers repeated constants and additions of zero, then drops what
+// nothing reads. Registers may be reused, as after allocation: a value is
+// only forwarded from a register nothing overwrites before the value's
+// last read, and outputs keep their final writes. x + 0 is -0 for x = -0,
+// and forwarding x in its place is the one way results can change.
+pub(super) fn simplify(instructions: &[Instruction], outputs: &[usize]) -> Vec<Instruction> {
+    let folded = fold(instructions, outputs);
+    let forwarded = forward(&folded, outputs);
+    eliminate_dead(&forwarded, outputs)
+}
+
+fn forward(instructions: &[Instruction], outputs: &[usize]) -> Vec<Instruction> {
+    let mut code: Vec<Option<Instruction>> = instructions.iter().cloned().map(Some).collect();
+    // The constant each register holds, if any, and where its current value
+    // was written. Ordered so the same program always forwards the same way.
+    let mut constants: BTreeMap<usize, f32> = BTreeMap::new();
+    let mut writes: HashMap<usize, usize> = HashMap::new();
+    for position in 0..code.len() {
+        let instruction = code[position].clone().unwrap();
+        let zero = |r: usize| constants.get(&r).is_some_and(|&value| value == 0.);
+        let source = match instruction.opcode() {
+            Opcode::Constant(value) => constants
+                .iter()
+                .find(|(_, held)| held.to_bits() == value.to_bits())
+                .map(|(&register, _)| register),
+            Opcode::Add(a, b) if zero(b) => Some(a),
+            Opcode::Add(a, b) if zero(a) => Some(b),
+            // x - 0 is exactly x, but x - -0 is not.
+            Opcode::Sub(a, b) if constants.get(&b).is_some_and(|b| b.to_bits() == 0) => Some(a),
+            _ => None,
+        };
+        let ret = instruction.ret();
+        if let Some(source) = source {
+            if let Some(reads) = readers(&code, position, source, outputs) {
+                for read in reads {
+                    let reader = code[read].as_ref().unwrap();
+                    code[read] =
+                        Some(reader.rename(|r| if r == ret { source } else { r }, reader.ret()));
+                }
+                if let (Some(note), Some(&write)) = (instruction.provenance(), writes.get(&source))
+                {
+                    code[write].as_mut().unwrap().annotate(note);
+                }
+                code[position] = None;
+                continue;
+            }
+        }
+        match instruction.constant() {
+            Some(value) => constants.insert(ret, value),
+            None => constants.remove(&ret),
+        };
+        writes.insert(ret, position);
+    }
+    code.into_iter().flatten().collect()
+}
+
+// The instructions reading the value written at `position`, provided each
+// could read it from `source` instead.
+fn readers(
+    code: &[Option<Instruction>],
+    position: usize,
+    source: usize,
+    outputs: &[usize],
+) -> Option<Vec<usize>> {
+    let ret = code[position].as_ref().unwrap().ret();
+    let mut reads = Vec::new();
+    let mut overwritten = false;
+    for (index, instruction) in code.iter().enumerate().skip(position + 1) {
+        let Some(instruction) = instruction else {
+            continue;
+        };
+        if instruction.operands().contains(&ret) {
+            if overwritten {
+                return None;
+            }
+            reads.push(index);
+        }
+        if instruction.ret() == ret {
+            return Some(reads);
+        }
+        overwritten |= instruction.ret() == source;
+    }
+    if outputs.contains(&ret) && source != ret {
+        return None;
+    }
+    Some(reads)
+}
+
+// Noise is kept even when unread: dropping a source would move the sources
+// after it onto other streams.
+fn eliminate_dead(instructions: &[Instruction], outputs: &[usize]) -> Vec<Instruction> {
+    let mut live: HashSet<usize> = outputs.iter().copied().collect();
+    let mut kept = Vec::new();
+    for instruction in instructions.iter().rev() {
+        if instruction.deterministic() && !live.contains(&instruction.ret()) {
+            continue;
+        }
+        live.remove(&instruction.ret());
+        live.extend(instruction.operands());
+        kept.push(instruction.clone());
+    }
+    kept.reverse();
+    kept
+}
+
+pub fn eliminate_common_subexpressions(instructions: &[Instruction]) -> Vec<Instruction> {
+    cse(instructions, &[]).0
+}
+
+// Value numbering: an instruction that matches an earlier one after its
+// operands are rewritten to their representatives is dropped, and its
+// register is redirected to the earlier result. Noise is never merged.
+// Expects every register to be written once, i.e. before allocation.
+pub(super) fn cse(
+    instructions: &[Instruction],
+    outputs: &[usize],
+) -> (Vec<Instruction>, Vec<usize>) {
+    let mut representatives: HashMap<usize, usize> = HashMap::new();
+    // Where each kept value was computed, by position in `kept`.
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut kept: Vec<Instruction> = Vec::new();
+    for instruction in instructions {
+        let instruction = instruction.remap(|r| *representatives.get(&r).unwrap_or(&r));
+        if instruction.deterministic() {
+            match seen.get(&instruction.key()) {
+                Some(&index) => {
+                    representatives.insert(instruction.ret(), kept[index].ret());
+                    if let Some(provenance) = instruction.provenance() {
+                        kept[index].annotate(provenance);
+                    }
+                    continue;
+                }
+                None => {
+                    seen.insert(instruction.key(), kept.len());
+                }
+            }
+        }
+        kept.push(instruction);
+    }
+    let outputs = outputs
+        .iter()
+        .map(|r| *representatives.get(r).unwrap_or(r))
+        .collect();
+    (kept, outputs)
+}
+
+pub fn allocate_registers(instructions: &[Instruction]) -> Vec<Instruction> {
+    let outputs: Vec<usize> = instructions.last().map(|i| i.ret()).into_iter().collect();
+    allocate(instructions, &outputs, &mut RegisterAllocator::new())
+        .expect("unbounded register allocator ran out")
+        .0
+}
+
+// Linear scan over the stream: a register's slot is released after the last
+// instruction that reads it, and each result takes the lowest free slot. An
+// instruction may reuse an operand's slot since operands are read first.
+// New slots come from `allocator`, which fails once a bounded file is full.
+pub(super) fn allocate(
+    instructions: &[Instruction],
+    outputs: &[usize],
+    allocator: &mut RegisterAllocator,
+) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+    let mut last_use = HashMap::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        for operand in instruction.operands() {
+            last_use.insert(operand, index);
+        }
+    }
+    for &output in outputs {
+        last_use.insert(output, usize::MAX);
+    }
+
+    let mut slots: HashMap<usize, usize> = HashMap::new();
+    let mut free = BinaryHeap::new();
+    let mut allocated = Vec::with_capacity(instructions.len());
+    for (index, instruction) in instructions.iter().enumerate() {
+        let mut registers: HashMap<usize, usize> = instruction
+            .operands()
+            .into_iter()
+            .map(|operand| (operand, slots[&operand]))
+            .collect();
+        for (&operand, &slot) in &registers {
+            if last_use[&operand] == index {
+                slots.remove(&operand);
+                free.push(Reverse(slot));
+            }
+        }
+        let slot = match free.pop() {
+            Some(Reverse(slot)) => slot,
+            None => allocator.alloc()?,
+        };
+        match last_use.get(&instruction.ret()) {
+            Some(_) => {
+                slots.insert(instruction.ret(), slot);
+            }
+            None => free.push(Reverse(slot)),
+        }
+        registers.insert(instruction.ret(), slot);
+        allocated.push(instruction.remap(|r| registers[&r]));
+    }
+    let outputs = outputs.iter().map(|output| slots[output]).collect();
+    Ok((allocated, outputs))
+}
+
+// Allocation into a file of `limit` registers. When every register holds a
+// live value, the one read furthest in the future is stored to a memory cell
+// and reloaded before its next use. Cells are numbered from `limit` up, so
+// the result still runs anywhere a plain program does.
+pub(super) fn spill(
+    instructions: &[Instruction],
+    outputs: &[usize],
+    limit: usize,
+) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+    let mut uses: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (index, instruction) in instructions.iter().enumerate() {
+        for operand in instruction.operands() {
+            uses.entry(operand).or_default().push(index);
+        }
+    }
+    for &output in outputs {
+        uses.entry(output).or_default().push(instructions.len());
+    }
+    let mut spiller = Spiller {
+        uses,
+        file: vec![None; limit],
+        registers: HashMap::new(),
+        memory: HashMap::new(),
+        code: Vec::new(),
+    };
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let operands = instruction.operands();
+        let mut pinned = Vec::new();
+        for &operand in &operands {
+            let register = match spiller.registers.get(&operand) {
+                Some(&register) => register,
+                None => {
+                    let register = spiller.take(index, &pinned)?;
+                    let cell = spiller.memory[&operand];
+                    spiller.code.push(instruction::reload(cell, register));
+                    spiller.place(operand, register);
+                    register
+                }
+            };
+            pinned.push(register);
+        }
+        let mut mapping: HashMap<usize, usize> = operands
+            .iter()
+            .map(|operand| (*operand, spiller.registers[operand]))
+            .collect();
+        for &operand in &operands {
+            if spiller.next_use(operand, index + 1).is_none() {
+                spiller.release(operand);
+            }
+        }
+        // Operands are read before the result is written, so any register,
+        // including one just freed, can take it.
+        let ret = instruction.ret();
+        let register = spiller.take(index + 1, &[])?;
+        mapping.insert(ret, register);
+        spiller.code.push(instruction.remap(|r| mapping[&r]));
+        spiller.place(ret, register);
+        if spiller.next_use(ret, index + 1).is_none() {
+            spiller.release(ret);
+        }
+    }
+    let outputs = outputs
+        .iter()
+        .map(|output| match spiller.registers.get(output) {
+            Some(&register) => register,
+            None => spiller.memory[output],
+        })
+        .collect();
+    Ok((spiller.code, outputs))
+}
+
+struct Spiller {
+    uses: HashMap<usize, Vec<usize>>,
+    file: Vec<Option<usize>>,
+    registers: HashMap<usize, usize>,
+    memory: HashMap<usize, usize>,
+    code: Vec<Instruction>,
+}
+
+impl Spiller {
+    fn next_use(&self, value: usize, from: usize) -> Option<usize> {
+        let uses = self.uses.get(&value)?;
+        uses.get(uses.partition_point(|&index| index < from))
+            .copied()
+    }
+
+    fn place(&mut self, value: usize, register: usize) {
+        self.file[register] = Some(value);
+        self.registers.insert(value, register);
+    }
+
+    fn release(&mut self, value: usize) {
+        if let Some(register) = self.registers.remove(&value) {
+            self.file[register] = None;
+        }
+    }
+
+    // A free register, or else the one whose value is needed last, storing
+    // that value first unless memory already has it.
+    fn take(&mut self, from: usize, pinned: &[usize]) -> Result<usize, CompileError> {
+        if let Some(register) = self.file.iter().position(Option::is_none) {
+            return Ok(register);
+        }
+        let limit = self.file.len();
+        let (register, value) = self
+            .file
+            .iter()
+            .enumerate()
+            .filter(|(register, _)| !pinned.contains(register))
+            .map(|(register, value)| (register, value.unwrap()))
+            .max_by_key(|&(_, value)| self.next_use(value, from).unwrap_or(usize::MAX))
+            .ok_or(CompileError::OutOfRegisters { limit })?;
+        if !self.memory.contains_key(&value) {
+            let cell = limit + self.memory.len();
+            self.code.push(instruction::store(register, cell));
+            self.memory.insert(value, cell);
+        }
+        self.release(value);
+        Ok(register)
+    }
+}
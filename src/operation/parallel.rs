@@ -0,0 +1,139 @@
+use rayon::prelude::*;
+
+use super::{
+    frozen::{select, FrozenExpr, Node},
+    simd::{LaneProgram, LANES},
+    Comparison, DynScalar, Environment, EvalContext, Operation, Program, Scalar,
+};
+
+// A threshold for the par_ functions: graphs smaller than this are rarely
+// worth handing to the thread pool.
+pub const PAR_THRESHOLD: usize = 4096;
+
+// The nodes that have operands.
+#[derive(Clone, Copy)]
+enum Step {
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Select(usize, usize, usize),
+    Compare(Comparison, usize, usize),
+}
+
+impl Step {
+    fn evaluate(self, values: &[f32]) -> f32 {
+        match self {
+            Step::Add(a, b) => values[a] + values[b],
+            Step::Sub(a, b) => values[a] - values[b],
+            Step::Mul(a, b) => values[a] * values[b],
+            Step::Div(a, b) => values[a] / values[b],
+            Step::Select(c, a, b) => select(values[c], values[a], values[b]),
+            Step::Compare(comparison, a, b) => comparison.apply(values[a], values[b]),
+        }
+    }
+}
+
+// Graphs with fewer than `threshold` distinct nodes run sequentially.
+// These freeze the graph on every call, which can cost as much as the
+// evaluation saves; to evaluate a graph more than once, freeze it once and
+// use FrozenExpr::par_execute_with().
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn par_execute(&self, threshold: usize) -> f32 {
+        self.par_execute_with(&Environment::new(), threshold)
+    }
+
+    pub fn par_execute_with(&self, env: &Environment, threshold: usize) -> f32 {
+        self.freeze().par_execute_with(env, threshold)
+    }
+}
+
+impl DynScalar {
+    pub fn par_execute(&self, threshold: usize) -> f32 {
+        self.scalar.par_execute(threshold)
+    }
+
+    pub fn par_execute_with(&self, env: &Environment, threshold: usize) -> f32 {
+        self.scalar.par_execute_with(env, threshold)
+    }
+}
+
+impl FrozenExpr {
+    pub fn par_execute_with(&self, env: &Environment, threshold: usize) -> f32 {
+        self.par_call(&self.arguments(env), threshold)
+    }
+
+    // Evaluates one level of the graph at a time, where a node's level is one
+    // past its deepest operand, so every node in a level can run at once.
+    // Leaves are read on the calling thread in node order, which keeps noise
+    // sampling identical to call().
+    pub fn par_call(&self, args: &[f32], threshold: usize) -> f32 {
+        self.with_context(|context| self.par_call_in(context, args, threshold))
+    }
+
+    pub fn par_call_in(&self, context: &mut EvalContext, args: &[f32], threshold: usize) -> f32 {
+        if self.nodes.len() < threshold {
+            return self.call_in(context, args);
+        }
+        let EvalContext { values, noise } = context;
+        let mut streams = noise.iter_mut();
+        values.clear();
+        values.resize(self.nodes.len(), 0.);
+        let mut depth = vec![0; self.nodes.len()];
+        let mut levels: Vec<Vec<(usize, Step)>> = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let step = match *node {
+                Node::Add(a, b) => Step::Add(a, b),
+                Node::Sub(a, b) => Step::Sub(a, b),
+                Node::Mul(a, b) => Step::Mul(a, b),
+                Node::Div(a, b) => Step::Div(a, b),
+                Node::Select(c, a, b) => Step::Select(c, a, b),
+                Node::Compare(comparison, a, b) => Step::Compare(comparison, a, b),
+                _ => {
+                    values[i] = node.evaluate(values, args, &mut streams);
+                    continue;
+                }
+            };
+            depth[i] = 1 + node.operands().iter().map(|&o| depth[o]).max().unwrap();
+            if levels.len() < depth[i] {
+                levels.push(Vec::new());
+            }
+            levels[depth[i] - 1].push((i, step));
+        }
+        for level in &levels {
+            let results: Vec<f32> = level
+                .par_iter()
+                .map(|&(_, step)| step.evaluate(values))
+                .collect();
+            for (&(i, ..), value) in level.iter().zip(results) {
+                values[i] = value;
+            }
+        }
+        values[self.root]
+    }
+}
+
+impl Program {
+    // run_batch_seeded() with the rows spread over the thread pool. Results
+    // come back in the order of the rows and match the serial run exactly.
+    pub fn par_run_batch_seeded(&self, rows: &[&[f32]], seed: u64) -> Vec<f32> {
+        let lanes = LaneProgram::new(self);
+        let results: Vec<Vec<f32>> = rows
+            .par_chunks(LANES)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut results = Vec::with_capacity(chunk.len() * self.outputs().len());
+                self.run_chunk_seeded(
+                    &lanes,
+                    &mut Vec::new(),
+                    chunk,
+                    index * LANES,
+                    seed,
+                    &mut results,
+                );
+                results
+            })
+            .collect();
+        results.concat()
+    }
+}
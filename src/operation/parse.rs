@@ -0,0 +1,180 @@
+use std::{iter::Peekable, str::CharIndices};
+
+use super::{Comparison, DynScalar, Scalar};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ParseError {
+    UnexpectedCharacter { position: usize, character: char },
+    InvalidNumber { position: usize },
+    UnexpectedEnd,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedCharacter {
+                position,
+                character,
+            } => write!(f, "unexpected {:?} at {}", character, position),
+            ParseError::InvalidNumber { position } => write!(f, "invalid number at {}", position),
+            ParseError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// comparison := expr (('==' | '!=' | '<' | '<=' | '>' | '>=') expr)?
+// expr       := term (('+' | '-') term)*
+// term       := unary (('*' | '/') unary)*
+// unary      := '-' unary | '(' comparison ')' | number | identifier
+pub fn parse(source: &str) -> Result<DynScalar, ParseError> {
+    let mut parser = Parser {
+        source,
+        chars: source.char_indices().peekable(),
+    };
+    let expr = parser.comparison()?;
+    match parser.next() {
+        Some((position, character)) => Err(ParseError::UnexpectedCharacter {
+            position,
+            character,
+        }),
+        None => Ok(expr),
+    }
+}
+
+impl std::str::FromStr for DynScalar {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        parse(source)
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        self.peek();
+        self.chars.next()
+    }
+
+    // Comparisons don't chain: `a < b < c` stops at the second `<`.
+    fn comparison(&mut self) -> Result<DynScalar, ParseError> {
+        let lhs = self.expr()?;
+        if !matches!(self.peek(), Some('<' | '>' | '=' | '!')) {
+            return Ok(lhs);
+        }
+        let (position, character) = self.next().unwrap();
+        let equals = self.chars.next_if(|&(_, c)| c == '=').is_some();
+        let comparison = match (character, equals) {
+            ('<', false) => Comparison::Lt,
+            ('<', true) => Comparison::Le,
+            ('>', false) => Comparison::Gt,
+            ('>', true) => Comparison::Ge,
+            ('=', true) => Comparison::Eq,
+            ('!', true) => Comparison::Ne,
+            (character, _) => {
+                return Err(ParseError::UnexpectedCharacter {
+                    position,
+                    character,
+                })
+            }
+        };
+        Ok(lhs.compare(comparison, &self.expr()?))
+    }
+
+    fn expr(&mut self) -> Result<DynScalar, ParseError> {
+        let mut lhs = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.next();
+            let rhs = self.term()?;
+            lhs = if op == '+' {
+                lhs.add(&rhs)
+            } else {
+                lhs.sub(&rhs)
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<DynScalar, ParseError> {
+        let mut lhs = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.next();
+            let rhs = self.unary()?;
+            lhs = if op == '*' {
+                lhs.mul(&rhs)
+            } else {
+                lhs.div(&rhs)
+            };
+        }
+        Ok(lhs)
+    }
+
+    // Negated literals become negative constants; anything else is scaled by
+    // -1 rather than subtracted from 0 so that -0 keeps its sign.
+    fn unary(&mut self) -> Result<DynScalar, ParseError> {
+        let (position, character) = self.next().ok_or(ParseError::UnexpectedEnd)?;
+        match character {
+            '-' => {
+                if matches!(self.peek(), Some(c) if c.is_ascii_digit() || c == '.') {
+                    let (position, _) = self.next().unwrap();
+                    Ok(Scalar::new(-self.number(position)?).into_dyn())
+                } else {
+                    Ok(Scalar::new(-1.).into_dyn().mul(&self.unary()?))
+                }
+            }
+            '(' => {
+                let expr = self.comparison()?;
+                match self.next() {
+                    Some((_, ')')) => Ok(expr),
+                    Some((position, character)) => Err(ParseError::UnexpectedCharacter {
+                        position,
+                        character,
+                    }),
+                    None => Err(ParseError::UnexpectedEnd),
+                }
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                Ok(Scalar::new(self.number(position)?).into_dyn())
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let end = self.skip_while(|c| c.is_alphanumeric() || c == '_');
+                Ok(Scalar::variable(&self.source[position..end]).into_dyn())
+            }
+            character => Err(ParseError::UnexpectedCharacter {
+                position,
+                character,
+            }),
+        }
+    }
+
+    // Called with the first character of the literal already consumed.
+    fn number(&mut self, start: usize) -> Result<f32, ParseError> {
+        let mut end = self.skip_while(|c| c.is_ascii_digit() || c == '.');
+        if let Some(&(_, 'e' | 'E')) = self.chars.peek() {
+            self.chars.next();
+            self.chars.next_if(|&(_, c)| c == '+' || c == '-');
+            end = self.skip_while(|c| c.is_ascii_digit());
+        }
+        self.source[start..end]
+            .parse()
+            .map_err(|_| ParseError::InvalidNumber { position: start })
+    }
+
+    fn skip_while(&mut self, predicate: impl Fn(char) -> bool) -> usize {
+        while self.chars.next_if(|&(_, c)| predicate(c)).is_some() {}
+        self.chars
+            .peek()
+            .map_or(self.source.len(), |&(position, _)| position)
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashMap, fmt::Display};
+
+use super::{
+    instruction, CompileError, Dual, DynScalar, Environment, EvalError, EvalPolicy, OpKind,
+    Operation, RegisterAllocator, Scalar,
+};
+
+#[derive(Clone)]
+pub struct Wildcard {
+    name: String,
+}
+
+impl Scalar<Wildcard> {
+    pub fn wildcard(name: impl Into<String>) -> Self {
+        Self::from_operation(Wildcard { name: name.into() })
+    }
+}
+
+impl Display for Wildcard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "?{}", self.name)
+    }
+}
+
+impl Operation for Wildcard {
+    fn execute(&self, _env: &Environment) -> f32 {
+        panic!("cannot execute pattern wildcard ?{}", self.name)
+    }
+
+    fn try_execute(&self, _env: &Environment, _policy: EvalPolicy) -> Result<f32, EvalError> {
+        panic!("cannot execute pattern wildcard ?{}", self.name)
+    }
+
+    fn execute_dual(&self, _env: &Environment, _wrt: &str) -> Dual {
+        panic!("cannot execute pattern wildcard ?{}", self.name)
+    }
+
+    fn compile(
+        &mut self,
+        _registers: &mut RegisterAllocator,
+        _instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        panic!("cannot compile pattern wildcard ?{}", self.name)
+    }
+
+    fn reset_compile(&mut self) {}
+
+    fn kind(&self) -> OpKind {
+        OpKind::Wildcard(self.name.clone())
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.name.capacity()
+    }
+}
+
+#[derive(Clone)]
+pub struct Match {
+    pub node: DynScalar,
+    pub bindings: HashMap<String, DynScalar>,
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn find_all<P: Operation + ?Sized>(&self, pattern: &Scalar<P>) -> Vec<Match> {
+        self.clone()
+            .into_dyn()
+            .find_all(&pattern.clone().into_dyn())
+    }
+}
+
+impl DynScalar {
+    // Matches in preorder, each distinct node tried once.
+    pub fn find_all(&self, pattern: &DynScalar) -> Vec<Match> {
+        self.preorder()
+            .filter_map(|node| matches_at(pattern, &node).map(|bindings| Match { node, bindings }))
+            .collect()
+    }
+}
+
+// Walks the pattern and the node together, left to right, so a wildcard is
+// bound where it first appears and must match that binding everywhere after.
+fn matches_at(pattern: &DynScalar, node: &DynScalar) -> Option<HashMap<String, DynScalar>> {
+    let mut bindings: HashMap<String, DynScalar> = HashMap::new();
+    let mut stack = vec![(pattern.clone(), node.clone())];
+    while let Some((pattern, node)) = stack.pop() {
+        let kind = pattern.kind();
+        if let OpKind::Wildcard(name) = kind {
+            match bindings.get(&name) {
+                Some(bound) if *bound != node => return None,
+                Some(_) => {}
+                None => {
+                    bindings.insert(name, node);
+                }
+            }
+            continue;
+        }
+        let (patterns, nodes) = (pattern.children(), node.children());
+        if kind != node.kind() || patterns.len() != nodes.len() {
+            return None;
+        }
+        stack.extend(patterns.into_iter().zip(nodes).rev());
+    }
+    Some(bindings)
+}
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, fmt::Display};
+
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+use super::{analysis::postorder, DynScalar, Environment, EvalLog, OpKind};
+
+pub struct NodePrecision {
+    pub node: DynScalar,
+    pub single: f32,
+    pub double: f64,
+    // None once a value is infinite, NaN or divided by zero.
+    pub exact: Option<BigRational>,
+}
+
+impl NodePrecision {
+    pub fn relative_error(&self) -> f64 {
+        self.error_of(self.single as f64)
+    }
+
+    pub fn double_relative_error(&self) -> f64 {
+        self.error_of(self.double)
+    }
+
+    fn error_of(&self, value: f64) -> f64 {
+        let reference = match &self.exact {
+            Some(exact) => exact.to_f64().unwrap_or(self.double),
+            None => self.double,
+        };
+        if value == reference || (value.is_nan() && reference.is_nan()) {
+            0.
+        } else if reference == 0. {
+            f64::INFINITY
+        } else {
+            ((value - reference) / reference).abs()
+        }
+    }
+}
+
+impl Display for NodePrecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: f32 {} f64 {} exact {} error {:e}",
+            self.node.kind(),
+            self.single,
+            self.double,
+            match &self.exact {
+                Some(exact) => exact.to_string(),
+                None => "-".to_string(),
+            },
+            self.relative_error()
+        )
+    }
+}
+
+pub struct PrecisionReport {
+    nodes: Vec<NodePrecision>,
+}
+
+impl PrecisionReport {
+    // Every distinct node, operands before the nodes that use them.
+    pub fn nodes(&self) -> &[NodePrecision] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> &NodePrecision {
+        self.nodes.last().unwrap()
+    }
+
+    pub fn worst(&self) -> &NodePrecision {
+        self.nodes
+            .iter()
+            .max_by(|a, b| a.relative_error().total_cmp(&b.relative_error()))
+            .unwrap()
+    }
+
+    // The f32 and f64 values as logs, to diff one against the other.
+    pub fn single_log(&self) -> EvalLog {
+        self.log(|node| node.single as f64)
+    }
+
+    pub fn double_log(&self) -> EvalLog {
+        self.log(|node| node.double)
+    }
+
+    fn log(&self, value: impl Fn(&NodePrecision) -> f64) -> EvalLog {
+        let nodes: Vec<DynScalar> = self.nodes.iter().map(|node| node.node.clone()).collect();
+        EvalLog::from_nodes(
+            &nodes,
+            self.nodes.iter().map(|node| Some(value(node))).collect(),
+        )
+    }
+
+    // The first node whose f32 value drifts past the tolerance; all of its
+    // operands are still within it, so this is where precision is lost.
+    pub fn first_divergence(&self, tolerance: f64) -> Option<&NodePrecision> {
+        self.nodes
+            .iter()
+            .find(|node| node.relative_error() > tolerance)
+    }
+}
+
+impl Display for PrecisionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            writeln!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+// Evaluates every node in f32, f64 and exact rational arithmetic. Every
+// finite float is a rational, so leaves are exact except for symbols, whose
+// exact value is taken from their f64 approximation. Noise is sampled once
+// and shared by all three modes.
+pub fn compare_precisions(expr: &DynScalar, env: &Environment) -> PrecisionReport {
+    let order = postorder(expr);
+    let mut index = HashMap::new();
+    let mut nodes: Vec<NodePrecision> = Vec::with_capacity(order.len());
+    for node in order {
+        let operands: Vec<&NodePrecision> = node
+            .children()
+            .iter()
+            .map(|child| &nodes[index[&child.id()]])
+            .collect();
+        let (single, double, exact) = match (node.kind(), operands.as_slice()) {
+            (OpKind::Add, [a, b]) => (
+                a.single + b.single,
+                a.double + b.double,
+                a.exact.as_ref().zip(b.exact.as_ref()).map(|(a, b)| a + b),
+            ),
+            (OpKind::Sub, [a, b]) => (
+                a.single - b.single,
+                a.double - b.double,
+                a.exact.as_ref().zip(b.exact.as_ref()).map(|(a, b)| a - b),
+            ),
+            (OpKind::Mul, [a, b]) => (
+                a.single * b.single,
+                a.double * b.double,
+                a.exact.as_ref().zip(b.exact.as_ref()).map(|(a, b)| a * b),
+            ),
+            (OpKind::Div, [a, b]) => (
+                a.single / b.single,
+                a.double / b.double,
+                a.exact
+                    .as_ref()
+                    .zip(b.exact.as_ref().filter(|b| !b.is_zero()))
+                    .map(|(a, b)| a / b),
+            ),
+            (OpKind::Select, [condition, then, otherwise]) => {
+                let pick = |taken: bool| if taken { then } else { otherwise };
+                (
+                    pick(condition.single != 0.).single,
+                    pick(condition.double != 0.).double,
+                    condition
+                        .exact
+                        .as_ref()
+                        .and_then(|c| pick(!c.is_zero()).exact.clone()),
+                )
+            }
+            (OpKind::Compare(comparison), [a, b]) => (
+                comparison.apply(a.single, b.single),
+                f64::from(u8::from(comparison.holds(a.double, b.double))),
+                a.exact.as_ref().zip(b.exact.as_ref()).map(|(a, b)| {
+                    BigRational::from_integer(u8::from(comparison.holds(a, b)).into())
+                }),
+            ),
+            (OpKind::Alias | OpKind::Provenance(_), [target]) => {
+                (target.single, target.double, target.exact.clone())
+            }
+            (OpKind::Symbol(symbol), _) => {
+                let value = symbol.value();
+                (value as f32, value, BigRational::from_float(value))
+            }
+            _ => {
+                let value = node.execute_with(env);
+                (value, value as f64, BigRational::from_float(value))
+            }
+        };
+        index.insert(node.id(), nodes.len());
+        nodes.push(NodePrecision {
+            node,
+            single,
+            double,
+            exact,
+        });
+    }
+    PrecisionReport { nodes }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use super::{DynScalar, Operation, Scalar};
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn pretty(&self) -> String {
+        self.clone().into_dyn().pretty()
+    }
+}
+
+impl DynScalar {
+    // Renders shared non-leaf subexpressions once as `let tN = ...;` bindings
+    // ahead of the final expression instead of repeating their text.
+    pub fn pretty(&self) -> String {
+        let mut parents = HashMap::new();
+        count_parents(self, &mut parents);
+
+        let mut names = HashMap::new();
+        let mut bindings = Vec::new();
+        let body = render(self, &parents, &mut names, &mut bindings);
+
+        bindings.push(body);
+        bindings.join("\n")
+    }
+}
+
+fn count_parents(node: &DynScalar, parents: &mut HashMap<usize, usize>) {
+    for child in node.children() {
+        let count = parents.entry(child.id()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            count_parents(&child, parents);
+        }
+    }
+}
+
+fn render(
+    node: &DynScalar,
+    parents: &HashMap<usize, usize>,
+    names: &mut HashMap<usize, String>,
+    bindings: &mut Vec<String>,
+) -> String {
+    if let Some(name) = names.get(&node.id()) {
+        return name.clone();
+    }
+    let children = node.children();
+    let rendered: Vec<String> = children
+        .iter()
+        .map(|child| render(child, parents, names, bindings))
+        .collect();
+    let text = node.scalar.node.borrow().render(&rendered);
+    let shared = parents.get(&node.id()).is_some_and(|&count| count > 1);
+    if shared && !children.is_empty() {
+        let name = format!("t{}", names.len());
+        bindings.push(format!("let {} = {};", name, text));
+        names.insert(node.id(), name.clone());
+        name
+    } else {
+        text
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use super::{
+    analysis::postorder,
+    instruction::{Instruction, Opcode, Spill},
+    optimize, CompileError, CompileOptions, DynScalar, Environment, EvalContext, RegisterAllocator,
+};
+use crate::vm;
+
+// Deserializing goes through Program::new(), so a loaded program is as
+// safe to run as one built by hand.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "super::serialize::RawProgram")
+)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    outputs: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProgramError {
+    Unwritten { instruction: usize, register: usize },
+    UnwrittenOutput { register: usize },
+}
+
+impl Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramError::Unwritten {
+                instruction,
+                register,
+            } => write!(
+                f,
+                "instruction {} reads %{} before it is written",
+                instruction, register
+            ),
+            ProgramError::UnwrittenOutput { register } => {
+                write!(f, "output %{} is never written", register)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+impl Program {
+    pub fn compile(roots: &[DynScalar]) -> Self {
+        Self::compile_with(roots, &CompileOptions::new())
+            .expect("unbounded register allocator ran out")
+    }
+
+    // For targets with a fixed register file: fails instead of producing a
+    // program that needs more than `limit` registers after allocation.
+    pub fn compile_with_registers(roots: &[DynScalar], limit: usize) -> Result<Self, CompileError> {
+        Self::compile_with(roots, &CompileOptions::new().with_registers(limit))
+    }
+
+    pub fn compile_with(
+        roots: &[DynScalar],
+        options: &CompileOptions,
+    ) -> Result<Self, CompileError> {
+        for root in roots {
+            options
+                .signature()
+                .check_compilable(root)
+                .map_err(CompileError::Type)?;
+        }
+        let (instructions, outputs) = compile_graph(roots, &mut RegisterAllocator::new())?;
+        let (instructions, outputs) = optimize::cse(&instructions, &outputs);
+        let (instructions, outputs) = options.allocate(&instructions, &outputs)?;
+        Ok(Self {
+            instructions,
+            outputs,
+        })
+    }
+
+    // For programs built or rewritten outside of compile(). Rejects those
+    // that read a register or memory cell before writing it, so the result
+    // is safe to run.
+    pub fn new(instructions: Vec<Instruction>, outputs: Vec<usize>) -> Result<Self, ProgramError> {
+        let mut written = HashSet::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            if let Some(register) = instruction
+                .operands()
+                .into_iter()
+                .find(|register| !written.contains(register))
+            {
+                return Err(ProgramError::Unwritten {
+                    instruction: index,
+                    register,
+                });
+            }
+            written.insert(instruction.ret());
+        }
+        if let Some(&register) = outputs.iter().find(|register| !written.contains(register)) {
+            return Err(ProgramError::UnwrittenOutput { register });
+        }
+        Ok(Self {
+            instructions,
+            outputs,
+        })
+    }
+
+    pub fn fold_constants(&self) -> Self {
+        Self {
+            instructions: optimize::fold(&self.instructions, &self.outputs),
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    pub fn peephole(&self) -> Self {
+        Self {
+            instructions: optimize::simplify(&self.instructions, &self.outputs),
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    // The distinct variables loaded, in order of first use. Backends that
+    // take inputs positionally expect them in this order.
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = Vec::new();
+        for instruction in &self.instructions {
+            if let Opcode::Load(name) = instruction.opcode() {
+                if !variables.contains(&name) {
+                    variables.push(name);
+                }
+            }
+        }
+        variables
+    }
+
+    // Registers used outside of spill code. Memory cells are numbered from
+    // here up.
+    pub fn register_file(&self) -> usize {
+        let mut file = 0;
+        for instruction in &self.instructions {
+            match instruction.spill() {
+                Some(Spill::Store(register)) => file = file.max(register + 1),
+                Some(Spill::Reload(_)) => file = file.max(instruction.ret() + 1),
+                None => {
+                    let highest = instruction.operands().into_iter().max().unwrap_or(0);
+                    file = file.max(instruction.ret().max(highest) + 1);
+                }
+            }
+        }
+        file
+    }
+
+    pub fn memory_cells(&self) -> usize {
+        let file = self.register_file();
+        self.instructions
+            .iter()
+            .filter(|i| matches!(i.spill(), Some(Spill::Store(_))))
+            .map(|i| i.ret() + 1 - file)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Fits an already compiled program into `registers` registers, storing
+    // values to memory where it has to. Works on loaded programs too.
+    pub fn spill(&self, registers: usize) -> Result<Program, CompileError> {
+        // Give every write its own register again, which is what the
+        // spilling allocator expects.
+        let mut current: HashMap<usize, usize> = HashMap::new();
+        let mut renamed = Vec::with_capacity(self.instructions.len());
+        for (value, instruction) in self.instructions.iter().enumerate() {
+            renamed.push(instruction.rename(|r| current[&r], value));
+            current.insert(instruction.ret(), value);
+        }
+        let outputs: Vec<usize> = self.outputs.iter().map(|r| current[r]).collect();
+        let (instructions, outputs) = optimize::spill(&renamed, &outputs, registers)?;
+        Ok(Self {
+            instructions,
+            outputs,
+        })
+    }
+
+    pub fn register_count(&self) -> usize {
+        self.instructions
+            .iter()
+            .map(|i| i.ret() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn run(&self) -> Vec<f32> {
+        self.run_with(&Environment::new())
+    }
+
+    // Each run starts noise over from its seed. To keep it going from run to
+    // run, or to run on several threads at once, hold a context per thread
+    // and use run_in().
+    pub fn run_with(&self, env: &Environment) -> Vec<f32> {
+        self.run_in(&mut self.context(), env)
+    }
+
+    pub fn context(&self) -> EvalContext {
+        EvalContext::new(self.noise_seeds())
+    }
+
+    // Reproducible noise: every source's stream comes from `seed` and the
+    // source's own seed, so editing other parts of the graph leaves it be.
+    pub fn context_with_seed(&self, seed: u64) -> EvalContext {
+        EvalContext::seeded(
+            seed,
+            self.instructions
+                .iter()
+                .filter_map(Instruction::noise_source),
+        )
+    }
+
+    fn noise_seeds(&self) -> impl Iterator<Item = Option<u64>> + '_ {
+        self.instructions
+            .iter()
+            .filter_map(|i| i.noise().map(|(_, seed)| seed))
+    }
+
+    pub fn run_in(&self, context: &mut EvalContext, env: &Environment) -> Vec<f32> {
+        vm::execute(context, &self.instructions, self.register_count(), env);
+        self.outputs
+            .iter()
+            .map(|&ret| context.values[ret])
+            .collect()
+    }
+
+    // Runs with the registers in a stack array and writes the outputs into
+    // the caller's buffer, so nothing is allocated. Both sizes are checked
+    // before any instruction executes.
+    pub fn run_fixed<const N_REGS: usize>(&self, env: &Environment, outputs: &mut [f32]) {
+        self.run_fixed_with_memory::<N_REGS>(env, &mut [], outputs)
+    }
+
+    // As run_fixed, with spilled values kept in the caller's `memory`, which
+    // needs at least memory_cells() entries.
+    pub fn run_fixed_with_memory<const N_REGS: usize>(
+        &self,
+        env: &Environment,
+        memory: &mut [f32],
+        outputs: &mut [f32],
+    ) {
+        let file = self.register_file();
+        assert!(
+            file <= N_REGS,
+            "program needs {} registers, only {} available",
+            file,
+            N_REGS
+        );
+        assert!(
+            self.memory_cells() <= memory.len(),
+            "program needs {} memory cells, only {} available",
+            self.memory_cells(),
+            memory.len()
+        );
+        assert_eq!(
+            outputs.len(),
+            self.outputs.len(),
+            "program has {} outputs",
+            self.outputs.len()
+        );
+        let mut slots = [0.; N_REGS];
+        for instruction in &self.instructions {
+            let ret = instruction.ret();
+            match instruction.spill() {
+                Some(Spill::Store(register)) => memory[ret - file] = slots[register],
+                Some(Spill::Reload(cell)) => slots[ret] = memory[cell - file],
+                None => slots[ret] = instruction.execute(&slots, env),
+            }
+        }
+        for (output, &ret) in outputs.iter_mut().zip(&self.outputs) {
+            *output = match ret.checked_sub(file) {
+                Some(cell) => memory[cell],
+                None => slots[ret],
+            };
+        }
+    }
+}
+
+// Lowers the graphs to one instruction per distinct node, numbering results
+// with `registers`. Nodes are compiled in postorder, so each finds its
+// children already compiled and nothing recurses however deep the graph.
+// Their compile state is cleared on the way out, children first, even if
+// compilation fails part way, so the next compile starts clean.
+pub(super) fn compile_graph(
+    roots: &[DynScalar],
+    registers: &mut RegisterAllocator,
+) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+    let mut instructions = Vec::new();
+    let mut outputs = Vec::with_capacity(roots.len());
+    let mut compiled = Vec::new();
+    let mut result = Ok(());
+    for root in roots {
+        let order = postorder(root);
+        let ret = order.iter().try_fold(0, |_, node| {
+            node.scalar
+                .operation
+                .borrow_mut()
+                .compile(registers, &mut instructions)
+        });
+        compiled.push(order);
+        match ret {
+            Ok(ret) => outputs.push(ret),
+            Err(error) => {
+                result = Err(error);
+                break;
+            }
+        }
+    }
+    for node in compiled.iter().flatten() {
+        node.scalar.operation.borrow_mut().reset_compile();
+    }
+    result.map(|_| (instructions, outputs))
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for instruction in &self.instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+        let outputs: Vec<String> = self.outputs.iter().map(|ret| format!("%{}", ret)).collect();
+        write!(f, "ret {}", outputs.join(", "))
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt::Display;
+
+use super::{
+    instruction, CompileError, Dual, DynScalar, Environment, EvalError, EvalPolicy, OpKind,
+    Operation, RegisterAllocator, Scalar,
+};
+
+// Records where its target came from, say the author, rule and date that
+// produced it, and otherwise forwards the target's value. The note is
+// serialized with the graph and copied onto the instruction that computes
+// the target, so it shows up in the compiled program and its IR.
+pub struct Provenance {
+    target: DynScalar,
+    note: String,
+}
+
+impl Scalar<Provenance> {
+    pub fn target(&self) -> DynScalar {
+        self.operation.borrow().target.clone()
+    }
+
+    pub fn note(&self) -> String {
+        self.operation.borrow().note.clone()
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn with_provenance(&self, note: impl Into<String>) -> Scalar<Provenance> {
+        Scalar::from_operation(Provenance {
+            target: self.clone().into_dyn(),
+            note: note.into(),
+        })
+    }
+}
+
+impl DynScalar {
+    pub fn with_provenance(&self, note: impl Into<String>) -> Scalar<Provenance> {
+        self.scalar.with_provenance(note)
+    }
+
+    // The note, if this node is a provenance node.
+    pub fn provenance(&self) -> Option<String> {
+        match self.kind() {
+            OpKind::Provenance(note) => Some(note),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.target)
+    }
+}
+
+impl Operation for Provenance {
+    fn execute(&self, env: &Environment) -> f32 {
+        self.target.scalar.operation.borrow().execute(env)
+    }
+
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        self.target
+            .scalar
+            .operation
+            .borrow()
+            .try_execute(env, policy)
+    }
+
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        self.target.scalar.operation.borrow().execute_dual(env, wrt)
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        let ret = self
+            .target
+            .scalar
+            .operation
+            .borrow_mut()
+            .compile(registers, instructions)?;
+        // The last write to the register is the one holding the target.
+        if let Some(instruction) = instructions.iter_mut().rev().find(|i| i.ret() == ret) {
+            instruction.annotate(&self.note);
+        }
+        Ok(ret)
+    }
+
+    fn reset_compile(&mut self) {
+        self.target.scalar.operation.borrow_mut().reset_compile();
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Provenance(self.note.clone())
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![self.target.clone()]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        children[0].clone()
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use super::{calibrate::Histogram, instruction::Opcode, Comparison, Environment, Program};
+
+// The integer type values are stored in. Arithmetic happens in wider
+// integers and every result is saturated back into this range.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuantType {
+    U8,
+    I16,
+}
+
+impl QuantType {
+    pub fn min(self) -> i32 {
+        match self {
+            QuantType::U8 => 0,
+            QuantType::I16 => i16::MIN as i32,
+        }
+    }
+
+    pub fn max(self) -> i32 {
+        match self {
+            QuantType::U8 => u8::MAX as i32,
+            QuantType::I16 => i16::MAX as i32,
+        }
+    }
+}
+
+// Affine quantization: q stands for scale * (q - zero_point). Ranges are
+// widened to take in 0, so zero is always exact, which select relies on.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct QuantParams {
+    pub scale: f32,
+    pub zero_point: i32,
+}
+
+impl QuantParams {
+    pub fn from_range(min: f32, max: f32, ty: QuantType) -> Self {
+        let (min, max) = (min.min(0.), max.max(0.));
+        let steps = (ty.max() - ty.min()) as f32;
+        let scale = if max > min { (max - min) / steps } else { 1. };
+        let zero_point = (ty.min() as f32 - min / scale).round() as i32;
+        Self {
+            scale,
+            zero_point: zero_point.clamp(ty.min(), ty.max()),
+        }
+    }
+
+    pub fn quantize(&self, value: f32, ty: QuantType) -> i32 {
+        let q = (value / self.scale).round() as i64 + self.zero_point as i64;
+        q.clamp(ty.min() as i64, ty.max() as i64) as i32
+    }
+
+    pub fn dequantize(&self, q: i32) -> f32 {
+        self.scale * (q - self.zero_point) as f32
+    }
+}
+
+// The range of values each instruction of a program produces, in
+// instruction order, with their histograms when it came from calibrate().
+#[derive(Clone, PartialEq, Debug)]
+pub struct Calibration {
+    ranges: Vec<(f32, f32)>,
+    histograms: Vec<Histogram>,
+}
+
+impl Calibration {
+    pub fn new(ranges: Vec<(f32, f32)>) -> Self {
+        Self::with_histograms(ranges, Vec::new())
+    }
+
+    pub(super) fn with_histograms(ranges: Vec<(f32, f32)>, histograms: Vec<Histogram>) -> Self {
+        Self { ranges, histograms }
+    }
+
+    pub fn ranges(&self) -> &[(f32, f32)] {
+        &self.ranges
+    }
+
+    pub fn histograms(&self) -> &[Histogram] {
+        &self.histograms
+    }
+
+    // Ranges covering `coverage` of the recorded values, so rare outliers
+    // don't stretch the scales for everything else. Without histograms the
+    // ranges are kept as they are.
+    pub fn clipped(&self, coverage: f32) -> Calibration {
+        if self.histograms.is_empty() {
+            return self.clone();
+        }
+        Self {
+            ranges: self.histograms.iter().map(|h| h.range(coverage)).collect(),
+            histograms: self.histograms.clone(),
+        }
+    }
+
+    // The params Program::quantize() gives each instruction.
+    pub fn params(&self, ty: QuantType) -> Vec<QuantParams> {
+        self.ranges
+            .iter()
+            .map(|&(min, max)| QuantParams::from_range(min, max, ty))
+            .collect()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum QuantizeError {
+    Unsupported(String),
+    RangeCount { expected: usize, found: usize },
+}
+
+impl std::fmt::Display for QuantizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuantizeError::Unsupported(instruction) => {
+                write!(f, "cannot quantize `{}`", instruction)
+            }
+            QuantizeError::RangeCount { expected, found } => write!(
+                f,
+                "expected a range for each of {} instructions, found {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QuantizeError {}
+
+// A positive real factor as mantissa * 2^-shift, with the mantissa in
+// [2^30, 2^31), so rescaling needs integer multiplies and shifts only.
+#[derive(Clone, Copy, Debug)]
+struct Multiplier {
+    mantissa: i64,
+    shift: i32,
+}
+
+impl Multiplier {
+    fn new(real: f64) -> Self {
+        if real == 0. {
+            return Self {
+                mantissa: 0,
+                shift: 0,
+            };
+        }
+        let mut exponent = real.log2().floor() as i32;
+        let mut mantissa = (real * 2f64.powi(30 - exponent)).round() as i64;
+        if mantissa == 1 << 31 {
+            mantissa >>= 1;
+            exponent += 1;
+        }
+        Self {
+            mantissa,
+            shift: 30 - exponent,
+        }
+    }
+
+    // Rounds half up.
+    fn apply(self, x: i128) -> i128 {
+        let product = x * self.mantissa as i128;
+        match self.shift {
+            shift if shift > 0 => (product + (1 << (shift - 1))) >> shift,
+            shift => product << -shift,
+        }
+    }
+}
+
+// A register read, rescaled from the params it was written with.
+#[derive(Clone, Copy, Debug)]
+struct Operand {
+    register: usize,
+    zero_point: i32,
+    multiplier: Multiplier,
+}
+
+impl Operand {
+    fn read(&self, slots: &[i32]) -> i128 {
+        self.multiplier
+            .apply((slots[self.register] - self.zero_point) as i128)
+    }
+
+    fn raw(&self, slots: &[i32]) -> i128 {
+        (slots[self.register] - self.zero_point) as i128
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Constant(i32),
+    Input(usize, Multiplier),
+    Copy(Operand),
+    Add(Operand, Operand),
+    Sub(Operand, Operand),
+    Mul(Operand, Operand, Multiplier),
+    Div(Operand, Operand, Multiplier),
+    Select(Operand, Operand, Operand),
+    Compare(Comparison, Operand, Operand, i32),
+}
+
+// Extra bits kept in the quotient of a division before it is rescaled.
+const DIVISION_BITS: u32 = 16;
+
+// A program in integer arithmetic only. Each value has the quantization
+// params of its calibrated range; inputs are quantized with the params of
+// the first load of their variable.
+#[derive(Clone, Debug)]
+pub struct QuantizedProgram {
+    ty: QuantType,
+    steps: Vec<(Step, usize)>,
+    params: Vec<QuantParams>,
+    variables: Vec<String>,
+    inputs: Vec<QuantParams>,
+    outputs: Vec<(usize, QuantParams)>,
+    registers: usize,
+}
+
+impl Program {
+    pub fn quantize(
+        &self,
+        calibration: &Calibration,
+        ty: QuantType,
+    ) -> Result<QuantizedProgram, QuantizeError> {
+        let instructions = self.instructions();
+        if calibration.ranges().len() != instructions.len() {
+            return Err(QuantizeError::RangeCount {
+                expected: instructions.len(),
+                found: calibration.ranges().len(),
+            });
+        }
+        let params = calibration.params(ty);
+        let variables = self.variables();
+        let mut inputs: Vec<Option<QuantParams>> = vec![None; variables.len()];
+        // Which instruction last wrote each register, for its params.
+        let mut writers: HashMap<usize, usize> = HashMap::new();
+        let mut steps = Vec::with_capacity(instructions.len());
+        for (i, instruction) in instructions.iter().enumerate() {
+            let out = params[i];
+            let source = |register: usize| params[writers[&register]];
+            let operand = |register: usize, factor: f64| Operand {
+                register,
+                zero_point: source(register).zero_point,
+                multiplier: Multiplier::new(source(register).scale as f64 * factor),
+            };
+            let rescaled = |register: usize| operand(register, 1. / out.scale as f64);
+            let step = match instruction.opcode() {
+                Opcode::Constant(value) => Step::Constant(out.quantize(value, ty)),
+                Opcode::Load(name) => {
+                    let index = variables.iter().position(|v| *v == name).unwrap();
+                    let input = *inputs[index].get_or_insert(out);
+                    Step::Input(index, Multiplier::new((input.scale / out.scale) as f64))
+                }
+                Opcode::Noise(..) => {
+                    return Err(QuantizeError::Unsupported(instruction.to_string()))
+                }
+                Opcode::Store(a) | Opcode::Reload(a) => Step::Copy(rescaled(a)),
+                Opcode::Add(a, b) => Step::Add(rescaled(a), rescaled(b)),
+                Opcode::Sub(a, b) => Step::Sub(rescaled(a), rescaled(b)),
+                Opcode::Mul(a, b) => {
+                    let scale = source(a).scale as f64 * source(b).scale as f64;
+                    let multiplier = Multiplier::new(scale / out.scale as f64);
+                    Step::Mul(operand(a, 1.), operand(b, 1.), multiplier)
+                }
+                Opcode::Div(a, b) => {
+                    let scale = source(a).scale as f64 / source(b).scale as f64;
+                    let multiplier =
+                        Multiplier::new(scale / out.scale as f64 / (1u64 << DIVISION_BITS) as f64);
+                    Step::Div(operand(a, 1.), operand(b, 1.), multiplier)
+                }
+                Opcode::Select(c, a, b) => Step::Select(operand(c, 1.), rescaled(a), rescaled(b)),
+                Opcode::Compare(comparison, a, b) => {
+                    // Both sides onto a scale fine enough to tell apart any
+                    // two values either can hold.
+                    let finest = source(a).scale.min(source(b).scale) as f64;
+                    let factor = 256. / finest;
+                    Step::Compare(
+                        comparison,
+                        operand(a, factor),
+                        operand(b, factor),
+                        out.quantize(1., ty),
+                    )
+                }
+            };
+            steps.push((step, instruction.ret()));
+            writers.insert(instruction.ret(), i);
+        }
+        let inputs = inputs.into_iter().map(|input| input.unwrap()).collect();
+        let outputs = self
+            .outputs()
+            .iter()
+            .map(|&ret| (ret, params[writers[&ret]]))
+            .collect();
+        Ok(QuantizedProgram {
+            ty,
+            steps,
+            params,
+            variables,
+            inputs,
+            outputs,
+            registers: self.register_count(),
+        })
+    }
+}
+
+impl QuantizedProgram {
+    pub fn ty(&self) -> QuantType {
+        self.ty
+    }
+
+    // The params of each instruction's result, in instruction order.
+    pub fn params(&self) -> &[QuantParams] {
+        &self.params
+    }
+
+    // Variables in the order run_quantized() takes them.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn input_params(&self) -> &[QuantParams] {
+        &self.inputs
+    }
+
+    pub fn output_params(&self) -> Vec<QuantParams> {
+        self.outputs.iter().map(|&(_, params)| params).collect()
+    }
+
+    // Quantizes the inputs, runs in integers and dequantizes the outputs.
+    pub fn run_with(&self, env: &Environment) -> Vec<f32> {
+        let inputs: Vec<i32> = self
+            .variables
+            .iter()
+            .zip(&self.inputs)
+            .map(|(name, params)| {
+                let value = env
+                    .get(name)
+                    .unwrap_or_else(|| panic!("unbound variable {}", name));
+                params.quantize(value, self.ty)
+            })
+            .collect();
+        self.run_quantized(&inputs)
+            .into_iter()
+            .zip(&self.outputs)
+            .map(|(q, (_, params))| params.dequantize(q))
+            .collect()
+    }
+
+    pub fn run_quantized(&self, inputs: &[i32]) -> Vec<i32> {
+        assert_eq!(
+            inputs.len(),
+            self.variables.len(),
+            "expected values for {:?}",
+            self.variables
+        );
+        let mut slots = vec![0; self.registers];
+        for ((step, ret), params) in self.steps.iter().zip(&self.params) {
+            let zero = params.zero_point as i128;
+            let value = match *step {
+                Step::Constant(q) => q as i128,
+                Step::Input(index, multiplier) => {
+                    let input = &self.inputs[index];
+                    multiplier.apply((inputs[index] - input.zero_point) as i128) + zero
+                }
+                Step::Copy(a) => a.read(&slots) + zero,
+                Step::Add(a, b) => a.read(&slots) + b.read(&slots) + zero,
+                Step::Sub(a, b) => a.read(&slots) - b.read(&slots) + zero,
+                Step::Mul(a, b, multiplier) => {
+                    multiplier.apply(a.raw(&slots) * b.raw(&slots)) + zero
+                }
+                Step::Div(a, b, multiplier) => match (a.raw(&slots), b.raw(&slots)) {
+                    (0, 0) => zero,
+                    (a, 0) if a > 0 => self.ty.max() as i128,
+                    (_, 0) => self.ty.min() as i128,
+                    (a, b) => multiplier.apply((a << DIVISION_BITS) / b) + zero,
+                },
+                Step::Select(c, a, b) => {
+                    if c.raw(&slots) != 0 {
+                        a.read(&slots) + zero
+                    } else {
+                        b.read(&slots) + zero
+                    }
+                }
+                Step::Compare(comparison, a, b, one) => {
+                    if comparison.holds(a.read(&slots), b.read(&slots)) {
+                        one as i128
+                    } else {
+                        zero
+                    }
+                }
+            };
+            slots[*ret] = value.clamp(self.ty.min() as i128, self.ty.max() as i128) as i32;
+        }
+        self.outputs.iter().map(|&(ret, _)| slots[ret]).collect()
+    }
+}
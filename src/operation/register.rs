@@ -0,0 +1,191 @@
+use std::{collections::HashMap, fmt::Display, rc::Rc};
+
+use super::{instruction::Instruction, optimize, Signature, TypeError};
+
+// Hands out registers in increasing order, up to an optional limit. Graph
+// compilation numbers every node's result with one, and register allocation
+// uses a bounded one to map them onto a fixed register file.
+#[derive(Clone, Debug, Default)]
+pub struct RegisterAllocator {
+    next: usize,
+    limit: Option<usize>,
+}
+
+impl RegisterAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            next: 0,
+            limit: Some(limit),
+        }
+    }
+
+    pub fn alloc(&mut self) -> Result<usize, CompileError> {
+        match self.limit {
+            Some(limit) if self.next >= limit => Err(CompileError::OutOfRegisters { limit }),
+            _ => {
+                self.next += 1;
+                Ok(self.next - 1)
+            }
+        }
+    }
+
+    pub fn allocated(&self) -> usize {
+        self.next
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CompileError {
+    OutOfRegisters { limit: usize },
+    Type(TypeError),
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::OutOfRegisters { limit } => {
+                write!(f, "program needs more than {} registers", limit)
+            }
+            CompileError::Type(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+// Maps the single-assignment registers a graph compiles to onto the
+// registers a program runs with. `limit`, if given, is the size of the
+// target's register file.
+pub trait RegisterStrategy {
+    fn allocate(
+        &self,
+        instructions: &[Instruction],
+        outputs: &[usize],
+        limit: Option<usize>,
+    ) -> Result<(Vec<Instruction>, Vec<usize>), CompileError>;
+}
+
+// One register per value, numbered in order of definition.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sequential;
+
+// Reuses a register once the last instruction reading it has run. This is
+// what Program::compile does.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinearScan;
+
+// Like LinearScan, but when the register file is full values are stored to
+// memory and reloaded instead of failing. Needs at least two registers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Spilling;
+
+impl RegisterStrategy for Sequential {
+    fn allocate(
+        &self,
+        instructions: &[Instruction],
+        outputs: &[usize],
+        limit: Option<usize>,
+    ) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+        let mut allocator = allocator(limit);
+        let mut registers = HashMap::new();
+        let mut allocated = Vec::with_capacity(instructions.len());
+        for instruction in instructions {
+            registers.insert(instruction.ret(), allocator.alloc()?);
+            allocated.push(instruction.remap(|r| registers[&r]));
+        }
+        let outputs = outputs.iter().map(|output| registers[output]).collect();
+        Ok((allocated, outputs))
+    }
+}
+
+impl RegisterStrategy for LinearScan {
+    fn allocate(
+        &self,
+        instructions: &[Instruction],
+        outputs: &[usize],
+        limit: Option<usize>,
+    ) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+        optimize::allocate(instructions, outputs, &mut allocator(limit))
+    }
+}
+
+impl RegisterStrategy for Spilling {
+    fn allocate(
+        &self,
+        instructions: &[Instruction],
+        outputs: &[usize],
+        limit: Option<usize>,
+    ) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+        match limit {
+            Some(limit) => optimize::spill(instructions, outputs, limit),
+            None => LinearScan.allocate(instructions, outputs, None),
+        }
+    }
+}
+
+fn allocator(limit: Option<usize>) -> RegisterAllocator {
+    match limit {
+        Some(limit) => RegisterAllocator::with_limit(limit),
+        None => RegisterAllocator::new(),
+    }
+}
+
+#[derive(Clone)]
+pub struct CompileOptions {
+    registers: Option<usize>,
+    strategy: Rc<dyn RegisterStrategy>,
+    signature: Signature,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            registers: None,
+            strategy: Rc::new(LinearScan),
+            signature: Signature::new(),
+        }
+    }
+}
+
+impl CompileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_registers(mut self, registers: usize) -> Self {
+        self.registers = Some(registers);
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: impl RegisterStrategy + 'static) -> Self {
+        self.strategy = Rc::new(strategy);
+        self
+    }
+
+    // The types roots are checked against before compiling.
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = signature;
+        self
+    }
+
+    pub fn registers(&self) -> Option<usize> {
+        self.registers
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    pub(super) fn allocate(
+        &self,
+        instructions: &[Instruction],
+        outputs: &[usize],
+    ) -> Result<(Vec<Instruction>, Vec<usize>), CompileError> {
+        self.strategy
+            .allocate(instructions, outputs, self.registers)
+    }
+}
@@ -0,0 +1,430 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crate::conformance::{ulp_report, UlpReport};
+
+use super::{fnv1a, stream, uniform, DynScalar, Environment, Program};
+
+const MAGIC: &[u8; 4] = b"LZRG";
+
+// How a replacement is checked against the program it replaces. Each
+// variable of a sampled input is uniform over `range`; `inputs` are checked
+// as well, for the cases sampling is unlikely to hit. A forced swap skips
+// the comparison but still requires the same variables.
+#[derive(Clone, Debug)]
+pub struct SwapOptions {
+    samples: usize,
+    range: (f32, f32),
+    seed: u64,
+    max_ulps: u64,
+    inputs: Vec<Environment>,
+    force: bool,
+}
+
+impl Default for SwapOptions {
+    fn default() -> Self {
+        Self {
+            samples: 256,
+            range: (-100., 100.),
+            seed: 0,
+            max_ulps: 4,
+            inputs: Vec::new(),
+            force: false,
+        }
+    }
+}
+
+impl SwapOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_range(mut self, low: f32, high: f32) -> Self {
+        assert!(low <= high, "range is empty");
+        self.range = (low, high);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_max_ulps(mut self, max_ulps: u64) -> Self {
+        self.max_ulps = max_ulps;
+        self
+    }
+
+    pub fn with_inputs(mut self, inputs: Vec<Environment>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    fn inputs(&self, variables: &[String]) -> Vec<Environment> {
+        let (low, high) = self.range;
+        let mut inputs = self.inputs.clone();
+        inputs.extend((0..self.samples).map(|i| {
+            let mut state = stream(self.seed, i as u64);
+            variables
+                .iter()
+                .map(|name| (name.as_str(), low + (high - low) * uniform(&mut state)))
+                .collect::<Environment>()
+        }));
+        inputs
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum RegistryError {
+    Registered(String),
+    Unknown(String),
+    // Variables are sorted by name.
+    Variables {
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+    Nonconforming(UlpReport),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::Registered(name) => write!(f, "`{}` is already registered", name),
+            RegistryError::Unknown(name) => write!(f, "`{}` is not registered", name),
+            RegistryError::Variables { expected, found } => write!(
+                f,
+                "expected variables [{}], found [{}]",
+                expected.join(", "),
+                found.join(", ")
+            ),
+            RegistryError::Nonconforming(report) => {
+                write!(f, "replacement does not conform: {}", report)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+struct Slot {
+    // Every program the name has had, oldest first; version n is at n - 1
+    // and the last is current.
+    versions: RwLock<Vec<Arc<Program>>>,
+    metadata: RwLock<BTreeMap<String, String>>,
+    // Held while a replacement is checked, so swaps of a name happen one
+    // at a time and each is checked against the program it replaces.
+    swapping: Mutex<()>,
+}
+
+impl Slot {
+    fn new(versions: Vec<Arc<Program>>, metadata: BTreeMap<String, String>) -> Self {
+        Self {
+            versions: RwLock::new(versions),
+            metadata: RwLock::new(metadata),
+            swapping: Mutex::new(()),
+        }
+    }
+
+    fn current(&self) -> Arc<Program> {
+        self.versions.read().unwrap().last().unwrap().clone()
+    }
+}
+
+// Compiled programs by name, shared between threads. Evaluators look their
+// program up on every run, so a swap takes effect on their next run; a run
+// already under way finishes on the program it started with. Registering a
+// name makes version 1 and each swap adds the next; older versions stay
+// available, along with free-form metadata, and save() writes all of it
+// to disk for load().
+#[derive(Default)]
+pub struct Registry {
+    slots: RwLock<HashMap<String, Arc<Slot>>>,
+    options: SwapOptions,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The options hot_swap() checks replacements with.
+    pub fn with_swap_options(mut self, options: SwapOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn register(
+        &self,
+        name: impl Into<String>,
+        expr: impl Into<DynScalar>,
+    ) -> Result<(), RegistryError> {
+        let name = name.into();
+        let mut slots = self.slots.write().unwrap();
+        if slots.contains_key(&name) {
+            return Err(RegistryError::Registered(name));
+        }
+        let slot = Slot::new(vec![Arc::new(compile(expr.into()))], BTreeMap::new());
+        slots.insert(name, Arc::new(slot));
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.slots.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Program>> {
+        Some(self.slot(name)?.current())
+    }
+
+    // The current version of a name; versions count up from 1.
+    pub fn version(&self, name: &str) -> Option<usize> {
+        Some(self.slot(name)?.versions.read().unwrap().len())
+    }
+
+    pub fn get_version(&self, name: &str, version: usize) -> Option<Arc<Program>> {
+        let slot = self.slot(name)?;
+        let versions = slot.versions.read().unwrap();
+        versions.get(version.checked_sub(1)?).cloned()
+    }
+
+    pub fn metadata(&self, name: &str) -> Option<BTreeMap<String, String>> {
+        Some(self.slot(name)?.metadata.read().unwrap().clone())
+    }
+
+    pub fn set_metadata(
+        &self,
+        name: &str,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), RegistryError> {
+        let slot = self
+            .slot(name)
+            .ok_or_else(|| RegistryError::Unknown(name.to_string()))?;
+        slot.metadata
+            .write()
+            .unwrap()
+            .insert(key.into(), value.into());
+        Ok(())
+    }
+
+    pub fn evaluator(&self, name: &str) -> Option<Evaluator> {
+        Some(Evaluator {
+            name: name.to_string(),
+            slot: self.slot(name)?,
+        })
+    }
+
+    pub fn hot_swap(
+        &self,
+        name: &str,
+        new_expr: impl Into<DynScalar>,
+    ) -> Result<(), RegistryError> {
+        self.hot_swap_with(name, new_expr, &self.options)
+    }
+
+    // Compiles the replacement, checks it has the same variables as the
+    // current program and, unless forced, that the two agree to within
+    // max_ulps on every input, then swaps it in for every evaluator.
+    pub fn hot_swap_with(
+        &self,
+        name: &str,
+        new_expr: impl Into<DynScalar>,
+        options: &SwapOptions,
+    ) -> Result<(), RegistryError> {
+        let slot = self
+            .slot(name)
+            .ok_or_else(|| RegistryError::Unknown(name.to_string()))?;
+        let replacement = compile(new_expr.into());
+        let _swapping = slot.swapping.lock().unwrap();
+        let current = slot.current();
+        let (expected, found) = (sorted(current.variables()), sorted(replacement.variables()));
+        if expected != found {
+            return Err(RegistryError::Variables { expected, found });
+        }
+        if !options.force {
+            let report = ulp_report(&current, &replacement, &options.inputs(&expected));
+            if report.max > options.max_ulps {
+                return Err(RegistryError::Nonconforming(report));
+            }
+        }
+        slot.versions.write().unwrap().push(Arc::new(replacement));
+        Ok(())
+    }
+
+    // One file holding every name with its versions and metadata, behind a
+    // checksum of the rest. Programs are stored as bytecode, so a swap
+    // racing with the save is either in the file or not. Written to a
+    // temporary file first and renamed into place, like ArtifactCache.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut contents = MAGIC.to_vec();
+        let names = self.names();
+        write_length(&mut contents, names.len());
+        for name in names {
+            let slot = self.slot(&name).unwrap();
+            write_bytes(&mut contents, name.as_bytes());
+            let metadata = slot.metadata.read().unwrap().clone();
+            write_length(&mut contents, metadata.len());
+            for (key, value) in &metadata {
+                write_bytes(&mut contents, key.as_bytes());
+                write_bytes(&mut contents, value.as_bytes());
+            }
+            let versions = slot.versions.read().unwrap().clone();
+            write_length(&mut contents, versions.len());
+            for program in versions {
+                write_bytes(&mut contents, &program.to_bytes());
+            }
+        }
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", std::process::id()));
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(&fnv1a(&contents).to_le_bytes())?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        fs::rename(&temporary, path)
+    }
+
+    // Reads a file written by save(); the registry it returns checks swaps
+    // with the default options. Anything malformed is InvalidData.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Registry> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 8 {
+            return Err(invalid("registry file is truncated"));
+        }
+        let (checksum, contents) = bytes.split_at(8);
+        if checksum != fnv1a(contents).to_le_bytes() {
+            return Err(invalid("registry file checksum does not match"));
+        }
+        let mut reader = Reader { bytes: contents };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a registry file"));
+        }
+        let mut slots = HashMap::new();
+        for _ in 0..reader.length()? {
+            let name = reader.string()?;
+            let mut metadata = BTreeMap::new();
+            for _ in 0..reader.length()? {
+                metadata.insert(reader.string()?, reader.string()?);
+            }
+            let mut versions = Vec::new();
+            for _ in 0..reader.length()? {
+                let program = Program::from_bytes(reader.bytes()?)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+                versions.push(Arc::new(program));
+            }
+            if versions.is_empty() {
+                return Err(invalid("registered name has no versions"));
+            }
+            if slots
+                .insert(name, Arc::new(Slot::new(versions, metadata)))
+                .is_some()
+            {
+                return Err(invalid("name is registered twice"));
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(invalid("registry file has trailing bytes"));
+        }
+        Ok(Registry {
+            slots: RwLock::new(slots),
+            options: SwapOptions::default(),
+        })
+    }
+
+    fn slot(&self, name: &str) -> Option<Arc<Slot>> {
+        self.slots.read().unwrap().get(name).cloned()
+    }
+}
+
+// A handle on a registered program that follows its swaps. Handles are
+// cheap to clone and to send to other threads.
+#[derive(Clone)]
+pub struct Evaluator {
+    name: String,
+    slot: Arc<Slot>,
+}
+
+impl Evaluator {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // The program as of now; holding on to it pins it across swaps.
+    pub fn program(&self) -> Arc<Program> {
+        self.slot.current()
+    }
+
+    pub fn run_with(&self, env: &Environment) -> Vec<f32> {
+        self.program().run_with(env)
+    }
+}
+
+fn compile(expr: DynScalar) -> Program {
+    Program::compile(&[expr])
+}
+
+fn write_length(contents: &mut Vec<u8>, length: usize) {
+    contents.extend_from_slice(&(length as u64).to_le_bytes());
+}
+
+fn write_bytes(contents: &mut Vec<u8>, bytes: &[u8]) {
+    write_length(contents, bytes.len());
+    contents.extend_from_slice(bytes);
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("registry file is truncated"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn length(&mut self) -> io::Result<usize> {
+        let length = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(length).map_err(|_| invalid("length does not fit in memory"))
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let length = self.length()?;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let bytes = self.bytes()?;
+        let string = std::str::from_utf8(bytes).map_err(|_| invalid("name is not utf-8"))?;
+        Ok(string.to_string())
+    }
+}
+
+fn sorted(mut variables: Vec<String>) -> Vec<String> {
+    variables.sort();
+    variables
+}
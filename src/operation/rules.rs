@@ -0,0 +1,119 @@
+use std::cell::OnceCell;
+
+use super::{DynScalar, Environment, Program};
+
+// A named condition gating a value. The condition holds where it is
+// non-zero, as for select(), so comparisons and products of comparisons
+// make natural conditions.
+#[derive(Clone)]
+pub struct Rule {
+    name: String,
+    condition: DynScalar,
+    value: DynScalar,
+}
+
+impl Rule {
+    pub fn new(
+        name: impl Into<String>,
+        condition: impl Into<DynScalar>,
+        value: impl Into<DynScalar>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            condition: condition.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn condition(&self) -> &DynScalar {
+        &self.condition
+    }
+
+    pub fn value(&self) -> &DynScalar {
+        &self.value
+    }
+}
+
+// Rules in priority order over a default value: the first rule whose
+// condition holds decides. The whole set is one graph of selects, so it
+// compiles, serializes and exports like any other expression, and each
+// rule's value carries the rule's name as provenance.
+#[derive(Clone)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    default: DynScalar,
+    program: OnceCell<Program>,
+}
+
+// The outcome of evaluating a rule set: the value, the rule that decided
+// it, if any, and every rule whose condition held, in priority order.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Decision {
+    pub value: f32,
+    pub applied: Option<String>,
+    pub fired: Vec<String>,
+}
+
+impl RuleSet {
+    pub fn new(default: impl Into<DynScalar>) -> Self {
+        Self {
+            rules: Vec::new(),
+            default: default.into(),
+            program: OnceCell::new(),
+        }
+    }
+
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self.program = OnceCell::new();
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn default_value(&self) -> &DynScalar {
+        &self.default
+    }
+
+    pub fn expr(&self) -> DynScalar {
+        self.rules
+            .iter()
+            .rev()
+            .fold(self.default.clone(), |otherwise, rule| {
+                let value = rule
+                    .value
+                    .with_provenance(format!("rule {}", rule.name))
+                    .into_dyn();
+                rule.condition.select(&value, &otherwise)
+            })
+    }
+
+    // Outputs the decided value and then each rule's condition.
+    pub fn compile(&self) -> Program {
+        let mut roots = vec![self.expr()];
+        roots.extend(self.rules.iter().map(|rule| rule.condition.clone()));
+        Program::compile(&roots)
+    }
+
+    pub fn evaluate(&self, env: &Environment) -> Decision {
+        let outputs = self.program.get_or_init(|| self.compile()).run_with(env);
+        let fired: Vec<String> = self
+            .rules
+            .iter()
+            .zip(&outputs[1..])
+            .filter(|(_, &condition)| condition != 0.)
+            .map(|(rule, _)| rule.name.clone())
+            .collect();
+        Decision {
+            value: outputs[0],
+            applied: fired.first().cloned(),
+            fired,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{cell::Cell, fmt::Write};
+
+use super::{
+    instruction::{Opcode, Unsupported},
+    shader::is_parameter,
+    Program,
+};
+
+pub type Closure = Box<dyn Fn(&[f32]) -> f32>;
+
+type Step = Box<dyn Fn(&mut [f32], &[f32])>;
+
+impl Program {
+    // Renders `pub fn eval` taking the variables as f32 parameters. Every
+    // write shadows the register's previous binding, so reused registers
+    // need no `mut`.
+    pub fn to_rust(&self) -> Result<String, Unsupported> {
+        let variables = self.variables();
+        let mut body = String::new();
+        for instruction in self.instructions() {
+            let value = match instruction.opcode() {
+                Opcode::Constant(value) if value.is_finite() => format!("{:?}_f32", value),
+                Opcode::Constant(value) => format!("f32::from_bits({:#010x})", value.to_bits()),
+                Opcode::Load(name) if is_parameter(&name) => name,
+                Opcode::Load(_) | Opcode::Noise(..) => {
+                    return Err(Unsupported {
+                        instruction: instruction.to_string(),
+                    })
+                }
+                Opcode::Add(a, b) => format!("t{} + t{}", a, b),
+                Opcode::Sub(a, b) => format!("t{} - t{}", a, b),
+                Opcode::Mul(a, b) => format!("t{} * t{}", a, b),
+                Opcode::Div(a, b) => format!("t{} / t{}", a, b),
+                Opcode::Store(a) | Opcode::Reload(a) => format!("t{}", a),
+                Opcode::Select(c, a, b) => {
+                    format!("if t{} != 0.0 {{ t{} }} else {{ t{} }}", c, a, b)
+                }
+                Opcode::Compare(comparison, a, b) => {
+                    format!("f32::from(u8::from(t{} {} t{}))", a, comparison, b)
+                }
+            };
+            writeln!(body, "    let t{} = {};", instruction.ret(), value).unwrap();
+        }
+
+        let parameters: Vec<String> = variables
+            .iter()
+            .map(|name| format!("{}: f32", name))
+            .collect();
+        let outputs: Vec<String> = self
+            .outputs()
+            .iter()
+            .map(|ret| format!("t{}", ret))
+            .collect();
+        let (ty, result) = match outputs.as_slice() {
+            [output] => ("f32".to_string(), output.clone()),
+            _ => (
+                format!("[f32; {}]", outputs.len()),
+                format!("[{}]", outputs.join(", ")),
+            ),
+        };
+        Ok(format!(
+            "pub fn eval({}) -> {} {{\n{}    {}\n}}\n",
+            parameters.join(", "),
+            ty,
+            body,
+            result
+        ))
+    }
+
+    // Builds one closure per instruction and runs them in order over a
+    // register buffer. Arguments are given in the order of variables().
+    pub fn to_closure(&self) -> Closure {
+        assert_eq!(self.outputs().len(), 1, "to_closure needs a single output");
+        let variables = self.variables();
+        let steps: Vec<Step> = self
+            .instructions()
+            .iter()
+            .map(|instruction| -> Step {
+                let ret = instruction.ret();
+                match instruction.opcode() {
+                    Opcode::Constant(value) => Box::new(move |slots, _| slots[ret] = value),
+                    Opcode::Load(name) => {
+                        let index = variables.iter().position(|v| *v == name).unwrap();
+                        Box::new(move |slots, args| slots[ret] = args[index])
+                    }
+                    Opcode::Noise(distribution, seed) => {
+                        let state = Cell::new(seed.unwrap_or_else(super::entropy));
+                        Box::new(move |slots, _| {
+                            let mut next = state.get();
+                            slots[ret] = distribution.sample(&mut next);
+                            state.set(next);
+                        })
+                    }
+                    Opcode::Add(a, b) => Box::new(move |slots, _| slots[ret] = slots[a] + slots[b]),
+                    Opcode::Sub(a, b) => Box::new(move |slots, _| slots[ret] = slots[a] - slots[b]),
+                    Opcode::Mul(a, b) => Box::new(move |slots, _| slots[ret] = slots[a] * slots[b]),
+                    Opcode::Div(a, b) => Box::new(move |slots, _| slots[ret] = slots[a] / slots[b]),
+                    Opcode::Store(a) | Opcode::Reload(a) => {
+                        Box::new(move |slots, _| slots[ret] = slots[a])
+                    }
+                    Opcode::Select(c, a, b) => Box::new(move |slots, _| {
+                        slots[ret] = if slots[c] != 0. { slots[a] } else { slots[b] }
+                    }),
+                    Opcode::Compare(comparison, a, b) => {
+                        Box::new(move |slots, _| slots[ret] = comparison.apply(slots[a], slots[b]))
+                    }
+                }
+            })
+            .collect();
+        let arity = variables.len();
+        let register_count = self.register_count();
+        let result = self.outputs()[0];
+        Box::new(move |args: &[f32]| {
+            assert_eq!(args.len(), arity, "expected {} arguments", arity);
+            let mut slots = vec![0.; register_count];
+            for step in &steps {
+                step(&mut slots, args);
+            }
+            slots[result]
+        })
+    }
+}
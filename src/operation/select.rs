@@ -0,0 +1,152 @@
+use std::fmt::Display;
+
+use super::{
+    instruction, CompileError, Dual, DynScalar, Environment, EvalError, EvalPolicy, OpKind,
+    Operation, RegisterAllocator, Scalar,
+};
+
+// `then` where the condition is non-zero, `otherwise` elsewhere. execute()
+// only evaluates the arm it picks. Compiled programs are straight-line and
+// evaluate both, which gives the same value unless the other arm samples
+// noise, whose stream then advances anyway.
+#[derive(Clone)]
+pub struct Select<C, T, E>
+where
+    C: Operation + ?Sized,
+    T: Operation + ?Sized,
+    E: Operation + ?Sized,
+{
+    condition: Scalar<C>,
+    then: Scalar<T>,
+    otherwise: Scalar<E>,
+    compile_ret: Option<usize>,
+}
+
+impl<C, T, E> Select<C, T, E>
+where
+    C: Operation + ?Sized,
+    T: Operation + ?Sized,
+    E: Operation + ?Sized,
+{
+    pub(super) fn new(condition: &Scalar<C>, then: &Scalar<T>, otherwise: &Scalar<E>) -> Self {
+        Self {
+            condition: condition.clone(),
+            then: then.clone(),
+            otherwise: otherwise.clone(),
+            compile_ret: None,
+        }
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn select<T, E>(&self, then: &Scalar<T>, otherwise: &Scalar<E>) -> Scalar<Select<O, T, E>>
+    where
+        T: Operation + ?Sized,
+        E: Operation + ?Sized,
+    {
+        Scalar::from_operation(Select::new(self, then, otherwise))
+    }
+}
+
+impl<C, T, E> Display for Select<C, T, E>
+where
+    C: Operation + ?Sized,
+    T: Operation + ?Sized,
+    E: Operation + ?Sized,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "select({}, {}, {})",
+            self.condition, self.then, self.otherwise
+        )
+    }
+}
+
+impl<C, T, E> Operation for Select<C, T, E>
+where
+    C: Operation + ?Sized,
+    T: Operation + ?Sized,
+    E: Operation + ?Sized,
+{
+    fn execute(&self, env: &Environment) -> f32 {
+        if self.condition.operation.borrow().execute(env) != 0. {
+            self.then.operation.borrow().execute(env)
+        } else {
+            self.otherwise.operation.borrow().execute(env)
+        }
+    }
+
+    fn try_execute(&self, env: &Environment, policy: EvalPolicy) -> Result<f32, EvalError> {
+        if self.condition.operation.borrow().try_execute(env, policy)? != 0. {
+            self.then.operation.borrow().try_execute(env, policy)
+        } else {
+            self.otherwise.operation.borrow().try_execute(env, policy)
+        }
+    }
+
+    // The condition is piecewise constant, so only the chosen arm carries a
+    // derivative.
+    fn execute_dual(&self, env: &Environment, wrt: &str) -> Dual {
+        if self.condition.operation.borrow().execute(env) != 0. {
+            self.then.operation.borrow().execute_dual(env, wrt)
+        } else {
+            self.otherwise.operation.borrow().execute_dual(env, wrt)
+        }
+    }
+
+    fn compile(
+        &mut self,
+        registers: &mut RegisterAllocator,
+        instructions: &mut Vec<instruction::Instruction>,
+    ) -> Result<usize, CompileError> {
+        match self.compile_ret {
+            Some(ret) => Ok(ret),
+            None => {
+                let condition = self
+                    .condition
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let then = self
+                    .then
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let otherwise = self
+                    .otherwise
+                    .operation
+                    .borrow_mut()
+                    .compile(registers, instructions)?;
+                let ret = registers.alloc()?;
+                self.compile_ret = Some(ret);
+                instructions.push(instruction::select(condition, then, otherwise, ret));
+                Ok(ret)
+            }
+        }
+    }
+
+    fn reset_compile(&mut self) {
+        if self.compile_ret.take().is_some() {
+            self.condition.operation.borrow_mut().reset_compile();
+            self.then.operation.borrow_mut().reset_compile();
+            self.otherwise.operation.borrow_mut().reset_compile();
+        }
+    }
+
+    fn kind(&self) -> OpKind {
+        OpKind::Select
+    }
+
+    fn children(&self) -> Vec<DynScalar> {
+        vec![
+            self.condition.clone().into_dyn(),
+            self.then.clone().into_dyn(),
+            self.otherwise.clone().into_dyn(),
+        ]
+    }
+
+    fn render(&self, children: &[String]) -> String {
+        format!("select({}, {}, {})", children[0], children[1], children[2])
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    analysis::postorder,
+    instruction::{Instruction, Opcode},
+    DynScalar, OpKind, Operation, Program, ProgramError, Scalar,
+};
+
+#[derive(Serialize, Deserialize)]
+struct SerializedInstruction {
+    op: Opcode,
+    ret: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    provenance: Option<String>,
+}
+
+impl Serialize for Instruction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedInstruction {
+            op: self.opcode(),
+            ret: self.ret(),
+            provenance: self.provenance().map(str::to_string),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Instruction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let instruction = SerializedInstruction::deserialize(deserializer)?;
+        let mut decoded = Instruction::from_opcode(instruction.op, instruction.ret);
+        if let Some(provenance) = &instruction.provenance {
+            decoded.annotate(provenance);
+        }
+        Ok(decoded)
+    }
+}
+
+// Program's fields as stored, before Program::new() has checked them.
+#[derive(Deserialize)]
+pub(super) struct RawProgram {
+    instructions: Vec<Instruction>,
+    outputs: Vec<usize>,
+}
+
+impl TryFrom<RawProgram> for Program {
+    type Error = ProgramError;
+
+    fn try_from(raw: RawProgram) -> Result<Self, ProgramError> {
+        Program::new(raw.instructions, raw.outputs)
+    }
+}
+
+// Graphs are stored as their distinct nodes in dependency order, operands
+// referring to earlier nodes by index and the root last, so sharing
+// survives the round trip.
+#[derive(Serialize, Deserialize)]
+struct SerializedNode {
+    kind: OpKind,
+    operands: Vec<usize>,
+}
+
+impl Serialize for DynScalar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nodes = postorder(self);
+        let index: HashMap<usize, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id(), i))
+            .collect();
+        let serialized: Vec<SerializedNode> = nodes
+            .iter()
+            .map(|node| SerializedNode {
+                kind: node.kind(),
+                operands: node.children().iter().map(|c| index[&c.id()]).collect(),
+            })
+            .collect();
+        serialized.serialize(serializer)
+    }
+}
+
+impl<O: Operation + ?Sized> Serialize for Scalar<O> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.clone().into_dyn().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DynScalar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = Vec::<SerializedNode>::deserialize(deserializer)?;
+        let mut nodes: Vec<DynScalar> = Vec::with_capacity(serialized.len());
+        for node in serialized {
+            let arity = match node.kind {
+                OpKind::Alias | OpKind::Provenance(_) => 1,
+                OpKind::Add | OpKind::Sub | OpKind::Mul | OpKind::Div | OpKind::Compare(_) => 2,
+                OpKind::Select => 3,
+                _ => 0,
+            };
+            if node.operands.len() != arity {
+                return Err(D::Error::custom(format!(
+                    "{} takes {} operands, found {}",
+                    node.kind,
+                    arity,
+                    node.operands.len()
+                )));
+            }
+            let operands = node
+                .operands
+                .iter()
+                .map(|&i| nodes.get(i).cloned())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| D::Error::custom("operand refers to a later node"))?;
+            nodes.push(DynScalar::from_kind(node.kind, &operands));
+        }
+        nodes
+            .pop()
+            .ok_or_else(|| D::Error::custom("graph has no nodes"))
+    }
+}
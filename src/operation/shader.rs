@@ -0,0 +1,135 @@
+use std::{collections::HashSet, fmt::Write};
+
+use super::{
+    instruction::{Opcode, Unsupported},
+    Program,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Dialect {
+    Wgsl,
+    Glsl,
+}
+
+impl Program {
+    pub fn to_wgsl(&self) -> Result<String, Unsupported> {
+        render(self, Dialect::Wgsl)
+    }
+
+    pub fn to_glsl(&self) -> Result<String, Unsupported> {
+        render(self, Dialect::Glsl)
+    }
+}
+
+// Renders `eval`, taking the variables as parameters in order of first use.
+// Register N becomes local tN, declared on its first write since registers
+// are reused after allocation. Several outputs are returned as an array.
+fn render(program: &Program, dialect: Dialect) -> Result<String, Unsupported> {
+    let mut parameters: Vec<String> = Vec::new();
+    let mut declared = HashSet::new();
+    let mut body = String::new();
+    for instruction in program.instructions() {
+        let value = match instruction.opcode() {
+            Opcode::Constant(value) => literal(value, dialect),
+            Opcode::Load(name) if is_parameter(&name) => {
+                if !parameters.contains(&name) {
+                    parameters.push(name.clone());
+                }
+                name
+            }
+            Opcode::Load(_) | Opcode::Noise(..) => {
+                return Err(Unsupported {
+                    instruction: instruction.to_string(),
+                })
+            }
+            Opcode::Add(a, b) => format!("t{} + t{}", a, b),
+            Opcode::Sub(a, b) => format!("t{} - t{}", a, b),
+            Opcode::Mul(a, b) => format!("t{} * t{}", a, b),
+            Opcode::Div(a, b) => format!("t{} / t{}", a, b),
+            Opcode::Store(a) | Opcode::Reload(a) => format!("t{}", a),
+            Opcode::Select(c, a, b) => match dialect {
+                Dialect::Wgsl => format!("select(t{}, t{}, t{} != 0.0)", b, a, c),
+                Dialect::Glsl => format!("t{} != 0.0 ? t{} : t{}", c, a, b),
+            },
+            Opcode::Compare(comparison, a, b) => match dialect {
+                Dialect::Wgsl => format!("select(0.0, 1.0, t{} {} t{})", a, comparison, b),
+                Dialect::Glsl => format!("float(t{} {} t{})", a, comparison, b),
+            },
+        };
+        let declaration = match (declared.insert(instruction.ret()), dialect) {
+            (true, Dialect::Wgsl) => "var ",
+            (true, Dialect::Glsl) => "float ",
+            (false, _) => "",
+        };
+        writeln!(
+            body,
+            "    {}t{} = {};",
+            declaration,
+            instruction.ret(),
+            value
+        )
+        .unwrap();
+    }
+
+    let outputs: Vec<String> = program
+        .outputs()
+        .iter()
+        .map(|ret| format!("t{}", ret))
+        .collect();
+    let (ty, result) = match (outputs.len(), dialect) {
+        (1, Dialect::Wgsl) => ("f32".to_string(), outputs[0].clone()),
+        (1, Dialect::Glsl) => ("float".to_string(), outputs[0].clone()),
+        (n, Dialect::Wgsl) => {
+            let ty = format!("array<f32, {}>", n);
+            let result = format!("{}({})", ty, outputs.join(", "));
+            (ty, result)
+        }
+        (n, Dialect::Glsl) => {
+            let ty = format!("float[{}]", n);
+            let result = format!("{}({})", ty, outputs.join(", "));
+            (ty, result)
+        }
+    };
+    let signature = match dialect {
+        Dialect::Wgsl => {
+            let parameters: Vec<String> =
+                parameters.iter().map(|p| format!("{}: f32", p)).collect();
+            format!("fn eval({}) -> {}", parameters.join(", "), ty)
+        }
+        Dialect::Glsl => {
+            let parameters: Vec<String> =
+                parameters.iter().map(|p| format!("float {}", p)).collect();
+            format!("{} eval({})", ty, parameters.join(", "))
+        }
+    };
+    Ok(format!(
+        "{} {{\n{}    return {};\n}}\n",
+        signature, body, result
+    ))
+}
+
+// Neither language has literals for infinities or NaN, so those are spelled
+// as bit casts.
+fn literal(value: f32, dialect: Dialect) -> String {
+    if value.is_finite() {
+        format!("{:?}", value)
+    } else if dialect == Dialect::Wgsl {
+        format!("bitcast<f32>({:#010x}u)", value.to_bits())
+    } else {
+        format!("uintBitsToFloat({:#010x}u)", value.to_bits())
+    }
+}
+
+// Variables become parameters under their own names, so they have to be
+// plain identifiers that cannot clash with the register locals.
+pub(super) fn is_parameter(name: &str) -> bool {
+    let mut chars = name.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let is_register = name
+        .strip_prefix('t')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    is_identifier && !is_register
+}
@@ -0,0 +1,224 @@
+use super::{instruction::Opcode, Comparison, Distribution, Program};
+
+// Rows that go through each instruction together.
+pub const LANES: usize = 8;
+
+// With the simd feature lanes are wide vectors; without it they are arrays
+// the compiler is free to vectorize, with the same results either way.
+#[cfg(feature = "simd")]
+mod lanes {
+    use wide::{f32x8, CmpEq, CmpGe, CmpGt, CmpLe, CmpLt};
+
+    use super::{Comparison, LANES};
+
+    pub(in crate::operation) type Lanes = f32x8;
+
+    pub(super) fn splat(value: f32) -> Lanes {
+        f32x8::splat(value)
+    }
+
+    pub(super) fn from_array(values: [f32; LANES]) -> Lanes {
+        f32x8::from(values)
+    }
+
+    pub(super) fn to_array(lanes: Lanes) -> [f32; LANES] {
+        lanes.to_array()
+    }
+
+    pub(super) fn add(a: Lanes, b: Lanes) -> Lanes {
+        a + b
+    }
+
+    pub(super) fn sub(a: Lanes, b: Lanes) -> Lanes {
+        a - b
+    }
+
+    pub(super) fn mul(a: Lanes, b: Lanes) -> Lanes {
+        a * b
+    }
+
+    pub(super) fn div(a: Lanes, b: Lanes) -> Lanes {
+        a / b
+    }
+
+    // Tests for zero rather than non-zero so NaN conditions pick `then`, as
+    // they do elsewhere.
+    pub(super) fn select(condition: Lanes, then: Lanes, otherwise: Lanes) -> Lanes {
+        condition.cmp_eq(f32x8::ZERO).blend(otherwise, then)
+    }
+
+    // wide's cmp_ne is ordered, so `!=` is built from `==` to stay true for
+    // NaN.
+    pub(super) fn compare(comparison: Comparison, a: Lanes, b: Lanes) -> Lanes {
+        let (one, zero) = (f32x8::ONE, f32x8::ZERO);
+        match comparison {
+            Comparison::Eq => a.cmp_eq(b).blend(one, zero),
+            Comparison::Ne => a.cmp_eq(b).blend(zero, one),
+            Comparison::Lt => a.cmp_lt(b).blend(one, zero),
+            Comparison::Le => a.cmp_le(b).blend(one, zero),
+            Comparison::Gt => a.cmp_gt(b).blend(one, zero),
+            Comparison::Ge => a.cmp_ge(b).blend(one, zero),
+        }
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+mod lanes {
+    use super::{super::frozen::select as select_one, Comparison, LANES};
+
+    pub(in crate::operation) type Lanes = [f32; LANES];
+
+    pub(super) fn splat(value: f32) -> Lanes {
+        [value; LANES]
+    }
+
+    pub(super) fn from_array(values: [f32; LANES]) -> Lanes {
+        values
+    }
+
+    pub(super) fn to_array(lanes: Lanes) -> [f32; LANES] {
+        lanes
+    }
+
+    fn zip(a: Lanes, b: Lanes, f: impl Fn(f32, f32) -> f32) -> Lanes {
+        std::array::from_fn(|i| f(a[i], b[i]))
+    }
+
+    pub(super) fn add(a: Lanes, b: Lanes) -> Lanes {
+        zip(a, b, |a, b| a + b)
+    }
+
+    pub(super) fn sub(a: Lanes, b: Lanes) -> Lanes {
+        zip(a, b, |a, b| a - b)
+    }
+
+    pub(super) fn mul(a: Lanes, b: Lanes) -> Lanes {
+        zip(a, b, |a, b| a * b)
+    }
+
+    pub(super) fn div(a: Lanes, b: Lanes) -> Lanes {
+        zip(a, b, |a, b| a / b)
+    }
+
+    pub(super) fn select(condition: Lanes, then: Lanes, otherwise: Lanes) -> Lanes {
+        std::array::from_fn(|i| select_one(condition[i], then[i], otherwise[i]))
+    }
+
+    pub(super) fn compare(comparison: Comparison, a: Lanes, b: Lanes) -> Lanes {
+        zip(a, b, |a, b| comparison.apply(a, b))
+    }
+}
+
+pub(super) use lanes::Lanes;
+
+#[derive(Clone, Copy)]
+enum Step {
+    Column(usize),
+    // The distribution and the source's position among the noise sources.
+    Noise(Distribution, usize),
+    Constant(f32),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Copy(usize),
+    Select(usize, usize, usize),
+    Compare(Comparison, usize, usize),
+}
+
+// A program lowered to steps over lanes, with loads resolved to columns.
+pub(super) struct LaneProgram {
+    steps: Vec<(Step, usize)>,
+    outputs: Vec<usize>,
+    width: usize,
+    registers: usize,
+}
+
+impl LaneProgram {
+    pub(super) fn new(program: &Program) -> Self {
+        let variables = program.variables();
+        let mut sources = 0;
+        let steps = program
+            .instructions()
+            .iter()
+            .map(|instruction| {
+                let step = match instruction.opcode() {
+                    Opcode::Constant(value) => Step::Constant(value),
+                    Opcode::Load(name) => {
+                        Step::Column(variables.iter().position(|v| *v == name).unwrap())
+                    }
+                    Opcode::Noise(distribution, _) => {
+                        sources += 1;
+                        Step::Noise(distribution, sources - 1)
+                    }
+                    Opcode::Add(a, b) => Step::Add(a, b),
+                    Opcode::Sub(a, b) => Step::Sub(a, b),
+                    Opcode::Mul(a, b) => Step::Mul(a, b),
+                    Opcode::Div(a, b) => Step::Div(a, b),
+                    Opcode::Store(a) | Opcode::Reload(a) => Step::Copy(a),
+                    Opcode::Select(c, a, b) => Step::Select(c, a, b),
+                    Opcode::Compare(comparison, a, b) => Step::Compare(comparison, a, b),
+                };
+                (step, instruction.ret())
+            })
+            .collect();
+        Self {
+            steps,
+            outputs: program.outputs().to_vec(),
+            width: variables.len(),
+            registers: program.register_count(),
+        }
+    }
+
+    // Runs up to LANES rows and appends their outputs, row by row, to
+    // `results`. `noise` draws a sample for a source and lane; lanes past
+    // the last row draw nothing.
+    pub(super) fn run(
+        &self,
+        registers: &mut Vec<Lanes>,
+        rows: &[&[f32]],
+        mut noise: impl FnMut(usize, usize, Distribution) -> f32,
+        results: &mut Vec<f32>,
+    ) {
+        assert!(rows.len() <= LANES);
+        for row in rows {
+            assert_eq!(row.len(), self.width, "expected {} columns", self.width);
+        }
+        registers.clear();
+        registers.resize(self.registers, lanes::splat(0.));
+        for &(step, ret) in &self.steps {
+            let r = &registers[..];
+            let value = match step {
+                Step::Column(i) => lanes::from_array(std::array::from_fn(|lane| {
+                    rows.get(lane).map_or(0., |row| row[i])
+                })),
+                Step::Noise(distribution, source) => {
+                    lanes::from_array(std::array::from_fn(|lane| {
+                        if lane < rows.len() {
+                            noise(source, lane, distribution)
+                        } else {
+                            0.
+                        }
+                    }))
+                }
+                Step::Constant(value) => lanes::splat(value),
+                Step::Add(a, b) => lanes::add(r[a], r[b]),
+                Step::Sub(a, b) => lanes::sub(r[a], r[b]),
+                Step::Mul(a, b) => lanes::mul(r[a], r[b]),
+                Step::Div(a, b) => lanes::div(r[a], r[b]),
+                Step::Copy(a) => r[a],
+                Step::Select(c, a, b) => lanes::select(r[c], r[a], r[b]),
+                Step::Compare(comparison, a, b) => lanes::compare(comparison, r[a], r[b]),
+            };
+            registers[ret] = value;
+        }
+        let outputs: Vec<[f32; LANES]> = self
+            .outputs
+            .iter()
+            .map(|&ret| lanes::to_array(registers[ret]))
+            .collect();
+        for lane in 0..rows.len() {
+            results.extend(outputs.iter().map(|output| output[lane]));
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use super::Program;
+
+// Run counts by program fingerprint, kept across restarts so a service can
+// promote its hot programs straight away. The text form is one
+// `fingerprint runs` pair per line, fingerprint in hex.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExecutionStats {
+    runs: BTreeMap<u64, u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StatsError {
+    pub line: usize,
+}
+
+impl Display for StatsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "malformed statistics on line {}", self.line)
+    }
+}
+
+impl std::error::Error for StatsError {}
+
+impl ExecutionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn runs(&self, program: &Program) -> u64 {
+        self.runs.get(&program.fingerprint()).copied().unwrap_or(0)
+    }
+
+    pub fn set_runs(&mut self, program: &Program, runs: u64) {
+        self.runs.insert(program.fingerprint(), runs);
+    }
+
+    // Combines statistics from several processes by adding their counts.
+    pub fn merge(&mut self, other: &ExecutionStats) {
+        for (&fingerprint, &runs) in &other.runs {
+            *self.runs.entry(fingerprint).or_insert(0) += runs;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+}
+
+impl Display for ExecutionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (fingerprint, runs) in &self.runs {
+            writeln!(f, "{:016x} {}", fingerprint, runs)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ExecutionStats {
+    type Err = StatsError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut runs = BTreeMap::new();
+        for (i, line) in source.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let error = StatsError { line: i + 1 };
+            let (fingerprint, count) = line.trim().split_once(' ').ok_or(error)?;
+            let fingerprint = u64::from_str_radix(fingerprint, 16).map_err(|_| error)?;
+            let count = count.trim().parse().map_err(|_| error)?;
+            runs.insert(fingerprint, count);
+        }
+        Ok(Self { runs })
+    }
+}
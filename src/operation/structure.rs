@@ -0,0 +1,98 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt::Debug,
+    hash::{Hash, Hasher},
+};
+
+use super::{DynScalar, OpKind, Operation, Scalar};
+
+// Two expressions are equal when they have the same shape, the same ops and
+// the same leaves, however their nodes are shared. Constants compare by bit
+// pattern, so NaN equals itself and 0 differs from -0. Unseeded noise only
+// equals the very same node, since two of them never sample alike. Aliases
+// are nodes of their own, as they are to pattern matching; retargeting one
+// changes what an expression equals and hashes to, so don't retarget keys.
+impl PartialEq for DynScalar {
+    fn eq(&self, other: &DynScalar) -> bool {
+        if self.id() == other.id() {
+            return true;
+        }
+        let mut numbers = HashMap::new();
+        let a = fold(self, |key, operands| number(&mut numbers, key, operands));
+        let b = fold(other, |key, operands| number(&mut numbers, key, operands));
+        a == b
+    }
+}
+
+impl Eq for DynScalar {}
+
+impl Hash for DynScalar {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(fold(self, |key, operands| {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            operands.hash(&mut hasher);
+            hasher.finish()
+        }));
+    }
+}
+
+impl<O: Operation + ?Sized, P: Operation + ?Sized> PartialEq<Scalar<P>> for Scalar<O> {
+    fn eq(&self, other: &Scalar<P>) -> bool {
+        self.clone().into_dyn() == other.clone().into_dyn()
+    }
+}
+
+impl<O: Operation + ?Sized> Eq for Scalar<O> {}
+
+impl<O: Operation + ?Sized> Hash for Scalar<O> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.clone().into_dyn().hash(state)
+    }
+}
+
+// So assert_eq! can show expressions that differ.
+impl Debug for DynScalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl<O: Operation + ?Sized> Debug for Scalar<O> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// Combines each distinct node's key with the results of its operands,
+// operands first, without recursing.
+fn fold<T: Clone>(root: &DynScalar, mut combine: impl FnMut(String, Vec<T>) -> T) -> T {
+    let mut results: HashMap<usize, T> = HashMap::new();
+    for node in root.postorder() {
+        let operands = node
+            .children()
+            .iter()
+            .map(|child| results[&child.id()].clone())
+            .collect();
+        results.insert(node.id(), combine(key(&node), operands));
+    }
+    results.remove(&root.id()).unwrap()
+}
+
+fn key(node: &DynScalar) -> String {
+    match node.kind() {
+        kind @ OpKind::Noise(_, None) => format!("{:?} #{}", kind, node.id()),
+        kind => format!("{:?}", kind),
+    }
+}
+
+// Numbers nodes so that two get the same number exactly when they are
+// structurally equal, shared across every graph numbered with `numbers`.
+fn number(
+    numbers: &mut HashMap<(String, Vec<usize>), usize>,
+    key: String,
+    operands: Vec<usize>,
+) -> usize {
+    let next = numbers.len();
+    *numbers.entry((key, operands)).or_insert(next)
+}
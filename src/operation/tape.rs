@@ -0,0 +1,145 @@
+use std::{collections::HashMap, fmt::Display};
+
+use super::{eval, DynScalar, Environment, OpKind, Operation, Scalar};
+
+#[derive(Clone, PartialEq, Debug)]
+enum Step {
+    Variable(String),
+    // Constants, noise and comparisons, which pass no derivative on.
+    Constant,
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    // Aliases, provenance and selects, whose value is an operand's. For a
+    // select that is the arm taken.
+    Copy(usize),
+}
+
+// The Wengert list of one evaluation: every operation in the order it ran,
+// over the values it ran on. Only the arms of selects that were taken are
+// on it, so replaying it backward differentiates the path this evaluation
+// took, and a node used twice is on it twice.
+#[derive(Clone, Debug)]
+pub struct Tape {
+    steps: Vec<(Step, f32)>,
+    // Each step's kind and operands as recorded, for Display.
+    labels: Vec<(String, Vec<usize>)>,
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn execute_taped(&self, env: &Environment) -> Tape {
+        self.clone().into_dyn().execute_taped(env)
+    }
+}
+
+impl DynScalar {
+    // Evaluates as execute_with() does, drawing the same noise, and records
+    // the tape. The evaluator hands over nodes children first, so a stack
+    // of tape positions mirrors its stack of values.
+    pub fn execute_taped(&self, env: &Environment) -> Tape {
+        let mut tape = Tape {
+            steps: Vec::new(),
+            labels: Vec::new(),
+        };
+        let mut stack: Vec<usize> = Vec::new();
+        eval::evaluate_observed(self, env, None, |node, &value: &f32| {
+            let kind = node.kind();
+            let operands = match kind {
+                _ if node.children().is_empty() => Vec::new(),
+                OpKind::Alias | OpKind::Provenance(_) => vec![stack.pop().unwrap()],
+                // Both operands, or a select's condition and the arm taken.
+                _ => {
+                    let b = stack.pop().unwrap();
+                    vec![stack.pop().unwrap(), b]
+                }
+            };
+            let step = match (&kind, operands.as_slice()) {
+                (OpKind::Variable(name), _) => Step::Variable(name.clone()),
+                (OpKind::Alias | OpKind::Provenance(_), &[a]) | (OpKind::Select, &[_, a]) => {
+                    Step::Copy(a)
+                }
+                (OpKind::Add, &[a, b]) => Step::Add(a, b),
+                (OpKind::Sub, &[a, b]) => Step::Sub(a, b),
+                (OpKind::Mul, &[a, b]) => Step::Mul(a, b),
+                (OpKind::Div, &[a, b]) => Step::Div(a, b),
+                _ => Step::Constant,
+            };
+            stack.push(tape.steps.len());
+            tape.steps.push((step, value));
+            tape.labels.push((kind.to_string(), operands));
+        })
+        .expect("evaluation without a policy does not fail");
+        tape
+    }
+}
+
+impl Tape {
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn value(&self) -> f32 {
+        self.steps.last().expect("a tape records its root").1
+    }
+
+    // Replays the tape backward and returns the derivative of the value
+    // with respect to each variable on it.
+    pub fn gradient(&self) -> HashMap<String, f32> {
+        let mut adjoints = vec![0f32; self.steps.len()];
+        *adjoints.last_mut().expect("a tape records its root") = 1.;
+        let mut gradient = HashMap::new();
+        for (i, (step, _)) in self.steps.iter().enumerate().rev() {
+            let adjoint = adjoints[i];
+            let value = |j: usize| self.steps[j].1;
+            match *step {
+                Step::Variable(ref name) => *gradient.entry(name.clone()).or_insert(0.) += adjoint,
+                Step::Constant => {}
+                Step::Add(a, b) => {
+                    adjoints[a] += adjoint;
+                    adjoints[b] += adjoint;
+                }
+                Step::Sub(a, b) => {
+                    adjoints[a] += adjoint;
+                    adjoints[b] -= adjoint;
+                }
+                Step::Mul(a, b) => {
+                    adjoints[a] += adjoint * value(b);
+                    adjoints[b] += adjoint * value(a);
+                }
+                Step::Div(a, b) => {
+                    adjoints[a] += adjoint / value(b);
+                    adjoints[b] -= adjoint * value(a) / (value(b) * value(b));
+                }
+                Step::Copy(a) => adjoints[a] += adjoint,
+            }
+        }
+        gradient
+    }
+
+    // 0 for variables the evaluation never read.
+    pub fn derivative(&self, name: &str) -> f32 {
+        self.gradient().get(name).copied().unwrap_or(0.)
+    }
+}
+
+impl Display for Tape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, ((_, value), (label, operands))) in self.steps.iter().zip(&self.labels).enumerate()
+        {
+            let operands: Vec<String> = operands.iter().map(|j| format!("t{}", j)).collect();
+            match operands.as_slice() {
+                [] => writeln!(f, "t{} = {} = {}", i, label, value)?,
+                [a, b] if label != "select" => {
+                    writeln!(f, "t{} = {} {} {} = {}", i, a, label, b, value)?
+                }
+                operands => writeln!(f, "t{} = {} {} = {}", i, label, operands.join(" "), value)?,
+            }
+        }
+        Ok(())
+    }
+}
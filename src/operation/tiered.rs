@@ -0,0 +1,111 @@
+use super::{
+    jit::check_outputs, ArtifactCache, Environment, ExecutionStats, Jit, JitError, Program,
+};
+use crate::vm::Vm;
+
+// Runs in the interpreter until the program has run `threshold` times, then
+// compiles it to native code and uses that from then on. Programs the JIT
+// cannot handle stay interpreted, and promotion is not retried.
+pub struct TieredProgram {
+    program: Program,
+    vm: Vm,
+    threshold: u64,
+    runs: u64,
+    jit: Option<Result<Jit, JitError>>,
+    cache: Option<ArtifactCache>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tier {
+    Interpreted,
+    Native,
+}
+
+impl TieredProgram {
+    pub const DEFAULT_THRESHOLD: u64 = 100;
+
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            vm: Vm::new(),
+            threshold: Self::DEFAULT_THRESHOLD,
+            runs: 0,
+            jit: None,
+            cache: None,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: u64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    // Promotion goes through the cache, so a restarted process reuses the
+    // native code an earlier one compiled.
+    pub fn with_cache(mut self, cache: ArtifactCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // Carries on from a previous process's run count, compiling right away
+    // if that was already past the threshold, so it goes after
+    // with_threshold() and with_cache().
+    pub fn with_stats(mut self, stats: &ExecutionStats) -> Self {
+        self.runs = stats.runs(&self.program);
+        if self.runs > self.threshold {
+            self.promote();
+        }
+        self
+    }
+
+    pub fn record(&self, stats: &mut ExecutionStats) {
+        stats.set_runs(&self.program, self.runs);
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn tier(&self) -> Tier {
+        match self.jit {
+            Some(Ok(_)) => Tier::Native,
+            _ => Tier::Interpreted,
+        }
+    }
+
+    // Why the program is still interpreted after reaching the threshold.
+    pub fn promotion_error(&self) -> Option<&JitError> {
+        self.jit.as_ref().and_then(|jit| jit.as_ref().err())
+    }
+
+    // Compiles now rather than waiting for the threshold.
+    pub fn promote(&mut self) -> Tier {
+        if self.jit.is_none() {
+            self.jit = Some(match &self.cache {
+                Some(cache) => Jit::compile_cached(&self.program, cache),
+                None => check_outputs(&self.program)
+                    .and_then(|_| Jit::compile(self.program.instructions())),
+            });
+        }
+        self.tier()
+    }
+
+    pub fn run(&mut self) -> Vec<f32> {
+        self.run_with(&Environment::new())
+    }
+
+    pub fn run_with(&mut self, env: &Environment) -> Vec<f32> {
+        self.runs += 1;
+        if self.runs > self.threshold {
+            self.promote();
+        }
+        match &self.jit {
+            Some(Ok(jit)) => vec![jit.call_with(env)],
+            _ => self.vm.run_program(&self.program, env),
+        }
+    }
+}
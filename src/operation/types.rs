@@ -0,0 +1,194 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use super::{Comparison, DynScalar, OpKind};
+
+// The type of a value in a graph. Today every backend computes in f32, so
+// Float64, Int and Vector only come from declared variables, and compiling
+// rejects them; they are here so features that need them check graphs the
+// same way rather than each in their own fashion.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Type {
+    Float32,
+    Float64,
+    Bool,
+    Int,
+    Vector(usize),
+}
+
+impl Type {
+    pub fn is_scalar(self) -> bool {
+        !matches!(self, Type::Vector(_))
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Float32 => write!(f, "f32"),
+            Type::Float64 => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::Int => write!(f, "int"),
+            Type::Vector(n) => write!(f, "vector({})", n),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TypeError {
+    Mismatch { op: String, left: Type, right: Type },
+    NotScalar { op: String, found: Type },
+    Unsupported { found: Type },
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::Mismatch { op, left, right } => {
+                write!(f, "`{}` cannot combine {} and {}", op, left, right)
+            }
+            TypeError::NotScalar { op, found } => {
+                write!(f, "`{}` needs a scalar, found {}", op, found)
+            }
+            TypeError::Unsupported { found } => {
+                write!(f, "f32 programs cannot hold {} values", found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+// The types of a graph's variables; undeclared ones are Float32. Like
+// GraphLimits, it checks whole graphs and builds nodes that are checked as
+// they are made.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Signature {
+    variables: BTreeMap<String, Type>,
+}
+
+impl Signature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_variable(mut self, name: impl Into<String>, ty: Type) -> Self {
+        self.variables.insert(name.into(), ty);
+        self
+    }
+
+    pub fn variable(&self, name: &str) -> Type {
+        self.variables.get(name).copied().unwrap_or(Type::Float32)
+    }
+
+    // The type of the graph's value. Bools are 0 or 1, so next to another
+    // scalar type they take that type, and arithmetic on two of them gives
+    // Float32; otherwise operands must agree, as must the arms of a select.
+    // Comparisons give Bool and select conditions may be any scalar.
+    pub fn check(&self, expr: &DynScalar) -> Result<Type, TypeError> {
+        let mut types: HashMap<usize, Type> = HashMap::new();
+        for node in expr.postorder() {
+            let operands: Vec<Type> = node.children().iter().map(|c| types[&c.id()]).collect();
+            let ty = self.node_type(&node.kind(), &operands)?;
+            types.insert(node.id(), ty);
+        }
+        Ok(types[&expr.id()])
+    }
+
+    // Checks for the f32 backends, which hold Float32 and Bool values.
+    pub(super) fn check_compilable(&self, expr: &DynScalar) -> Result<(), TypeError> {
+        self.check(expr)?;
+        for node in expr.postorder() {
+            if let OpKind::Variable(name) = node.kind() {
+                let found = self.variable(&name);
+                if !matches!(found, Type::Float32 | Type::Bool) {
+                    return Err(TypeError::Unsupported { found });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn node_type(&self, kind: &OpKind, operands: &[Type]) -> Result<Type, TypeError> {
+        let op = || kind.to_string();
+        Ok(match kind {
+            OpKind::Variable(name) => self.variable(name),
+            OpKind::Constant(_) | OpKind::Symbol(_) | OpKind::Noise(..) | OpKind::Wildcard(_) => {
+                Type::Float32
+            }
+            OpKind::Alias | OpKind::Provenance(_) => operands[0],
+            OpKind::Add | OpKind::Sub | OpKind::Mul | OpKind::Div => {
+                arithmetic(op(), operands[0], operands[1])?
+            }
+            OpKind::Compare(_) => match arithmetic(op(), operands[0], operands[1])? {
+                found @ Type::Vector(_) => return Err(TypeError::NotScalar { op: op(), found }),
+                _ => Type::Bool,
+            },
+            OpKind::Select => {
+                if !operands[0].is_scalar() {
+                    return Err(TypeError::NotScalar {
+                        op: op(),
+                        found: operands[0],
+                    });
+                }
+                unify(op(), operands[1], operands[2])?
+            }
+        })
+    }
+
+    pub fn add(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, TypeError> {
+        self.checked(a.add(b))
+    }
+
+    pub fn sub(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, TypeError> {
+        self.checked(a.sub(b))
+    }
+
+    pub fn mul(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, TypeError> {
+        self.checked(a.mul(b))
+    }
+
+    pub fn div(&self, a: &DynScalar, b: &DynScalar) -> Result<DynScalar, TypeError> {
+        self.checked(a.div(b))
+    }
+
+    pub fn compare(
+        &self,
+        comparison: Comparison,
+        a: &DynScalar,
+        b: &DynScalar,
+    ) -> Result<DynScalar, TypeError> {
+        self.checked(a.compare(comparison, b))
+    }
+
+    pub fn select(
+        &self,
+        condition: &DynScalar,
+        then: &DynScalar,
+        otherwise: &DynScalar,
+    ) -> Result<DynScalar, TypeError> {
+        self.checked(condition.select(then, otherwise))
+    }
+
+    fn checked(&self, expr: DynScalar) -> Result<DynScalar, TypeError> {
+        self.check(&expr)?;
+        Ok(expr)
+    }
+}
+
+fn arithmetic(op: String, left: Type, right: Type) -> Result<Type, TypeError> {
+    match (left, right) {
+        (Type::Bool, Type::Bool) => Ok(Type::Float32),
+        (left, right) => unify(op, left, right),
+    }
+}
+
+fn unify(op: String, left: Type, right: Type) -> Result<Type, TypeError> {
+    match (left, right) {
+        (Type::Bool, other) | (other, Type::Bool) if other.is_scalar() => Ok(other),
+        (left, right) if left == right => Ok(left),
+        (left, right) => Err(TypeError::Mismatch { op, left, right }),
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{DynScalar, OpKind, Operation, Scalar};
+
+// A bottom-up fold over a graph. Each distinct node is visited once, after
+// its operands, and its result is handed to every parent, so shared nodes
+// are not counted twice. Constants and variables fall back to visit_leaf(),
+// which also sees symbols, noise and wildcards; aliases and provenance
+// nodes pass their target's result through unless visit_alias() says
+// otherwise.
+pub trait Visitor {
+    type Output: Clone;
+
+    fn visit_leaf(&mut self, kind: &OpKind) -> Self::Output;
+
+    fn visit_binary(&mut self, kind: &OpKind, a: Self::Output, b: Self::Output) -> Self::Output;
+
+    fn visit_select(
+        &mut self,
+        condition: Self::Output,
+        then: Self::Output,
+        otherwise: Self::Output,
+    ) -> Self::Output;
+
+    fn visit_constant(&mut self, value: f32) -> Self::Output {
+        self.visit_leaf(&OpKind::Constant(value))
+    }
+
+    fn visit_variable(&mut self, name: &str) -> Self::Output {
+        self.visit_leaf(&OpKind::Variable(name.to_string()))
+    }
+
+    fn visit_alias(&mut self, target: Self::Output) -> Self::Output {
+        target
+    }
+}
+
+impl<O: Operation + ?Sized> Scalar<O> {
+    pub fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        self.clone().into_dyn().accept(visitor)
+    }
+
+    pub fn preorder(&self) -> Preorder {
+        self.clone().into_dyn().preorder()
+    }
+
+    pub fn postorder(&self) -> Postorder {
+        self.clone().into_dyn().postorder()
+    }
+}
+
+impl DynScalar {
+    pub fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        let mut results: HashMap<usize, V::Output> = HashMap::new();
+        for node in self.postorder() {
+            let mut operands: Vec<V::Output> = node
+                .children()
+                .iter()
+                .map(|child| results[&child.id()].clone())
+                .collect();
+            let kind = node.kind();
+            let result = match kind {
+                OpKind::Constant(value) => visitor.visit_constant(value),
+                OpKind::Variable(ref name) => visitor.visit_variable(name),
+                OpKind::Alias | OpKind::Provenance(_) => visitor.visit_alias(operands.remove(0)),
+                OpKind::Select => {
+                    let otherwise = operands.pop().unwrap();
+                    let then = operands.pop().unwrap();
+                    visitor.visit_select(operands.pop().unwrap(), then, otherwise)
+                }
+                _ if operands.is_empty() => visitor.visit_leaf(&kind),
+                _ => {
+                    let b = operands.pop().unwrap();
+                    let a = operands.pop().unwrap();
+                    visitor.visit_binary(&kind, a, b)
+                }
+            };
+            results.insert(node.id(), result);
+        }
+        results.remove(&self.id()).unwrap()
+    }
+
+    // Each distinct node once, parents before their operands.
+    pub fn preorder(&self) -> Preorder {
+        Preorder {
+            stack: vec![self.clone()],
+            visited: HashSet::new(),
+        }
+    }
+
+    // Each distinct node once, operands before their parents, ending with
+    // this node.
+    pub fn postorder(&self) -> Postorder {
+        Postorder {
+            stack: vec![(self.clone(), false)],
+            visited: HashSet::new(),
+        }
+    }
+}
+
+pub struct Preorder {
+    stack: Vec<DynScalar>,
+    visited: HashSet<usize>,
+}
+
+impl Iterator for Preorder {
+    type Item = DynScalar;
+
+    fn next(&mut self) -> Option<DynScalar> {
+        while let Some(node) = self.stack.pop() {
+            if self.visited.insert(node.id()) {
+                self.stack.extend(node.children().into_iter().rev());
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+pub struct Postorder {
+    stack: Vec<(DynScalar, bool)>,
+    visited: HashSet<usize>,
+}
+
+impl Iterator for Postorder {
+    type Item = DynScalar;
+
+    fn next(&mut self) -> Option<DynScalar> {
+        while let Some((node, expanded)) = self.stack.pop() {
+            if expanded {
+                return Some(node);
+            }
+            if !self.visited.insert(node.id()) {
+                continue;
+            }
+            self.stack.push((node.clone(), true));
+            for child in node.children().into_iter().rev() {
+                self.stack.push((child, false));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,152 @@
+use super::{
+    instruction::{Instruction, Opcode, Unsupported},
+    Comparison,
+};
+
+const F32: u8 = 0x7d;
+
+// A standalone WebAssembly module exporting `eval`, which takes one f32 per
+// variable, in the order given by variables(), and returns the result.
+pub struct WasmModule {
+    bytes: Vec<u8>,
+    variables: Vec<String>,
+}
+
+impl WasmModule {
+    pub fn compile(instructions: &[Instruction]) -> Result<Self, Unsupported> {
+        let result = instructions
+            .last()
+            .expect("cannot compile an empty program")
+            .ret();
+        let mut variables: Vec<String> = Vec::new();
+        for instruction in instructions {
+            match instruction.opcode() {
+                Opcode::Load(name) if !variables.contains(&name) => variables.push(name),
+                Opcode::Noise(..) => {
+                    return Err(Unsupported {
+                        instruction: instruction.to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        // Parameters come first in the local index space, followed by one
+        // f32 local per register.
+        let register = |ret: usize| variables.len() + ret;
+        let mut body = Vec::new();
+        for instruction in instructions {
+            match instruction.opcode() {
+                Opcode::Constant(value) => {
+                    body.push(0x43);
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+                Opcode::Load(name) => {
+                    body.push(0x20);
+                    leb128(
+                        &mut body,
+                        variables.iter().position(|v| *v == name).unwrap(),
+                    );
+                }
+                Opcode::Noise(..) => unreachable!(),
+                Opcode::Add(a, b) => binary(&mut body, 0x92, register(a), register(b)),
+                Opcode::Sub(a, b) => binary(&mut body, 0x93, register(a), register(b)),
+                Opcode::Mul(a, b) => binary(&mut body, 0x94, register(a), register(b)),
+                Opcode::Div(a, b) => binary(&mut body, 0x95, register(a), register(b)),
+                Opcode::Select(c, a, b) => {
+                    for local in [a, b, c] {
+                        body.push(0x20);
+                        leb128(&mut body, register(local));
+                    }
+                    // f32.ne against zero gives the i32 condition select takes.
+                    body.push(0x43);
+                    body.extend_from_slice(&0f32.to_le_bytes());
+                    body.extend_from_slice(&[0x5c, 0x1b]);
+                }
+                Opcode::Compare(comparison, a, b) => {
+                    let opcode = match comparison {
+                        Comparison::Eq => 0x5b,
+                        Comparison::Ne => 0x5c,
+                        Comparison::Lt => 0x5d,
+                        Comparison::Gt => 0x5e,
+                        Comparison::Le => 0x5f,
+                        Comparison::Ge => 0x60,
+                    };
+                    binary(&mut body, opcode, register(a), register(b));
+                    // f32.convert_i32_u turns the i32 result into 0.0 or 1.0.
+                    body.push(0xb3);
+                }
+                Opcode::Store(a) | Opcode::Reload(a) => {
+                    body.push(0x20);
+                    leb128(&mut body, register(a));
+                }
+            }
+            body.push(0x21);
+            leb128(&mut body, register(instruction.ret()));
+        }
+        body.push(0x20);
+        leb128(&mut body, register(result));
+        body.push(0x0b);
+
+        let slot_count = instructions.iter().map(|i| i.ret() + 1).max().unwrap_or(0);
+        let mut function = Vec::new();
+        leb128(&mut function, 1);
+        leb128(&mut function, slot_count);
+        function.push(F32);
+        function.extend(body);
+
+        let mut types = vec![0x01, 0x60];
+        leb128(&mut types, variables.len());
+        types.extend(std::iter::repeat_n(F32, variables.len()));
+        types.extend([0x01, F32]);
+
+        let mut code = vec![0x01];
+        leb128(&mut code, function.len());
+        code.extend(function);
+
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        section(&mut bytes, 1, &types);
+        section(&mut bytes, 3, &[0x01, 0x00]);
+        section(&mut bytes, 7, b"\x01\x04eval\x00\x00");
+        section(&mut bytes, 10, &code);
+        Ok(Self { bytes, variables })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+}
+
+fn binary(body: &mut Vec<u8>, opcode: u8, a: usize, b: usize) {
+    for local in [a, b] {
+        body.push(0x20);
+        leb128(body, local);
+    }
+    body.push(opcode);
+}
+
+fn section(bytes: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    bytes.push(id);
+    leb128(bytes, contents.len());
+    bytes.extend_from_slice(contents);
+}
+
+fn leb128(bytes: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
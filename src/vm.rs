@@ -0,0 +1,80 @@
+use crate::operation::{Environment, EvalContext, Instruction, Program};
+
+// Runs instruction lists over a register file it keeps between runs, along
+// with the noise streams, so noise carries on from one run to the next. A
+// run with a different number of noise sources starts them over.
+#[derive(Default)]
+pub struct Vm {
+    context: EvalContext,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn run(&mut self, instructions: &[Instruction]) -> f32 {
+        self.run_with(instructions, &Environment::new())
+    }
+
+    pub fn run_with(&mut self, instructions: &[Instruction], env: &Environment) -> f32 {
+        let result = instructions
+            .last()
+            .expect("cannot run an empty program")
+            .ret();
+        let slot_count = instructions.iter().map(|i| i.ret() + 1).max().unwrap_or(0);
+        self.prepare(instructions);
+        execute(&mut self.context, instructions, slot_count, env);
+        self.context.values[result]
+    }
+
+    pub fn run_program(&mut self, program: &Program, env: &Environment) -> Vec<f32> {
+        self.prepare(program.instructions());
+        program.run_in(&mut self.context, env)
+    }
+
+    fn prepare(&mut self, instructions: &[Instruction]) {
+        let seeds: Vec<Option<u64>> = instructions
+            .iter()
+            .filter_map(|i| i.noise().map(|(_, seed)| seed))
+            .collect();
+        if seeds.len() != self.context.noise_sources() {
+            self.context = EvalContext::new(seeds);
+        }
+    }
+}
+
+// Noise instructions draw from the context's streams in order, so the
+// context has to come from the same instructions.
+pub(crate) fn execute(
+    context: &mut EvalContext,
+    instructions: &[Instruction],
+    slot_count: usize,
+    env: &Environment,
+) {
+    execute_observed(context, instructions, slot_count, env, |_, _| {})
+}
+
+// As execute, handing `observe` each instruction's index and result.
+pub(crate) fn execute_observed(
+    context: &mut EvalContext,
+    instructions: &[Instruction],
+    slot_count: usize,
+    env: &Environment,
+    mut observe: impl FnMut(usize, f32),
+) {
+    let EvalContext { values, noise } = context;
+    values.clear();
+    values.resize(slot_count, 0.);
+    let mut streams = noise.iter_mut();
+    for (i, instruction) in instructions.iter().enumerate() {
+        let value = match instruction.noise() {
+            Some((distribution, _)) => {
+                distribution.sample(streams.next().expect("context is for another program"))
+            }
+            None => instruction.execute(values, env),
+        };
+        values[instruction.ret()] = value;
+        observe(i, value);
+    }
+}
@@ -0,0 +1,38 @@
+use rust_lazy::operation::{Environment, OpKind, Program, Scalar};
+
+#[test]
+fn retargeting_rewires_every_parent() {
+    let x = Scalar::variable("x");
+    let slot = Scalar::alias(&x + &Scalar::new(1.));
+    let doubled = &slot * &Scalar::new(2.);
+    let squared = &slot * &slot;
+    let env: Environment = [("x", 3.)].into_iter().collect();
+
+    assert_eq!(doubled.execute_with(&env), 8.);
+    assert_eq!(squared.execute_with(&env), 16.);
+
+    slot.set_target(&x - &Scalar::new(1.));
+    assert_eq!(doubled.execute_with(&env), 4.);
+    assert_eq!(squared.execute_with(&env), 4.);
+    assert_eq!(slot.target().kind(), OpKind::Sub);
+}
+
+#[test]
+fn aliases_compile_to_nothing() {
+    let x = Scalar::variable("x");
+    let slot = Scalar::alias(&x * &x);
+    let res = &slot + &slot;
+    assert_eq!(res.clone().compile().len(), 3);
+
+    let program = Program::compile(&[res.into_dyn(), slot.into_dyn()]);
+    let env: Environment = [("x", 3.)].into_iter().collect();
+    assert_eq!(program.run_with(&env), vec![18., 9.]);
+}
+
+#[test]
+#[should_panic(expected = "own graph")]
+fn aliases_cannot_form_cycles() {
+    let slot = Scalar::alias(Scalar::new(1.));
+    let parent = &slot + &Scalar::new(2.);
+    slot.set_target(parent);
+}
@@ -0,0 +1,68 @@
+use rust_lazy::operation::{parse, Environment, Program, Scalar};
+
+#[test]
+fn matches_running_each_row() {
+    let program = Program::compile(&[parse("x * y + 1").unwrap(), parse("y - x").unwrap()]);
+    assert_eq!(program.variables(), ["x", "y"]);
+    let rows: Vec<[f32; 2]> = (0..10).map(|i| [i as f32, 0.5 - i as f32]).collect();
+    let rows: Vec<&[f32]> = rows.iter().map(|row| &row[..]).collect();
+
+    let expected: Vec<f32> = rows
+        .iter()
+        .flat_map(|row| {
+            let env: Environment = [("x", row[0]), ("y", row[1])].into_iter().collect();
+            program.run_with(&env)
+        })
+        .collect();
+    assert_eq!(program.run_batch(&rows), expected);
+    assert!(program.run_batch(&[]).is_empty());
+}
+
+#[test]
+fn graphs_run_batches() {
+    let x = Scalar::variable("x");
+    let square = &x * &x;
+    assert_eq!(square.execute_batch(&[&[1.], &[2.], &[-3.]]), [1., 4., 9.]);
+}
+
+#[test]
+fn noise_carries_on_between_rows() {
+    let program = Program::compile(&[Scalar::gaussian_noise(1., Some(3)).into_dyn()]);
+    let mut context = program.context();
+    let expected: Vec<f32> = (0..4)
+        .flat_map(|_| program.run_in(&mut context, &Environment::new()))
+        .collect();
+    assert_eq!(program.run_batch(&[&[], &[], &[], &[]]), expected);
+}
+
+#[test]
+fn seeded_rows_do_not_depend_on_each_other() {
+    let expr = &Scalar::variable("x") + &Scalar::laplace_noise(1., Some(3));
+    let program = Program::compile(&[expr.into_dyn()]);
+    let rows: [&[f32]; 4] = [&[1.], &[2.], &[3.], &[4.]];
+    let all = program.run_batch_seeded(&rows, 17);
+    let mut changed = rows;
+    changed[0] = &[100.];
+    assert_eq!(program.run_batch_seeded(&changed, 17)[1..], all[1..]);
+    assert_eq!(program.run_batch_seeded(&rows, 17), all);
+    assert_ne!(program.run_batch_seeded(&rows, 18), all);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_batches_match_serial_ones() {
+    let expr = &Scalar::variable("x") * &Scalar::gaussian_noise(1., None);
+    let program = Program::compile(&[expr.into_dyn(), parse("x / 2").unwrap()]);
+    let rows: Vec<[f32; 1]> = (0..10_000).map(|i| [i as f32]).collect();
+    let rows: Vec<&[f32]> = rows.iter().map(|row| &row[..]).collect();
+    assert_eq!(
+        program.par_run_batch_seeded(&rows, 5),
+        program.run_batch_seeded(&rows, 5)
+    );
+}
+
+#[test]
+#[should_panic(expected = "expected 2 columns")]
+fn rows_need_every_column() {
+    Program::compile(&[parse("x + y").unwrap()]).run_batch(&[&[1.]]);
+}
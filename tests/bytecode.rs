@@ -0,0 +1,91 @@
+use rust_lazy::operation::{
+    parse, BytecodeError, CompileOptions, Environment, Instruction, Opcode, Program, Scalar,
+    Sequential,
+};
+
+#[test]
+fn round_trips_programs() {
+    let x = Scalar::variable("x");
+    let noisy = &(&x * &Scalar::new(0.1)) + &Scalar::laplace_noise(2., Some(u64::MAX));
+    let program = Program::compile(&[
+        parse("(x + 2.5) * y / (x - 0.1)").unwrap(),
+        noisy.into_dyn(),
+        Scalar::gaussian_noise(0.5, None).into_dyn(),
+    ]);
+
+    let bytes = program.to_bytes();
+    assert_eq!(&bytes[..5], b"LZBC\x01");
+    let loaded = Program::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.to_string(), program.to_string());
+    assert_eq!(loaded.to_bytes(), bytes);
+
+    let env: Environment = [("x", 3.), ("y", -1.)].into_iter().collect();
+    assert_eq!(loaded.run_with(&env)[..2], program.run_with(&env)[..2]);
+}
+
+#[test]
+fn constants_are_pooled() {
+    let x = Scalar::variable("x");
+    let one = Program::compile(&[(&x * &Scalar::new(1.5)).into_dyn()]);
+    let two = Program::compile(&[(&(&x * &Scalar::new(1.5)) + &Scalar::new(1.5)).into_dyn()]);
+    // One pooled constant, plus an add with its two operands and result.
+    assert_eq!(two.to_bytes().len(), one.to_bytes().len() + 4);
+}
+
+#[test]
+fn rejects_malformed_input() {
+    let bytes = Program::compile(&[parse("a * b").unwrap()]).to_bytes();
+
+    assert_eq!(
+        Program::from_bytes(b"ELF\x7f\x01").err(),
+        Some(BytecodeError::BadMagic)
+    );
+    let mut newer = bytes.clone();
+    newer[4] = u8::MAX;
+    assert_eq!(
+        Program::from_bytes(&newer).err(),
+        Some(BytecodeError::UnsupportedVersion(u8::MAX))
+    );
+    for len in 0..bytes.len() {
+        assert!(Program::from_bytes(&bytes[..len]).is_err());
+    }
+
+    // A multiply that reads register 7, which is never written.
+    let mut dangling = bytes.clone();
+    let mul = dangling.iter().rposition(|&b| b == 6).unwrap();
+    dangling[mul + 1] = 7;
+    assert_eq!(
+        Program::from_bytes(&dangling).err(),
+        Some(BytecodeError::InvalidRegister)
+    );
+}
+
+#[test]
+fn round_trips_sparse_registers() {
+    // Folding leaves the constant in a register numbered past the
+    // instruction count.
+    let options = CompileOptions::new().with_strategy(Sequential);
+    let program = Program::compile_with(&[parse("(1 + 2) * x").unwrap()], &options)
+        .unwrap()
+        .fold_constants();
+    assert!(program
+        .instructions()
+        .iter()
+        .any(|i| i.ret() >= program.instructions().len()));
+    let loaded = Program::from_bytes(&program.to_bytes()).unwrap();
+    assert_eq!(loaded.to_string(), program.to_string());
+    let env: Environment = [("x", 4.)].into_iter().collect();
+    assert_eq!(loaded.run_with(&env), [12.]);
+
+    let program = Program::new(
+        vec![
+            Instruction::from_opcode(Opcode::Load("x".to_string()), 40),
+            Instruction::from_opcode(Opcode::Add(40, 40), 1000),
+        ],
+        vec![1000],
+    )
+    .unwrap();
+    let loaded = Program::from_bytes(&program.to_bytes()).unwrap();
+    assert_eq!(loaded.to_string(), program.to_string());
+    assert_eq!(loaded.run_with(&env), [8.]);
+}
@@ -0,0 +1,39 @@
+use rust_lazy::operation::{parse, Program, Scalar};
+
+#[test]
+fn renders_a_c_function() {
+    let program = Program::compile(&[parse("(x + 2) * y - x").unwrap()]);
+    assert_eq!(
+        program.to_c().unwrap(),
+        "float eval(float x, float y) {
+    float t0, t1, t2;
+    t0 = x;
+    t1 = 2.0f;
+    t1 = t0 + t1;
+    t2 = y;
+    t1 = t1 * t2;
+    t0 = t1 - t0;
+    return t0;
+}
+"
+    );
+}
+
+#[test]
+fn several_outputs_are_written_to_an_array() {
+    let x = Scalar::variable("x");
+    let program = Program::compile(&[
+        (&x * &x).into_dyn(),
+        (&x / &Scalar::new(f32::NEG_INFINITY)).into_dyn(),
+    ]);
+    let source = program.to_c().unwrap();
+    assert!(source.starts_with("#include <math.h>\n\nvoid eval(float x, float out[2]) {"));
+    assert!(source.contains("= -INFINITY;"));
+    assert!(source.contains("out[1] = t"));
+}
+
+#[test]
+fn constant_programs_take_void() {
+    let program = Program::compile(&[Scalar::new(1.5).into_dyn()]);
+    assert!(program.to_c().unwrap().starts_with("float eval(void) {"));
+}
@@ -0,0 +1,95 @@
+use std::{fs, path::PathBuf};
+
+use rust_lazy::operation::{parse, ArtifactCache, Program};
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust_lazy-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn program(source: &str) -> Program {
+    Program::compile(&[parse(source).unwrap()])
+}
+
+#[test]
+fn stores_and_loads_by_program_and_target() {
+    let cache = ArtifactCache::new(scratch("store"));
+    let program = program("x * x + 1");
+    assert_eq!(cache.load(&program, "test", "ir"), None);
+    cache.store(&program, "test", "ir", b"artifact").unwrap();
+    assert_eq!(cache.load(&program, "test", "ir").unwrap(), b"artifact");
+    assert_eq!(cache.load(&program, "other", "ir"), None);
+    assert_eq!(cache.load(&self::program("x * x + 2"), "test", "ir"), None);
+    fs::remove_dir_all(cache.dir()).unwrap();
+}
+
+#[test]
+fn corrupt_entries_are_misses() {
+    let cache = ArtifactCache::new(scratch("corrupt"));
+    let program = program("x / 3");
+    cache.store(&program, "test", "ir", b"artifact").unwrap();
+    let path = cache.path(&program, "test", "ir");
+    let mut bytes = fs::read(&path).unwrap();
+    *bytes.last_mut().unwrap() ^= 1;
+    fs::write(&path, &bytes).unwrap();
+    assert_eq!(cache.load(&program, "test", "ir"), None);
+    fs::write(&path, b"short").unwrap();
+    assert_eq!(cache.load(&program, "test", "ir"), None);
+    fs::remove_dir_all(cache.dir()).unwrap();
+}
+
+#[test]
+fn entries_sharing_a_fingerprint_are_told_apart() {
+    let cache = ArtifactCache::new(scratch("collision"));
+    let (first, second) = (program("x + 1"), program("x - 1"));
+    cache.store(&first, "test", "ir", b"first").unwrap();
+    // Put the first entry where the second program looks, as if their
+    // fingerprints were the same.
+    fs::copy(
+        cache.path(&first, "test", "ir"),
+        cache.path(&second, "test", "ir"),
+    )
+    .unwrap();
+    assert_eq!(cache.load(&second, "test", "ir"), None);
+    assert_eq!(cache.load(&first, "test", "ir").unwrap(), b"first");
+    fs::remove_dir_all(cache.dir()).unwrap();
+}
+
+#[cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+#[test]
+fn jit_reuses_cached_code() {
+    use rust_lazy::operation::Jit;
+
+    let cache = ArtifactCache::new(scratch("jit"));
+    let (double, triple) = (program("x * 2"), program("x * 3"));
+    assert_eq!(
+        Jit::compile_cached(&double, &cache).unwrap().call(&[5.]),
+        10.
+    );
+    let written: Vec<PathBuf> = fs::read_dir(cache.dir())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(written.len(), 1);
+    assert_eq!(
+        Jit::compile_cached(&double, &cache).unwrap().call(&[5.]),
+        10.
+    );
+
+    // An entry planted under another program's fingerprint, as a collision
+    // would leave it, is a miss rather than the wrong code.
+    fs::copy(
+        &written[0],
+        written[0].to_str().unwrap().replace(
+            &format!("{:016x}", double.fingerprint()),
+            &format!("{:016x}", triple.fingerprint()),
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        Jit::compile_cached(&triple, &cache).unwrap().call(&[5.]),
+        15.
+    );
+    fs::remove_dir_all(cache.dir()).unwrap();
+}
@@ -0,0 +1,54 @@
+use rust_lazy::operation::{calibrate, parse, Environment, Program, QuantType, HISTOGRAM_BINS};
+
+fn samples(xs: impl IntoIterator<Item = f32>) -> Vec<Environment> {
+    xs.into_iter()
+        .map(|x| [("x", x)].into_iter().collect())
+        .collect()
+}
+
+#[test]
+fn records_the_range_of_every_instruction() {
+    let program = Program::compile(&[parse("x * 2 + 1").unwrap()]);
+    let calibration = calibrate(&program, &samples((0..=20).map(|i| i as f32 / 10. - 1.)));
+    assert_eq!(calibration.ranges().len(), program.instructions().len());
+    assert_eq!(calibration.ranges().last(), Some(&(-1., 3.)));
+    assert!(calibration.ranges().contains(&(-1., 1.)));
+    assert!(calibration.ranges().contains(&(2., 2.)));
+    for histogram in calibration.histograms() {
+        assert_eq!(histogram.counts().len(), HISTOGRAM_BINS);
+        assert_eq!(histogram.total(), 21);
+    }
+}
+
+#[test]
+fn clipping_drops_outliers() {
+    let program = Program::compile(&[parse("x").unwrap()]);
+    let xs = (0..1000).map(|i| i as f32 / 1000.).chain([1000.]);
+    let calibration = calibrate(&program, &samples(xs));
+    assert_eq!(calibration.ranges()[0], (0., 1000.));
+    let (min, max) = calibration.clipped(0.99).ranges()[0];
+    assert_eq!(min, 0.);
+    assert!((1. ..2.).contains(&max), "{}", max);
+}
+
+#[test]
+fn feeds_the_quantizer() {
+    let program = Program::compile(&[parse("x * x - x / 2").unwrap()]);
+    let inputs = samples((0..=40).map(|i| i as f32 / 10. - 2.));
+    let calibration = calibrate(&program, &inputs);
+    let params = calibration.params(QuantType::I16);
+    assert_eq!(params.len(), program.instructions().len());
+
+    let quantized = program.quantize(&calibration, QuantType::I16).unwrap();
+    for env in &inputs {
+        let (q, f) = (quantized.run_with(env)[0], program.run_with(env)[0]);
+        assert!((q - f).abs() < 1e-3, "{} vs {}", q, f);
+    }
+}
+
+#[test]
+fn skips_values_that_are_not_finite() {
+    let program = Program::compile(&[parse("1 / x").unwrap()]);
+    let calibration = calibrate(&program, &samples([0., 0.5, 4.]));
+    assert_eq!(calibration.ranges().last(), Some(&(0.25, 2.)));
+}
@@ -0,0 +1,99 @@
+use rust_lazy::operation::{parse, CanonicalId, Scalar};
+
+#[test]
+fn ids_depend_only_on_content() {
+    let a = parse("(x + 2) * y").unwrap();
+    let b = parse("(x + 2) * y").unwrap();
+    assert_eq!(a.canonical_id(), b.canonical_id());
+    assert_eq!(a.canonical_ids(), b.canonical_ids());
+    assert_ne!(
+        a.canonical_id(),
+        parse("(x + 2) * z").unwrap().canonical_id()
+    );
+    assert_ne!(
+        parse("x + 2").unwrap().canonical_id(),
+        parse("2 + x").unwrap().canonical_id()
+    );
+    assert_eq!(a.canonical_id().disambiguator, 0);
+}
+
+#[test]
+fn subexpressions_hash_alike_in_any_graph() {
+    let sum = parse("x + 2").unwrap();
+    let graph = parse("(x + 2) * y").unwrap();
+    let (id, node) = &graph.canonical_ids()[2];
+    assert_eq!(node, &sum);
+    assert_eq!(*id, sum.canonical_id());
+    assert_eq!(graph.find_canonical(*id).unwrap(), sum);
+}
+
+#[test]
+fn repeats_are_disambiguated() {
+    let first = Scalar::gaussian_noise(1., None);
+    let second = Scalar::gaussian_noise(1., None);
+    let graph = (&first - &second).into_dyn();
+    let ids = graph.canonical_ids();
+    assert_eq!(ids[0].0.hash, ids[1].0.hash);
+    assert_eq!((ids[0].0.disambiguator, ids[1].0.disambiguator), (0, 1));
+    // One source used twice has a single node where two sources have two.
+    assert_eq!(graph.canonical_id(), (&first - &first).canonical_id());
+    assert_eq!((&first - &first).canonical_ids().len(), ids.len() - 1);
+}
+
+#[test]
+fn unshared_repeats_share_a_hash() {
+    let graph = parse("(x + 2) * (x + 2)").unwrap();
+    let sum = parse("x + 2").unwrap();
+    let repeats: Vec<CanonicalId> = graph
+        .canonical_ids()
+        .into_iter()
+        .filter(|(_, node)| *node == sum)
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(repeats.len(), 2);
+    assert_eq!(repeats[0].hash, sum.canonical_id().hash);
+    assert_eq!(repeats[1].hash, sum.canonical_id().hash);
+    assert_eq!((repeats[0].disambiguator, repeats[1].disambiguator), (0, 1));
+}
+
+#[test]
+fn ids_round_trip_through_strings() {
+    let id = parse("x * x").unwrap().canonical_id();
+    let text = id.to_string();
+    assert_eq!(text.len(), 18);
+    assert_eq!(text.parse::<CanonicalId>().unwrap(), id);
+    assert!("12:0".parse::<CanonicalId>().is_err());
+    assert!("not an id".parse::<CanonicalId>().is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ids_survive_serde() {
+    use rust_lazy::operation::DynScalar;
+
+    let x = Scalar::variable("x");
+    let noise = Scalar::gaussian_noise(0.5, None);
+    let shared = &(&x * &noise) + &Scalar::new(0.1);
+    let graph = (&(&shared / &shared) - &Scalar::gaussian_noise(0.5, None)).into_dyn();
+    let restored: DynScalar =
+        serde_json::from_str(&serde_json::to_string(&graph).unwrap()).unwrap();
+    let ids: Vec<CanonicalId> = graph
+        .canonical_ids()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    let restored_ids: Vec<CanonicalId> = restored
+        .canonical_ids()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(ids, restored_ids);
+
+    let id = shared.canonical_id();
+    assert_eq!(
+        restored.find_canonical(id).unwrap().to_string(),
+        shared.to_string()
+    );
+    let json = serde_json::to_string(&id).unwrap();
+    assert_eq!(serde_json::from_str::<CanonicalId>(&json).unwrap(), id);
+}
@@ -0,0 +1,129 @@
+use rust_lazy::operation::{parse, Comparison, DynScalar, Environment, Program, Scalar};
+
+const COMPARISONS: [Comparison; 6] = [
+    Comparison::Eq,
+    Comparison::Ne,
+    Comparison::Lt,
+    Comparison::Le,
+    Comparison::Gt,
+    Comparison::Ge,
+];
+
+fn env(a: f32, b: f32) -> Environment {
+    [("a", a), ("b", b)].into_iter().collect()
+}
+
+fn compared(comparison: Comparison) -> DynScalar {
+    Scalar::variable("a")
+        .compare(comparison, &Scalar::variable("b"))
+        .into()
+}
+
+#[test]
+fn yields_one_or_zero() {
+    let (a, b) = (Scalar::variable("a"), Scalar::variable("b"));
+    let cases = [
+        (1., 2., [0., 1., 1., 1., 0., 0.]),
+        (2., 2., [1., 0., 0., 1., 0., 1.]),
+        (3., 2., [0., 1., 0., 0., 1., 1.]),
+        (f32::NAN, 2., [0., 1., 0., 0., 0., 0.]),
+    ];
+    for (x, y, expected) in cases {
+        let env = env(x, y);
+        let results = [
+            a.eq(&b).execute_with(&env),
+            a.ne(&b).execute_with(&env),
+            a.lt(&b).execute_with(&env),
+            a.le(&b).execute_with(&env),
+            a.gt(&b).execute_with(&env),
+            a.ge(&b).execute_with(&env),
+        ];
+        assert_eq!(results, expected, "{} vs {}", x, y);
+    }
+    assert_eq!(a.lt(&b).to_string(), "(a < b)");
+}
+
+#[test]
+fn masks_and_thresholds() {
+    let x = Scalar::variable("x");
+    let zero = Scalar::new(0.);
+    let abs = x.gt(&zero).select(&x, &(&zero - &x));
+    let relu = &x * &x.ge(&zero);
+    for (value, magnitude, clipped) in [(3., 3., 3.), (-2., 2., -0.), (0., 0., 0.)] {
+        let env: Environment = [("x", value)].into_iter().collect();
+        assert_eq!(abs.execute_with(&env), magnitude);
+        assert_eq!(relu.execute_with(&env), clipped);
+    }
+    assert_eq!(
+        x.gt(&zero)
+            .execute_dual(&[("x", 1.)].into_iter().collect(), "x")
+            .derivative,
+        0.
+    );
+}
+
+#[test]
+fn compiled_forms_agree() {
+    for comparison in COMPARISONS {
+        let expr = compared(comparison);
+        let program = Program::compile(std::slice::from_ref(&expr));
+        let reloaded = Program::from_bytes(&program.to_bytes()).unwrap();
+        let closure = program.to_closure();
+        let frozen = expr.freeze();
+        let mut memo = expr.memoize();
+        for (a, b) in [(1., 2.), (2., 2.), (3., 2.), (f32::NAN, 2.), (-0., 0.)] {
+            let env = env(a, b);
+            let expected = expr.execute_with(&env);
+            assert_eq!(program.run_with(&env), [expected]);
+            assert_eq!(reloaded.run_with(&env), [expected]);
+            assert_eq!(closure(&[a, b]), expected);
+            assert_eq!(frozen.call(&[a, b]), expected);
+            assert_eq!(memo.execute_with(&env), expected);
+        }
+    }
+}
+
+#[test]
+fn parses_comparison_operators() {
+    for (source, comparison) in [
+        ("a == b", Comparison::Eq),
+        ("a != b", Comparison::Ne),
+        ("a < b", Comparison::Lt),
+        ("a <= b", Comparison::Le),
+        ("a > b", Comparison::Gt),
+        ("a >= b", Comparison::Ge),
+    ] {
+        let parsed = parse(source).unwrap();
+        assert_eq!(parsed.to_string(), compared(comparison).to_string());
+    }
+    let threshold = parse("(x * 2 > 1) * x").unwrap();
+    assert_eq!(threshold.to_string(), "(((x * 2) > 1) * x)");
+    assert_eq!(
+        parse(&threshold.to_string()).unwrap().to_string(),
+        threshold.to_string()
+    );
+    assert!(parse("a = b").is_err());
+    assert!(parse("a < b < c").is_err());
+}
+
+#[test]
+fn renders_in_every_backend() {
+    let program = Program::compile(&[compared(Comparison::Le)]);
+    assert!(program.to_c().unwrap().contains("(float)(t0 <= t1)"));
+    assert!(program
+        .to_rust()
+        .unwrap()
+        .contains("f32::from(u8::from(t0 <= t1))"));
+    assert!(program
+        .to_wgsl()
+        .unwrap()
+        .contains("select(0.0, 1.0, t0 <= t1)"));
+    assert!(program.to_glsl().unwrap().contains("float(t0 <= t1)"));
+    let ir = program.to_llvm_ir().unwrap();
+    assert!(ir.contains("fcmp ole float %a, %b"), "{}", ir);
+    assert!(ir.contains("uitofp i1"), "{}", ir);
+    assert_eq!(
+        program.to_string(),
+        "%0: load a\n%1: load b\n%0: le %0 %1\nret %0"
+    );
+}
@@ -0,0 +1,91 @@
+use rust_lazy::operation::{CScalar, Complex, ComplexOp, Environment, Program, Scalar};
+
+fn close(a: Complex, b: Complex) -> bool {
+    (a.re - b.re).abs() <= 1e-5 * b.re.abs().max(1.)
+        && (a.im - b.im).abs() <= 1e-5 * b.im.abs().max(1.)
+}
+
+#[test]
+fn displays_complex_values() {
+    assert_eq!(Complex::new(3., 2.).to_string(), "3+2i");
+    assert_eq!(Complex::new(3., -2.).to_string(), "3-2i");
+    assert_eq!(Complex::i().to_string(), "0+1i");
+    let z = CScalar::variable("z");
+    let expr = &(&z * &CScalar::new(Complex::new(3., 2.))) + &CScalar::new(1.);
+    assert_eq!(expr.to_string(), "((z * (3+2i)) + 1)");
+    assert_eq!(z.conj().to_string(), "conj(z)");
+}
+
+#[test]
+fn arithmetic() {
+    let a = CScalar::new(Complex::new(3., 2.));
+    let b = CScalar::new(Complex::new(1., -1.));
+    assert_eq!((&a + &b).execute(), Complex::new(4., 1.));
+    assert_eq!((&a - &b).execute(), Complex::new(2., 3.));
+    assert_eq!((&a * &b).execute(), Complex::new(5., -1.));
+    assert_eq!((&a / &b).execute(), Complex::new(0.5, 2.5));
+    assert_eq!(a.conj().execute(), Complex::new(3., -2.));
+    assert_eq!((&a * &a.conj()).execute(), Complex::real(13.));
+}
+
+#[test]
+fn variables_bind_both_parts() {
+    let z = CScalar::variable("z");
+    let w = CScalar::variable("w");
+    let mut env = Environment::new();
+    env.set_complex("z", Complex::new(1., 2.))
+        .set_complex("w", Complex::new(0., 1.));
+    assert_eq!(env.get("z.im"), Some(2.));
+    assert_eq!((&z * &w).execute_with(&env), Complex::new(-2., 1.));
+}
+
+#[test]
+fn lifts_real_expressions() {
+    let x = Scalar::variable("x");
+    let phase = CScalar::from_parts(&x * &Scalar::new(2.), Scalar::new(1.));
+    let expr = &phase * &CScalar::real(x.clone());
+    let env: Environment = [("x", 3.)].into_iter().collect();
+    assert_eq!(expr.execute_with(&env), Complex::new(18., 3.));
+
+    let program = expr.compile();
+    assert_eq!(program.real().unwrap().outputs().len(), 4);
+    assert!(matches!(program.instructions()[0], ComplexOp::Lift(0, 1)));
+    assert_eq!(
+        program.to_string(),
+        "%0: lift $0 $1\n%1: lift $2 $3\n%2: cmul %0 %1\nret %2"
+    );
+}
+
+#[test]
+fn shares_common_subexpressions() {
+    let z = CScalar::variable("z");
+    let square = &z * &z;
+    let expr = &square + &square;
+    let program = expr.compile();
+    assert_eq!(
+        program.to_string(),
+        "%0: cload z\n%1: cmul %0 %0\n%2: cadd %1 %1\nret %2"
+    );
+}
+
+#[test]
+fn lowered_parts_match_complex_evaluation() {
+    let z = CScalar::variable("z");
+    let w = CScalar::variable("w");
+    let c = CScalar::new(Complex::new(0.5, -1.5));
+    let expr = &(&(&z * &w) - &c) / &(&z.conj() + &w);
+    for (zv, wv) in [
+        (Complex::new(1., 2.), Complex::new(-0.5, 3.)),
+        (Complex::new(-4., 0.25), Complex::new(2., -1.)),
+    ] {
+        let mut env = Environment::new();
+        env.set_complex("z", zv).set_complex("w", wv);
+        let (re, im) = expr.parts();
+        let program = Program::compile(&[re, im]);
+        let lowered = program.run_with(&env);
+        let direct = expr.execute_with(&env);
+        assert!(close(Complex::new(lowered[0], lowered[1]), direct));
+        let expected = (zv * wv - Complex::new(0.5, -1.5)) / (zv.conj() + wv);
+        assert!(close(direct, expected));
+    }
+}
@@ -0,0 +1,36 @@
+use rust_lazy::{
+    conformance::{ulp_distance, ulp_report},
+    operation::{parse, Environment, Program},
+};
+
+fn program(source: &str) -> Program {
+    Program::compile(&[parse(source).unwrap()])
+}
+
+#[test]
+fn ulp_distance_orders_floats() {
+    assert_eq!(ulp_distance(1., 1.), 0);
+    assert_eq!(ulp_distance(0., -0.), 0);
+    assert_eq!(ulp_distance(1., f32::from_bits(1f32.to_bits() + 3)), 3);
+    assert_eq!(ulp_distance(-f32::from_bits(1), f32::from_bits(1)), 2);
+    assert_eq!(ulp_distance(f32::NAN, f32::NAN), 0);
+    assert_eq!(ulp_distance(f32::NAN, 1.), u64::MAX);
+}
+
+#[test]
+fn reports_reassociation_error() {
+    let inputs: Vec<Environment> = [(1e8, -1e8, 1.), (1., 2., 3.), (0.1, 0.2, 0.3)]
+        .into_iter()
+        .map(|(a, b, c)| [("a", a), ("b", b), ("c", c)].into_iter().collect())
+        .collect();
+
+    let same = ulp_report(&program("a + b + c"), &program("a + b + c"), &inputs);
+    assert_eq!(same.max, 0);
+    assert_eq!(same.worst_inputs.len(), 3);
+
+    let report = ulp_report(&program("(a + b) + c"), &program("a + (b + c)"), &inputs);
+    assert!(report.max > 0);
+    assert_eq!(report.worst_inputs.len(), 1);
+    assert_eq!(report.worst_inputs[0].get("a"), Some(1e8));
+    assert!(report.mean > 0.);
+}
@@ -0,0 +1,29 @@
+use rust_lazy::operation::{Program, Scalar};
+
+#[test]
+fn set_reaches_every_parent() {
+    let k = Scalar::new(2.);
+    let x = Scalar::variable("x");
+    let a = &x * &k;
+    let res = &a + &k;
+    let env = [("x", 3.)].into_iter().collect();
+    assert_eq!(res.execute_with(&env), 8.);
+
+    k.set(5.);
+    assert_eq!(k.value(), 5.);
+    assert_eq!(res.execute_with(&env), 20.);
+}
+
+#[test]
+fn recompiles_with_the_new_value() {
+    let k = Scalar::new(2.);
+    let res = &Scalar::variable("x") * &k;
+    let env = [("x", 3.)].into_iter().collect();
+    let before = Program::compile(&[res.clone().into()]);
+
+    k.set(4.);
+    let after = Program::compile(&[res.clone().into()]);
+    assert_eq!(before.run_with(&env), [6.]);
+    assert_eq!(after.run_with(&env), [12.]);
+    assert_eq!(res.clone().compile().len(), 3);
+}
@@ -0,0 +1,43 @@
+use rust_lazy::operation::{Environment, GraphContext, GraphLimits, LimitError};
+
+fn distinct_nodes(dot: &str) -> usize {
+    dot.matches("[label=").count()
+}
+
+#[test]
+fn variables_and_constants_are_interned() {
+    let ctx = GraphContext::new();
+    let x = ctx.var("x");
+    let two = ctx.constant(2.);
+    let doubled = ctx.mul(&x, &two).unwrap();
+    let res = ctx.add(&doubled, &ctx.var("x")).unwrap();
+    let res = ctx.add(&res, &ctx.constant(2.)).unwrap();
+
+    // x, 2, *, + and +: the second x and 2 are the same nodes.
+    assert_eq!(distinct_nodes(&res.to_dot()), 5);
+    assert!(ctx.variable("x").is_some());
+    assert!(ctx.variable("y").is_none());
+
+    let env: Environment = [("x", 3.)].into_iter().collect();
+    assert_eq!(res.execute_with(&env), 11.);
+}
+
+#[test]
+fn negative_zero_is_its_own_constant() {
+    let ctx = GraphContext::new();
+    let zero = ctx.constant(0.);
+    let res = ctx.add(&zero, &ctx.constant(-0.)).unwrap();
+    assert_eq!(distinct_nodes(&res.to_dot()), 3);
+}
+
+#[test]
+fn limits_apply_to_every_expression() {
+    let ctx = GraphContext::new().with_limits(GraphLimits::new().with_max_depth(3));
+    let x = ctx.var("x");
+    let one = ctx.constant(1.);
+    let shallow = ctx.add(&x, &one).unwrap();
+    assert_eq!(
+        ctx.mul(&ctx.add(&shallow, &one).unwrap(), &x).err(),
+        Some(LimitError::TooDeep { limit: 3 })
+    );
+}
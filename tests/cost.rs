@@ -0,0 +1,23 @@
+use rust_lazy::operation::{parse, CostModel, Program, Scalar};
+
+#[test]
+fn shared_nodes_are_counted_once() {
+    let model = CostModel::default();
+    let x = Scalar::variable("x");
+    let square = &x * &x;
+    let res = &(&square + &square) / &Scalar::new(2.);
+    assert_eq!(res.cost(&model), 2 + 1 + 8);
+    assert_eq!(Program::compile(&[res.into_dyn()]).cost(&model), 2 + 1 + 8);
+}
+
+#[test]
+fn model_is_tunable() {
+    let cheap_division = CostModel {
+        div: 1,
+        ..CostModel::default()
+    };
+    let quotient = parse("x / 4").unwrap();
+    let product = parse("x * 0.25").unwrap();
+    assert!(quotient.cost(&CostModel::default()) > product.cost(&CostModel::default()));
+    assert!(quotient.cost(&cheap_division) < product.cost(&cheap_division));
+}
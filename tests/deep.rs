@@ -0,0 +1,88 @@
+use rust_lazy::operation::{
+    DynScalar, Environment, EvalPolicy, GraphLimits, LimitError, Program, Scalar,
+};
+
+// Deep enough that recursing once per node overflows a test thread's stack.
+const DEPTH: usize = 100_000;
+
+fn chain() -> DynScalar {
+    let one = Scalar::new(1.).into_dyn();
+    let mut expr = Scalar::variable("x").into_dyn();
+    for _ in 0..DEPTH {
+        expr = expr.add(&one);
+    }
+    expr
+}
+
+#[test]
+fn executes_deep_chains() {
+    let expr = chain();
+    let env: Environment = [("x", 0.5)].into_iter().collect();
+    assert_eq!(expr.execute_with(&env), 0.5 + DEPTH as f32);
+    assert_eq!(
+        expr.try_execute_with(&env, EvalPolicy::Error),
+        Ok(0.5 + DEPTH as f32)
+    );
+}
+
+#[test]
+fn compiles_deep_chains() {
+    let expr = chain();
+    let program = Program::compile(std::slice::from_ref(&expr));
+    assert_eq!(program.instructions().len(), DEPTH + 2);
+    let env: Environment = [("x", 0.5)].into_iter().collect();
+    assert_eq!(program.run_with(&env), [0.5 + DEPTH as f32]);
+    // Compile state was cleared, so compiling again gives the same program.
+    assert_eq!(Program::compile(&[expr]).to_string(), program.to_string());
+}
+
+#[test]
+fn drops_deep_chains() {
+    for _ in 0..3 {
+        drop(chain());
+    }
+}
+
+#[test]
+fn deep_select_arms_stay_lazy() {
+    let x = Scalar::variable("x").into_dyn();
+    let mut expr = x.clone();
+    for _ in 0..DEPTH {
+        expr = Scalar::new(1.)
+            .into_dyn()
+            .select(&expr.add(&x), &Scalar::wildcard("never").into_dyn());
+    }
+    let env: Environment = [("x", 1.)].into_iter().collect();
+    assert_eq!(expr.execute_with(&env), 1. + DEPTH as f32);
+}
+
+#[test]
+fn limits_stop_early_on_deep_chains() {
+    let expr = chain();
+    assert_eq!(
+        GraphLimits::new().with_max_nodes(10).check(&expr),
+        Err(LimitError::TooManyNodes { limit: 10 })
+    );
+    assert_eq!(
+        GraphLimits::new().with_max_depth(10).check(&expr),
+        Err(LimitError::TooDeep { limit: 10 })
+    );
+    assert_eq!(
+        GraphLimits::new()
+            .with_max_depth(DEPTH + 1)
+            .with_max_nodes(DEPTH + 2)
+            .check(&expr),
+        Ok(())
+    );
+}
+
+#[test]
+fn finds_matches_in_deep_chains() {
+    let expr = chain();
+    let pattern = Scalar::wildcard("a")
+        .into_dyn()
+        .add(&Scalar::new(1.).into_dyn());
+    let matches = expr.find_all(&pattern);
+    assert_eq!(matches.len(), DEPTH);
+    assert_eq!(matches[0].node, expr);
+}
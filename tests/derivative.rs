@@ -0,0 +1,66 @@
+use rust_lazy::operation::{parse, Environment, Program, Scalar};
+
+fn at(x: f32, y: f32) -> Environment {
+    [("x", x), ("y", y)].into_iter().collect()
+}
+
+#[test]
+fn matches_dual_numbers() {
+    let x = Scalar::variable("x");
+    let mut exprs: Vec<_> = [
+        "x * x * x - 2 * x",
+        "(x + y) / (x - 3)",
+        "y / x",
+        "x * y - y * y / 4",
+    ]
+    .into_iter()
+    .map(|source| (source, parse(source).unwrap()))
+    .collect();
+    let branch = parse("x < 1")
+        .unwrap()
+        .select(&parse("x * x").unwrap(), &parse("2 - x").unwrap());
+    exprs.push(("select", &branch + &parse("y").unwrap()));
+    for (source, expr) in exprs {
+        let derivative = expr.derivative(&x.clone().into_dyn());
+        for (a, b) in [(0.5, 2.), (-1.25, 0.75), (4., -3.)] {
+            let env = at(a, b);
+            let expected = expr.execute_dual(&env, "x").derivative;
+            let actual = derivative.execute_with(&env);
+            assert!(
+                (actual - expected).abs() <= 1e-5 * expected.abs().max(1.),
+                "{}: {} vs {}",
+                source,
+                actual,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn derivatives_are_graphs() {
+    let x = Scalar::variable("x");
+    let cube = &(&x * &x) * &x;
+    let first = cube.derivative(&x);
+    let second = first.derivative(&x.clone().into_dyn());
+    assert_eq!(second.execute_with(&at(2., 0.)), 12.);
+
+    let program = Program::compile(std::slice::from_ref(&first));
+    assert_eq!(program.run_with(&at(3., 0.)), [27.]);
+}
+
+#[test]
+fn vanishing_terms_are_dropped() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    assert_eq!((&x * &y).derivative(&x).to_string(), "y");
+    assert_eq!(y.derivative(&x).to_string(), "0");
+    assert_eq!((&x + &Scalar::new(2.)).derivative(&x).to_string(), "1");
+}
+
+#[test]
+#[should_panic(expected = "with respect to a variable")]
+fn needs_a_variable() {
+    let x = Scalar::variable("x");
+    x.derivative(&(&x + &x));
+}
@@ -0,0 +1,79 @@
+use rust_lazy::operation::{parse, Divergence, Environment, Scalar};
+
+fn at(x: f32, y: f32) -> Environment {
+    [("x", x), ("y", y)].into_iter().collect()
+}
+
+#[test]
+fn reports_where_values_start_to_differ() {
+    let expr = parse("(x + 1) * (y - 2) + x").unwrap();
+    let before = expr.execute_logged(&at(1., 5.));
+    let after = expr.execute_logged(&at(1., 6.));
+    assert_eq!(
+        before.diff(&after, 0.),
+        [Divergence {
+            id: 3,
+            label: "y".to_string(),
+            before: Some(5.),
+            after: Some(6.),
+        }]
+    );
+    assert_eq!(before.diff(&before.clone(), 0.), []);
+    assert_eq!(
+        before.first_divergence(&after, 0.).unwrap().to_string(),
+        "#3 y: 5 -> 6"
+    );
+}
+
+#[test]
+fn independent_changes_are_each_reported() {
+    let expr = parse("x * 2 + y * 3").unwrap();
+    let diff = expr
+        .execute_logged(&at(1., 1.))
+        .diff(&expr.execute_logged(&at(2., 4.)), 0.);
+    let labels: Vec<_> = diff.iter().map(|d| d.label.as_str()).collect();
+    assert_eq!(labels, ["x", "y"]);
+}
+
+#[test]
+fn tolerance_is_relative() {
+    let expr = parse("x * 1000").unwrap();
+    let before = expr.execute_logged(&at(1., 0.));
+    let after = expr.execute_logged(&at(1.0001, 0.));
+    assert!(before.diff(&after, 1e-3).is_empty());
+    assert_eq!(before.diff(&after, 1e-5).len(), 1);
+}
+
+#[test]
+fn a_branch_change_diverges_at_the_select() {
+    let x = Scalar::variable("x");
+    let expr = x.gt(&Scalar::new(0.)).select(&(&x * &x), &Scalar::new(0.));
+    let before = expr.execute_logged(&at(2., 0.));
+    let after = expr.execute_logged(&at(-2., 0.));
+    let diff = before.diff(&after, 0.);
+    assert_eq!(diff[0].label, "x");
+    // The squared arm was skipped in the second run.
+    assert!(diff.iter().all(|d| d.label != "*"));
+}
+
+#[test]
+#[should_panic(expected = "different graphs")]
+fn logs_must_be_of_one_graph() {
+    let before = parse("x + 1").unwrap().execute_logged(&at(1., 0.));
+    let after = parse("x - 1").unwrap().execute_logged(&at(1., 0.));
+    before.diff(&after, 0.);
+}
+
+#[cfg(feature = "rational")]
+#[test]
+fn single_against_double_precision() {
+    use rust_lazy::operation::compare_precisions;
+
+    let expr = parse("(x + 1e8) - 1e8").unwrap();
+    let report = compare_precisions(&expr, &at(0.5, 0.));
+    let diff = report.single_log().diff(&report.double_log(), 1e-6);
+    // The sum is only slightly off in f32; the difference cancels that.
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff[0].label, "-");
+    assert_eq!((diff[0].before, diff[0].after), (Some(0.), Some(0.5)));
+}
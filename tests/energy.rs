@@ -0,0 +1,46 @@
+use rust_lazy::operation::{parse, CostModel, Program, TargetSpec};
+
+#[test]
+fn counts_ops_and_cycles() {
+    let program = Program::compile(&[parse("(x + 1) / y").unwrap()]);
+    let target = TargetSpec::cortex_m4f();
+    let report = program.energy_report(&target);
+    assert_eq!(report.target, "cortex-m4f");
+    assert_eq!(report.ops["load"], 2);
+    assert_eq!(report.ops["div"], 1);
+    assert_eq!(report.instructions(), program.instructions().len());
+    assert_eq!(report.cycles, program.cost(&target.cycles));
+    assert_eq!(report.seconds, report.cycles as f64 / 64e6);
+    assert!(report.to_string().starts_with("cortex-m4f: "));
+}
+
+#[test]
+fn compares_variants() {
+    let quotient = Program::compile(&[parse("x / 4").unwrap()]);
+    let product = Program::compile(&[parse("x * 0.25").unwrap()]);
+    for target in [TargetSpec::cortex_m4f(), TargetSpec::cortex_m0plus()] {
+        let (q, p) = (
+            quotient.energy_report(&target),
+            product.energy_report(&target),
+        );
+        assert!(p.picojoules < q.picojoules, "{}", target.name);
+        assert!(p.evaluations_per_second(1e-3) > q.evaluations_per_second(1e-3));
+    }
+}
+
+#[test]
+fn targets_are_configurable() {
+    let target = TargetSpec {
+        name: "custom".to_string(),
+        clock_hz: 1_000,
+        cycles: CostModel {
+            mul: 10,
+            ..CostModel::default()
+        },
+        picojoules: CostModel::default(),
+    };
+    let report = Program::compile(&[parse("x * x").unwrap()]).energy_report(&target);
+    assert_eq!(report.cycles, 10);
+    assert_eq!(report.seconds, 0.01);
+    assert_eq!(report.picojoules, 2);
+}
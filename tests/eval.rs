@@ -0,0 +1,70 @@
+use rust_lazy::operation::{parse, Environment, EvalError, EvalPolicy, Scalar};
+
+fn env(x: f32, y: f32) -> Environment {
+    [("x", x), ("y", y)].into_iter().collect()
+}
+
+#[test]
+fn names_the_offending_division() {
+    let expr = parse("1 + x / (y - y)").unwrap();
+    let error = expr.try_execute_with(&env(2., 3.), EvalPolicy::Error);
+    assert_eq!(
+        error,
+        Err(EvalError::DivisionByZero {
+            expression: "(x / (y - y))".to_string()
+        })
+    );
+    assert_eq!(
+        error.unwrap_err().to_string(),
+        "division by zero in (x / (y - y))"
+    );
+    assert_eq!(
+        parse("x / y")
+            .unwrap()
+            .try_execute_with(&env(3., 2.), EvalPolicy::Error),
+        Ok(1.5)
+    );
+    assert!(Scalar::new(1.)
+        .div(&Scalar::new(-0.))
+        .try_execute()
+        .is_err());
+}
+
+#[test]
+fn policies_replace_the_quotient() {
+    let expr = parse("x / y").unwrap();
+    let with = |x, policy| expr.try_execute_with(&env(x, 0.), policy).unwrap();
+    assert!(with(2., EvalPolicy::PropagateNaN).is_nan());
+    assert_eq!(with(2., EvalPolicy::Clamp), f32::MAX);
+    assert_eq!(with(-2., EvalPolicy::Clamp), -f32::MAX);
+    assert_eq!(with(0., EvalPolicy::Clamp), 0.);
+    assert!(with(f32::NAN, EvalPolicy::Clamp).is_nan());
+    assert_eq!(expr.execute_with(&env(2., 0.)), f32::INFINITY);
+}
+
+#[test]
+fn unbound_variables_are_errors() {
+    assert_eq!(
+        parse("x + z")
+            .unwrap()
+            .try_execute_with(&env(1., 2.), EvalPolicy::Error),
+        Err(EvalError::UnboundVariable {
+            name: "z".to_string()
+        })
+    );
+}
+
+#[test]
+fn untaken_select_arm_is_not_evaluated() {
+    let x = Scalar::variable("x");
+    let zero = Scalar::new(0.);
+    let safe = x.ne(&zero).select(&(&Scalar::new(1.) / &x), &zero);
+    assert_eq!(
+        safe.try_execute_with(&env(0., 0.), EvalPolicy::Error),
+        Ok(0.)
+    );
+    assert_eq!(
+        safe.try_execute_with(&env(4., 0.), EvalPolicy::Error),
+        Ok(0.25)
+    );
+}
@@ -0,0 +1,80 @@
+use std::thread;
+
+use rust_lazy::operation::{parse, Environment, FrozenExpr, Program, Scalar};
+
+fn noisy() -> Program {
+    let x = Scalar::variable("x");
+    let expr = &(&x * &Scalar::new(2.)) + &Scalar::gaussian_noise(1., Some(9));
+    Program::compile(&[expr.into_dyn(), parse("x / 4").unwrap()])
+}
+
+fn sequence(program: &Program, runs: usize) -> Vec<Vec<f32>> {
+    let mut context = program.context();
+    (0..runs)
+        .map(|i| {
+            let env: Environment = [("x", i as f32)].into_iter().collect();
+            program.run_in(&mut context, &env)
+        })
+        .collect()
+}
+
+#[test]
+fn programs_and_frozen_graphs_can_be_shared() {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<Program>();
+    shareable::<FrozenExpr>();
+}
+
+#[test]
+fn threads_share_a_program() {
+    let program = noisy();
+    let expected = sequence(&program, 50);
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| sequence(&program, 50)))
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    });
+}
+
+#[test]
+fn threads_share_a_frozen_graph() {
+    let expr = &Scalar::variable("x") + &Scalar::laplace_noise(0.5, Some(3));
+    let frozen = expr.freeze();
+    let run = || {
+        let mut context = frozen.context();
+        (0..50)
+            .map(|i| frozen.call_in(&mut context, &[i as f32]))
+            .collect::<Vec<f32>>()
+    };
+    let expected = run();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..4).map(|_| scope.spawn(run)).collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    });
+}
+
+#[test]
+fn run_with_starts_noise_over() {
+    let program = noisy();
+    let env: Environment = [("x", 1.)].into_iter().collect();
+    assert_eq!(program.run_with(&env), program.run_with(&env));
+
+    let mut context = program.context();
+    let first = program.run_in(&mut context, &env);
+    assert_eq!(first, program.run_with(&env));
+    assert_ne!(program.run_in(&mut context, &env), first);
+}
+
+#[test]
+fn contexts_count_noise_sources() {
+    assert_eq!(noisy().context().noise_sources(), 1);
+    assert_eq!(
+        parse("x + 1").unwrap().freeze().context().noise_sources(),
+        0
+    );
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "rational")]
+
+use num_rational::BigRational;
+use rust_lazy::operation::{parse, Environment, EvalError, Scalar};
+
+fn ratio(numerator: i64, denominator: i64) -> BigRational {
+    BigRational::new(numerator.into(), denominator.into())
+}
+
+#[test]
+fn rational_constants_stay_exact() {
+    assert_eq!(parse("1 / 3 * 3").unwrap().execute_exact(), ratio(1, 1));
+    assert_eq!(parse("1 / 3 + 1 / 6").unwrap().execute_exact(), ratio(1, 2));
+    // In f32 the big term swallows the small one.
+    let cancel = parse("(1e8 + 1 / 4) - 1e8").unwrap();
+    assert_eq!(cancel.execute(), 0.);
+    assert_eq!(cancel.execute_exact(), ratio(1, 4));
+}
+
+#[test]
+fn inputs_convert_exactly() {
+    let env: Environment = [("x", 0.1)].into_iter().collect();
+    let exact = parse("x * 10").unwrap().execute_exact_with(&env);
+    assert_eq!(
+        exact,
+        BigRational::from_float(0.1f32).unwrap() * ratio(10, 1)
+    );
+    assert_ne!(exact, ratio(1, 1));
+}
+
+#[test]
+fn selects_and_comparisons() {
+    let x = Scalar::variable("x");
+    let guarded = x
+        .ne(&Scalar::new(0.))
+        .select(&(&Scalar::new(1.) / &x), &Scalar::new(0.));
+    let at = |x: f32| -> Environment { [("x", x)].into_iter().collect() };
+    assert_eq!(guarded.execute_exact_with(&at(3.)), ratio(1, 3));
+    // The untaken arm would divide by zero.
+    assert_eq!(guarded.execute_exact_with(&at(0.)), ratio(0, 1));
+}
+
+#[test]
+fn errors() {
+    let env = Environment::new();
+    assert!(matches!(
+        parse("1 / (2 - 2)").unwrap().try_execute_exact_with(&env),
+        Err(EvalError::DivisionByZero { .. })
+    ));
+    assert!(matches!(
+        parse("y + 1").unwrap().try_execute_exact_with(&env),
+        Err(EvalError::UnboundVariable { .. })
+    ));
+    assert!(matches!(
+        (&Scalar::pi() * &Scalar::new(2.)).try_execute_exact_with(&env),
+        Err(EvalError::NotRational { .. })
+    ));
+}
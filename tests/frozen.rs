@@ -0,0 +1,61 @@
+use rust_lazy::operation::{parse, DynScalar, Environment, Scalar};
+
+#[test]
+fn matches_execute() {
+    let expr = parse("(a + 2.5) * b / (c - 1) - a * a").unwrap();
+    let frozen = expr.freeze();
+    assert_eq!(frozen.variables(), ["a", "b", "c"]);
+
+    let env: Environment = [("a", 1.5), ("b", 4.), ("c", 3.)].into_iter().collect();
+    assert_eq!(frozen.execute_with(&env), expr.execute_with(&env));
+    assert_eq!(frozen.call(&[1.5, 4., 3.]), expr.execute_with(&env));
+}
+
+#[test]
+fn snapshot_ignores_later_edits() {
+    let x = Scalar::variable("x");
+    let slot = Scalar::alias(&x * &Scalar::pi());
+    let res = &slot + &slot;
+    let frozen = res.freeze();
+
+    slot.set_target(Scalar::new(0.));
+    assert_eq!(frozen.call(&[1.]), 2. * std::f32::consts::PI);
+    assert_eq!(res.execute(), 0.);
+}
+
+#[test]
+fn seeded_noise_starts_from_its_seed() {
+    let expr: DynScalar = (&Scalar::new(1.) + &Scalar::gaussian_noise(1., Some(5))).into();
+    let frozen = expr.freeze();
+    let first: Vec<f32> = (0..3).map(|_| frozen.execute()).collect();
+    let second: Vec<f32> = (0..3).map(|_| expr.execute()).collect();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn threads_call_at_the_same_time() {
+    let frozen = parse("a * a - b / 3").unwrap().freeze();
+    std::thread::scope(|scope| {
+        for t in 0..4 {
+            let frozen = &frozen;
+            scope.spawn(move || {
+                for i in 0..1000 {
+                    let (a, b) = (t as f32, i as f32);
+                    assert_eq!(frozen.call(&[a, b]), a * a - b / 3.);
+                }
+            });
+        }
+    });
+
+    // Noise streams still carry on from call to call, whichever thread
+    // makes it.
+    let noisy: DynScalar = Scalar::laplace_noise(1., Some(9)).into();
+    let frozen = noisy.freeze();
+    let expected: Vec<f32> = (0..4).map(|_| noisy.execute()).collect();
+    let mut drawn = vec![frozen.execute()];
+    std::thread::scope(|scope| {
+        drawn.push(scope.spawn(|| frozen.execute()).join().unwrap());
+    });
+    drawn.extend([frozen.execute(), frozen.execute()]);
+    assert_eq!(drawn, expected);
+}
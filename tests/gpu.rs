@@ -0,0 +1,58 @@
+#![cfg(feature = "gpu")]
+
+use rust_lazy::operation::{parse, GpuError, GpuProgram, Program, Scalar};
+
+// Most machines running the tests have no GPU; those skip the runs.
+fn gpu(program: &Program) -> Option<GpuProgram> {
+    match GpuProgram::new(program) {
+        Ok(gpu) => Some(gpu),
+        Err(GpuError::NoAdapter) => None,
+        Err(error) => panic!("{}", error),
+    }
+}
+
+#[test]
+fn noise_is_rejected_before_touching_the_gpu() {
+    let expr = &Scalar::variable("x") + &Scalar::gaussian_noise(1., Some(1));
+    let program = Program::compile(&[expr.into_dyn()]);
+    assert!(matches!(
+        GpuProgram::new(&program),
+        Err(GpuError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn matches_the_cpu() {
+    let program = Program::compile(&[
+        parse("(x * x + y) / (y + 3)").unwrap(),
+        parse("(x < y) * x - y").unwrap(),
+    ]);
+    let Some(gpu) = gpu(&program) else {
+        return;
+    };
+    assert_eq!(gpu.variables(), ["x", "y"]);
+    let rows: Vec<[f32; 2]> = (0..1000)
+        .map(|i| [i as f32 * 0.25, 7. - i as f32 * 0.5])
+        .collect();
+    let rows: Vec<&[f32]> = rows.iter().map(|row| &row[..]).collect();
+    let expected = program.run_batch(&rows);
+    let actual = gpu.run_batch(&rows).unwrap();
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(&expected) {
+        assert!(
+            a == e || (a - e).abs() <= 1e-5 * e.abs().max(1.),
+            "{} vs {}",
+            a,
+            e
+        );
+    }
+}
+
+#[test]
+fn programs_without_variables() {
+    let program = Program::compile(&[parse("2 * 3").unwrap()]);
+    let Some(gpu) = gpu(&program) else {
+        return;
+    };
+    assert_eq!(gpu.run_batch(&[&[], &[]]).unwrap(), [6., 6.]);
+}
@@ -0,0 +1,81 @@
+#![cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+
+use rust_lazy::operation::{
+    parse, Environment, Instruction, Jit, JitError, Opcode, Program, Scalar,
+};
+
+#[test]
+fn matches_execute() {
+    let env: Environment = [("a", 1.25), ("b", -3.), ("c", 7.5)].into_iter().collect();
+    for source in [
+        "(a + 2.5) * b / (c - 1)",
+        "a * a * a - b / c",
+        "-(a - b) * (c + a) / (a * b)",
+        "3 / 7 + 1e-3",
+    ] {
+        let expr = parse(source).unwrap();
+        let jit = Jit::compile(&expr.clone().compile()).unwrap();
+        assert_eq!(jit.call_with(&env), expr.execute_with(&env), "{}", source);
+    }
+}
+
+#[test]
+fn variables_are_passed_in_order_of_first_use() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let jit = Jit::compile(&(&y - &x).compile()).unwrap();
+    assert_eq!(jit.variables(), ["y", "x"]);
+    assert_eq!(jit.call(&[10., 4.]), 6.);
+}
+
+#[test]
+fn runs_allocated_programs() {
+    let mut res = Scalar::variable("x").into_dyn();
+    for i in 1..16 {
+        res = &(&res * &Scalar::new(i as f32).into_dyn()) + &Scalar::variable("x").into_dyn();
+    }
+    let program = Program::compile(&[res.clone()]);
+    let jit = Jit::compile(program.instructions()).unwrap();
+    let env: Environment = [("x", 0.5)].into_iter().collect();
+    assert_eq!(jit.call_with(&env), res.execute_with(&env));
+}
+
+#[test]
+fn select_tests_against_zero() {
+    let flag = Scalar::variable("flag");
+    let x = Scalar::variable("x");
+    let jit = Jit::compile(&flag.select(&x, &(&x + &x)).compile()).unwrap();
+    for (flag, expected) in [(1., 3.), (0., 6.), (-0., 6.), (f32::NAN, 3.)] {
+        assert_eq!(jit.call(&[flag, 3.]), expected);
+    }
+}
+
+#[test]
+fn noise_is_unsupported() {
+    let expr = &Scalar::new(1.) + &Scalar::gaussian_noise(1., Some(3));
+    assert!(matches!(
+        Jit::compile(&expr.compile()),
+        Err(JitError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn comparisons_yield_one_or_zero() {
+    let (a, b) = (Scalar::variable("a"), Scalar::variable("b"));
+    let jit = Jit::compile(&(&a.lt(&b) + &(&a.ne(&b) * &Scalar::new(2.))).compile()).unwrap();
+    for (x, y, expected) in [(1., 2., 3.), (2., 2., 0.), (3., 2., 2.), (f32::NAN, 2., 2.)] {
+        assert_eq!(jit.call(&[x, y]), expected);
+    }
+}
+
+#[test]
+fn malformed_instructions_are_errors() {
+    assert_eq!(Jit::compile(&[]).err(), Some(JitError::Empty));
+    let dangling = [
+        Instruction::from_opcode(Opcode::Constant(1.), 0),
+        Instruction::from_opcode(Opcode::Mul(0, 9), 1),
+    ];
+    let error = Jit::compile(&dangling).err().unwrap();
+    assert_eq!(error, JitError::Unwritten { register: 9 });
+    assert_eq!(error.to_string(), "%9 is read before it is written");
+}
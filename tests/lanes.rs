@@ -0,0 +1,71 @@
+use rust_lazy::operation::{parse, CompileOptions, Environment, Program, Spilling, LANES};
+
+// Enough rows for full chunks and a partial one.
+fn rows() -> Vec<[f32; 2]> {
+    let values = [
+        0.,
+        -0.,
+        1.,
+        -2.5,
+        f32::MAX,
+        f32::MIN_POSITIVE,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+    ];
+    let mut rows = Vec::new();
+    for &x in &values {
+        for &y in &values[..3] {
+            rows.push([x, y]);
+        }
+    }
+    assert!(rows.len() % LANES != 0);
+    rows
+}
+
+#[test]
+fn lanes_match_rows_bit_for_bit() {
+    let program = Program::compile(&[
+        parse("x * y - x / y").unwrap(),
+        parse("(x != y) + (x < y) * 2 + (x >= y) * 4").unwrap(),
+        parse("x == 0")
+            .unwrap()
+            .select(&parse("y").unwrap(), &parse("x + 1").unwrap()),
+        parse("x")
+            .unwrap()
+            .select(&parse("1").unwrap(), &parse("2").unwrap()),
+    ]);
+    let rows = rows();
+    let rows: Vec<&[f32]> = rows.iter().map(|row| &row[..]).collect();
+    let expected: Vec<u32> = rows
+        .iter()
+        .flat_map(|row| {
+            let env: Environment = [("x", row[0]), ("y", row[1])].into_iter().collect();
+            program.run_with(&env)
+        })
+        .map(f32::to_bits)
+        .collect();
+    let actual: Vec<u32> = program
+        .run_batch(&rows)
+        .into_iter()
+        .map(f32::to_bits)
+        .collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn spilled_programs_run_in_lanes() {
+    let expr = parse("(x + 1) * (x + 2) * (x + 3) + (x - 1) / (x - 2)").unwrap();
+    let options = CompileOptions::new()
+        .with_registers(3)
+        .with_strategy(Spilling);
+    let program = Program::compile_with(std::slice::from_ref(&expr), &options).unwrap();
+    assert!(program.memory_cells() > 0);
+    let rows: Vec<[f32; 1]> = (0..LANES * 2 + 3).map(|i| [i as f32 * 0.75]).collect();
+    let rows: Vec<&[f32]> = rows.iter().map(|row| &row[..]).collect();
+    let expected: Vec<f32> = rows
+        .iter()
+        .map(|row| expr.execute_with(&[("x", row[0])].into_iter().collect()))
+        .collect();
+    assert_eq!(program.run_batch(&rows), expected);
+}
@@ -0,0 +1,43 @@
+use rust_lazy::operation::{parse, Program, Scalar};
+
+#[test]
+fn renders_llvm_ir() {
+    let program = Program::compile(&[parse("(x + 2) * y - x").unwrap()]);
+    assert_eq!(
+        program.to_llvm_ir().unwrap(),
+        "define float @eval(float %x, float %y) {
+entry:
+  %0 = fadd float %x, 0x4000000000000000
+  %1 = fmul float %0, %y
+  %2 = fsub float %1, %x
+  ret float %2
+}
+"
+    );
+}
+
+#[test]
+fn several_outputs_are_returned_as_an_array() {
+    let x = Scalar::variable("x");
+    let program = Program::compile(&[(&x * &x).into_dyn(), Scalar::new(0.1).into_dyn()]);
+    assert_eq!(
+        program.to_llvm_ir().unwrap(),
+        "define [2 x float] @eval(float %x) {
+entry:
+  %0 = fmul float %x, %x
+  %1 = insertvalue [2 x float] poison, float %0, 0
+  %2 = insertvalue [2 x float] %1, float 0x3FB99999A0000000, 1
+  ret [2 x float] %2
+}
+"
+    );
+}
+
+#[test]
+fn unusual_names_are_quoted() {
+    let program = Program::compile(&[Scalar::variable("rate \"a\"").into_dyn()]);
+    assert!(program
+        .to_llvm_ir()
+        .unwrap()
+        .contains("@eval(float %\"rate \\22a\\22\")"));
+}
@@ -0,0 +1,67 @@
+use rust_lazy::operation::{parse, Environment, Scalar};
+
+fn at(x: f32, y: f32) -> Environment {
+    [("x", x), ("y", y)].into_iter().collect()
+}
+
+#[test]
+fn every_node_is_logged() {
+    let expr = parse("(x + 1) * y").unwrap();
+    let log = expr.execute_logged(&at(2., 1.5));
+    assert_eq!(log.result(), 4.5);
+    assert_eq!(log.entries().len(), 5);
+    let sum = log.labelled("+").next().unwrap();
+    assert_eq!(sum.value, Some(3.));
+    assert_eq!(log.value(sum.operands[0]), Some(2.));
+    assert_eq!(log.labelled("y").next().unwrap().value, Some(1.5));
+    assert_eq!(log.get(log.entries().len() - 1).unwrap().label, "*");
+    assert_eq!(log.get(99), None);
+    assert_eq!(
+        log.to_string(),
+        "#0 x = 2\n#1 1 = 1\n#2 + #0 #1 = 3\n#3 y = 1.5\n#4 * #2 #3 = 4.5\n"
+    );
+}
+
+#[test]
+fn ids_are_stable_for_a_graph() {
+    let a = parse("x * y - x / y").unwrap();
+    let b = parse("x * y - x / y").unwrap();
+    let (first, second) = (a.execute_logged(&at(3., 4.)), b.execute_logged(&at(3., 4.)));
+    assert_eq!(first, second);
+    assert_ne!(first, a.execute_logged(&at(3., 5.)));
+}
+
+#[test]
+fn untaken_arms_are_skipped() {
+    let x = Scalar::variable("x");
+    let expr = x
+        .lt(&Scalar::new(0.))
+        .select(&(&x * &Scalar::new(-1.)).with_provenance("R-neg"), &x);
+    let log = expr.execute_logged(&at(4., 0.));
+    assert_eq!(log.result(), 4.);
+    assert_eq!(log.labelled("R-neg").next().unwrap().value, None);
+    assert_eq!(log.labelled("select").next().unwrap().value, Some(4.));
+
+    let log = expr.execute_logged(&at(-2., 0.));
+    assert_eq!(log.labelled("R-neg").next().unwrap().value, Some(2.));
+}
+
+#[test]
+fn logging_draws_the_same_noise() {
+    let expr = || &Scalar::variable("x") + &Scalar::gaussian_noise(1., Some(9));
+    let (plain, logged) = (expr(), expr());
+    for _ in 0..3 {
+        let expected = plain.execute_with(&at(1., 0.));
+        assert_eq!(logged.execute_logged(&at(1., 0.)).result(), expected as f64);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn logs_round_trip() {
+    use rust_lazy::operation::EvalLog;
+
+    let log = parse("x / y").unwrap().execute_logged(&at(1., 4.));
+    let json = serde_json::to_string(&log).unwrap();
+    assert_eq!(serde_json::from_str::<EvalLog>(&json).unwrap(), log);
+}
@@ -0,0 +1,75 @@
+use rust_lazy::operation::{parse, Environment, Scalar};
+
+#[test]
+fn matches_execute() {
+    let expr = parse("(a + 2.5) * b / (c - 1) - a * a").unwrap();
+    let mut memo = expr.memoize();
+    for (a, b, c) in [(1.5, 4., 3.), (1.5, 4., 5.), (-2., 4., 5.)] {
+        let env: Environment = [("a", a), ("b", b), ("c", c)].into_iter().collect();
+        assert_eq!(memo.execute_with(&env), expr.execute_with(&env));
+    }
+}
+
+#[test]
+fn unchanged_leaves_are_not_recomputed() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let left = &(&x * &x) + &Scalar::new(1.);
+    let right = &y * &Scalar::new(3.);
+    let res = &left * &right;
+    let mut memo = res.memoize();
+    memo.set("x", 2.);
+    memo.set("y", 1.);
+
+    assert_eq!(memo.execute(), 15.);
+    assert_eq!(memo.recomputed(), 8);
+    assert_eq!(memo.execute(), 15.);
+    assert_eq!(memo.recomputed(), 0);
+
+    // Only y, y * 3 and the root depend on y.
+    memo.set("y", 2.);
+    assert_eq!(memo.execute(), 30.);
+    assert_eq!(memo.recomputed(), 3);
+
+    // Setting the same value dirties nothing.
+    memo.set("y", 2.);
+    assert_eq!(memo.execute(), 30.);
+    assert_eq!(memo.recomputed(), 0);
+}
+
+#[test]
+fn unchanged_values_stop_propagation() {
+    let x = Scalar::variable("x");
+    let square = &x * &x;
+    let res = &square + &Scalar::new(1.);
+    let mut memo = res.memoize();
+    memo.set("x", 3.);
+    assert_eq!(memo.execute(), 10.);
+
+    // x changes but x * x does not, so the root is left alone.
+    memo.set("x", -3.);
+    assert_eq!(memo.execute(), 10.);
+    assert_eq!(memo.recomputed(), 2);
+}
+
+#[test]
+fn noise_is_resampled() {
+    let res = &Scalar::gaussian_noise(1., Some(7)) + &Scalar::new(1.);
+    let mut memo = res.memoize();
+    let first = memo.execute();
+    assert_ne!(memo.execute(), first);
+    assert_eq!(memo.recomputed(), 2);
+}
+
+#[test]
+#[should_panic(expected = "unbound variable x")]
+fn unbound_variable_panics() {
+    Scalar::variable("x").memoize().execute();
+}
+
+#[test]
+fn ignores_unknown_names() {
+    let mut memo = Scalar::variable("x").memoize();
+    assert!(!memo.set("y", 1.));
+    assert!(memo.set("x", 1.));
+}
@@ -0,0 +1,95 @@
+use rust_lazy::{
+    monte_carlo::{monte_carlo, InputDistribution, RandomVars},
+    operation::{parse, Scalar},
+};
+
+fn contains((low, high): (f64, f64), value: f64) -> bool {
+    low <= value && value <= high
+}
+
+#[test]
+fn estimates_a_uniform_mean() {
+    let vars = RandomVars::new().with_uniform("x", 0., 1.).with_seed(1);
+    let estimate = monte_carlo(parse("x").unwrap(), &vars, 20_000);
+    assert!(
+        contains(estimate.confidence_interval_95(), 0.5),
+        "{}",
+        estimate
+    );
+    assert!((estimate.variance - 1. / 12.).abs() < 0.005, "{}", estimate);
+    assert_eq!(estimate.samples, 20_000);
+}
+
+#[test]
+fn estimates_a_function_of_gaussian_inputs() {
+    // E[x^2 + y] = mean^2 + sigma^2 + E[y] = 9 + 4 - 1.
+    let vars = RandomVars::new()
+        .with_gaussian("x", 3., 2.)
+        .with_laplace("y", -1., 0.5)
+        .with_seed(7);
+    let estimate = monte_carlo(parse("x * x + y").unwrap(), &vars, 50_000);
+    assert!(
+        contains(estimate.confidence_interval_95(), 12.),
+        "{}",
+        estimate
+    );
+    let (low, high) = estimate.confidence_interval_95();
+    assert!(high - low < 0.5, "{}", estimate);
+}
+
+#[test]
+fn noise_nodes_and_constants_mix() {
+    let x = Scalar::variable("x");
+    let expr = &x + &Scalar::gaussian_noise(1., Some(1));
+    let vars = RandomVars::new().with_constant("x", 2.).with_seed(3);
+    let estimate = monte_carlo(expr, &vars, 20_000);
+    assert!(
+        contains(estimate.confidence_interval_95(), 2.),
+        "{}",
+        estimate
+    );
+    assert!((estimate.variance - 1.).abs() < 0.05, "{}", estimate);
+    // Noise alone needs no variables at all.
+    let estimate = monte_carlo(
+        Scalar::laplace_noise(1., Some(2)),
+        &RandomVars::new(),
+        20_000,
+    );
+    assert!(
+        contains(estimate.confidence_interval(4.), 0.),
+        "{}",
+        estimate
+    );
+}
+
+#[test]
+fn seeds_make_estimates_reproducible() {
+    let vars = RandomVars::new()
+        .with_variable("x", InputDistribution::Uniform { low: -1., high: 1. })
+        .with_seed(11);
+    let expr = parse("x * x").unwrap();
+    assert_eq!(
+        monte_carlo(expr.clone(), &vars, 5_000),
+        monte_carlo(expr.clone(), &vars, 5_000)
+    );
+    assert_ne!(
+        monte_carlo(expr.clone(), &vars, 5_000),
+        monte_carlo(expr.clone(), &vars.clone().with_seed(12), 5_000)
+    );
+    // Configuring another variable leaves this one's samples alone.
+    assert_eq!(
+        monte_carlo(expr.clone(), &vars, 5_000),
+        monte_carlo(
+            expr.clone(),
+            &vars.clone().with_uniform("unused", 0., 1.),
+            5_000
+        )
+    );
+}
+
+#[test]
+#[should_panic(expected = "no distribution for variable y")]
+fn every_variable_needs_a_distribution() {
+    let vars = RandomVars::new().with_constant("x", 1.);
+    monte_carlo(parse("x + y").unwrap(), &vars, 10);
+}
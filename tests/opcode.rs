@@ -0,0 +1,80 @@
+use rust_lazy::operation::{
+    parse, Comparison, Environment, Instruction, Opcode, Program, ProgramError,
+};
+
+#[test]
+fn instructions_are_inspectable() {
+    let program = Program::compile(&[parse("x * 2 + (x < 1)").unwrap()]);
+    let opcodes: Vec<Opcode> = program.instructions().iter().map(|i| i.opcode()).collect();
+    assert!(matches!(opcodes[0], Opcode::Load(ref name) if name == "x"));
+    assert_eq!(opcodes[1], Opcode::Constant(2.));
+    assert!(opcodes
+        .iter()
+        .any(|op| matches!(op, Opcode::Compare(Comparison::Lt, ..))));
+    for instruction in program.instructions() {
+        assert_eq!(instruction.operands(), instruction.opcode().operands());
+    }
+    let last = program.instructions().last().unwrap();
+    assert_eq!(last.opcode().name(), "add");
+    assert_eq!(program.outputs(), [last.ret()]);
+}
+
+#[test]
+fn programs_can_be_rewritten() {
+    // Strength-reduce multiplications by two into additions.
+    let program = Program::compile(&[parse("x * 2 - 1").unwrap()]);
+    let constants: Vec<(usize, f32)> = program
+        .instructions()
+        .iter()
+        .filter_map(|i| match i.opcode() {
+            Opcode::Constant(value) => Some((i.ret(), value)),
+            _ => None,
+        })
+        .collect();
+    let two = |r: usize| {
+        constants
+            .iter()
+            .any(|&(ret, value)| ret == r && value == 2.)
+    };
+    let rewritten: Vec<Instruction> = program
+        .instructions()
+        .iter()
+        .map(|i| match i.opcode() {
+            Opcode::Mul(a, b) if two(b) => Instruction::from_opcode(Opcode::Add(a, a), i.ret()),
+            opcode => Instruction::from_opcode(opcode, i.ret()),
+        })
+        .collect();
+    let rewritten = Program::new(rewritten, program.outputs().to_vec()).unwrap();
+    assert!(rewritten
+        .instructions()
+        .iter()
+        .all(|i| !matches!(i.opcode(), Opcode::Mul(..))));
+    let env: Environment = [("x", 3.5)].into_iter().collect();
+    assert_eq!(rewritten.run_with(&env), program.run_with(&env));
+}
+
+#[test]
+fn rejects_reads_before_writes() {
+    let instructions = vec![
+        Instruction::from_opcode(Opcode::Constant(1.), 0),
+        Instruction::from_opcode(Opcode::Add(0, 1), 2),
+    ];
+    let error = Program::new(instructions, vec![2]).err().unwrap();
+    assert_eq!(
+        error,
+        ProgramError::Unwritten {
+            instruction: 1,
+            register: 1
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "instruction 1 reads %1 before it is written"
+    );
+
+    let instructions = vec![Instruction::from_opcode(Opcode::Constant(1.), 0)];
+    assert_eq!(
+        Program::new(instructions, vec![3]).err(),
+        Some(ProgramError::UnwrittenOutput { register: 3 })
+    );
+}
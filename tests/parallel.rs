@@ -0,0 +1,49 @@
+#![cfg(feature = "parallel")]
+
+use rust_lazy::operation::{DynScalar, Environment, Scalar, PAR_THRESHOLD};
+
+fn wide(width: usize) -> (DynScalar, Environment) {
+    let terms: Vec<DynScalar> = (0..width)
+        .map(|i| {
+            let x = Scalar::variable(format!("x{}", i));
+            (&(&x * &x) / &Scalar::new(i as f32 + 1.)).into()
+        })
+        .collect();
+    let env = (0..width)
+        .map(|i| (format!("x{}", i), i as f32 * 0.25 - 3.))
+        .collect();
+    (DynScalar::sum(&terms), env)
+}
+
+#[test]
+fn matches_sequential_execution() {
+    let (expr, env) = wide(500);
+    let frozen = expr.freeze();
+    assert_eq!(frozen.par_execute_with(&env, 0), expr.execute_with(&env));
+    assert_eq!(expr.par_execute_with(&env, 0), expr.execute_with(&env));
+    assert_eq!(
+        expr.par_execute_with(&env, PAR_THRESHOLD),
+        expr.execute_with(&env)
+    );
+}
+
+#[test]
+fn noise_matches_sequential_execution() {
+    let terms: Vec<DynScalar> = (0..64)
+        .map(|i| (&Scalar::gaussian_noise(1., Some(i)) * &Scalar::new(2.)).into())
+        .collect();
+    let frozen = DynScalar::sum(&terms).freeze();
+    let serial = frozen.execute();
+    assert_eq!(
+        DynScalar::sum(&terms)
+            .freeze()
+            .par_execute_with(&Environment::new(), 0),
+        serial
+    );
+}
+
+#[test]
+fn small_graphs_run_sequentially() {
+    let expr = &Scalar::new(1.5) + &Scalar::e();
+    assert_eq!(expr.par_execute(PAR_THRESHOLD), expr.execute());
+}
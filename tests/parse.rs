@@ -0,0 +1,48 @@
+use rust_lazy::operation::{parse, DynScalar, Environment, ParseError};
+
+#[test]
+fn respects_precedence_and_parentheses() {
+    let env: Environment = [("a", 1.5), ("b", 4.), ("c", 3.)].into_iter().collect();
+    let expr = parse("(a + 2.5) * b / (c - 1)").unwrap();
+    assert_eq!(expr.execute_with(&env), 8.);
+    assert_eq!(parse("a + b * c - 1").unwrap().execute_with(&env), 12.5);
+    assert_eq!(parse("b / c / 2").unwrap().execute_with(&env), 4. / 3. / 2.);
+}
+
+#[test]
+fn unary_minus() {
+    let env: Environment = [("x", 2.)].into_iter().collect();
+    assert_eq!(parse("-x * 3").unwrap().execute_with(&env), -6.);
+    assert_eq!(parse("1 - -x").unwrap().execute_with(&env), 3.);
+    assert_eq!(parse("-(x - 5)").unwrap().execute_with(&env), 3.);
+    assert_eq!(parse("-2.5e1").unwrap().execute(), -25.);
+}
+
+#[test]
+fn from_str() {
+    let expr: DynScalar = "1.5 * 2".parse().unwrap();
+    assert_eq!(expr.execute(), 3.);
+}
+
+#[test]
+fn reports_errors() {
+    assert_eq!(parse("(a + 1").err(), Some(ParseError::UnexpectedEnd));
+    assert_eq!(
+        parse("a + * b").err(),
+        Some(ParseError::UnexpectedCharacter {
+            position: 4,
+            character: '*'
+        })
+    );
+    assert_eq!(
+        parse("2 x").err(),
+        Some(ParseError::UnexpectedCharacter {
+            position: 2,
+            character: 'x'
+        })
+    );
+    assert_eq!(
+        parse("1.2.3").err(),
+        Some(ParseError::InvalidNumber { position: 0 })
+    );
+}
@@ -0,0 +1,106 @@
+use rust_lazy::operation::{optimize, parse, Environment, Instruction, Opcode, Program, Scalar};
+
+fn op(opcode: Opcode, ret: usize) -> Instruction {
+    Instruction::from_opcode(opcode, ret)
+}
+
+fn opcodes(instructions: &[Instruction]) -> Vec<Opcode> {
+    instructions.iter().map(|i| i.opcode()).collect()
+}
+
+#[test]
+fn folds_constant_arithmetic() {
+    let program = Program::compile(&[parse("2 * 3 + x").unwrap()]);
+    let optimized = program.peephole();
+    assert!(opcodes(optimized.instructions()).contains(&Opcode::Constant(6.)));
+    assert_eq!(optimized.instructions().len(), 3);
+    let env: Environment = [("x", 1.5)].into_iter().collect();
+    assert_eq!(optimized.run_with(&env), program.run_with(&env));
+}
+
+#[test]
+fn removes_additions_of_zero() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let expr = &(&x + &Scalar::new(0.)) * &(&y - &Scalar::new(0.));
+    let program = Program::compile(&[expr.into_dyn()]);
+    let optimized = program.peephole();
+    assert_eq!(
+        opcodes(optimized.instructions())
+            .iter()
+            .map(Opcode::name)
+            .collect::<Vec<_>>(),
+        ["load", "load", "mul"]
+    );
+    let env: Environment = [("x", 3.), ("y", -2.)].into_iter().collect();
+    assert_eq!(optimized.run_with(&env), [-6.]);
+}
+
+#[test]
+fn collapses_duplicate_constants() {
+    let instructions = vec![
+        op(Opcode::Load("x".to_string()), 0),
+        op(Opcode::Constant(2.), 1),
+        op(Opcode::Mul(0, 1), 2),
+        op(Opcode::Constant(2.), 3),
+        op(Opcode::Add(2, 3), 4),
+    ];
+    let optimized = optimize::peephole(&instructions);
+    assert_eq!(
+        opcodes(&optimized),
+        [
+            Opcode::Load("x".to_string()),
+            Opcode::Constant(2.),
+            Opcode::Mul(0, 1),
+            Opcode::Add(2, 1),
+        ]
+    );
+}
+
+#[test]
+fn keeps_constants_whose_register_is_overwritten() {
+    let instructions = vec![
+        op(Opcode::Load("x".to_string()), 0),
+        op(Opcode::Constant(2.), 1),
+        op(Opcode::Mul(0, 1), 1),
+        op(Opcode::Constant(2.), 2),
+        op(Opcode::Add(1, 2), 3),
+    ];
+    let optimized = optimize::peephole(&instructions);
+    assert_eq!(optimized.len(), 5);
+    let program = Program::new(optimized, vec![3]).unwrap();
+    let env: Environment = [("x", 5.)].into_iter().collect();
+    assert_eq!(program.run_with(&env), [12.]);
+}
+
+#[test]
+fn drops_unread_results_but_not_noise() {
+    let instructions = vec![
+        op(Opcode::Load("x".to_string()), 0),
+        op(Opcode::Load("y".to_string()), 1),
+        op(Opcode::Mul(1, 1), 2),
+        op(
+            Opcode::Noise(rust_lazy::operation::Distribution::Gaussian(1.), None),
+            3,
+        ),
+        op(Opcode::Add(0, 0), 4),
+    ];
+    let names: Vec<&str> = optimize::peephole(&instructions)
+        .iter()
+        .map(|i| i.opcode().name())
+        .collect();
+    assert_eq!(names, ["load", "noise", "add"]);
+}
+
+#[test]
+fn preserves_results_of_spilled_programs() {
+    let expr = parse("(a + 0) * (b - 0) + (a * 1 + 0) / (b + 2 * 3) - (a - b) * 0").unwrap();
+    let program = Program::compile(std::slice::from_ref(&expr));
+    let spilled = program.spill(2).unwrap();
+    let optimized = spilled.peephole();
+    assert!(optimized.instructions().len() < spilled.instructions().len());
+    for (a, b) in [(1., 2.), (-3., 0.5), (0., -5.)] {
+        let env: Environment = [("a", a), ("b", b)].into_iter().collect();
+        assert_eq!(optimized.run_with(&env), spilled.run_with(&env));
+    }
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "rational")]
+
+use rust_lazy::operation::{compare_precisions, parse, Environment, OpKind};
+
+#[test]
+fn pinpoints_cancellation() {
+    let expr = parse("(x + 100000000) - 100000000").unwrap();
+    let env: Environment = [("x", 1.)].into_iter().collect();
+    let report = compare_precisions(&expr, &env);
+
+    assert_eq!(report.root().single, 0.);
+    assert_eq!(report.root().double, 1.);
+    assert_eq!(report.root().exact.as_ref().unwrap().to_string(), "1");
+
+    let divergence = report.first_divergence(1e-6).unwrap();
+    assert_eq!(divergence.node.kind(), OpKind::Sub);
+    assert_eq!(report.worst().node.kind(), OpKind::Sub);
+}
+
+#[test]
+fn exact_arithmetic_has_no_rounding() {
+    let expr = parse("1 / 3 * 3").unwrap();
+    let report = compare_precisions(&expr, &Environment::new());
+
+    assert_eq!(report.nodes().len(), 5);
+    assert_eq!(report.root().exact.as_ref().unwrap().to_string(), "1");
+    let divergence = report.first_divergence(0.).unwrap();
+    assert_eq!(divergence.node.kind(), OpKind::Div);
+    assert!(divergence.relative_error() < 1e-7);
+}
+
+#[test]
+fn division_by_zero_has_no_exact_value() {
+    let expr = parse("1 / (x - x)").unwrap();
+    let env: Environment = [("x", 2.)].into_iter().collect();
+    let report = compare_precisions(&expr, &env);
+
+    assert!(report.root().exact.is_none());
+    assert_eq!(report.root().single, f32::INFINITY);
+    assert_eq!(report.root().relative_error(), 0.);
+}
@@ -0,0 +1,115 @@
+use rust_lazy::operation::{DynScalar, Environment, Program, Scalar};
+
+#[test]
+fn roots_share_common_subexpressions() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let sum = &x + &y;
+    let product = &sum * &x;
+    let roots = [sum.clone().into_dyn(), product.clone().into_dyn()];
+
+    let program = Program::compile(&roots);
+    assert_eq!(program.instructions().len(), 4);
+
+    let env: Environment = [("x", 2.), ("y", 3.)].into_iter().collect();
+    assert_eq!(
+        program.run_with(&env),
+        vec![sum.execute_with(&env), product.execute_with(&env)]
+    );
+}
+
+#[test]
+fn graphs_can_be_compiled_repeatedly() {
+    let x = Scalar::new(2.);
+    let res = &(&x * &x) + &x;
+
+    let first = Program::compile(&[res.clone().into_dyn()]);
+    let second = Program::compile(&[res.clone().into_dyn()]);
+    assert_eq!(first.run(), second.run());
+    assert_eq!(res.clone().compile().len(), res.compile().len());
+}
+
+#[test]
+fn constant_subtrees_fold_to_one_constant() {
+    let x = Scalar::variable("x");
+    let res = &(&Scalar::new(1.) + &Scalar::new(2.)) * &x;
+
+    let program = Program::compile(&[res.clone().into_dyn()]);
+    let folded = program.fold_constants();
+    assert_eq!(folded.instructions().len(), 3);
+
+    let env: Environment = [("x", 4.)].into_iter().collect();
+    assert_eq!(folded.run_with(&env), program.run_with(&env));
+}
+
+#[test]
+fn structurally_equal_nodes_are_computed_once() {
+    let x = Scalar::variable("x");
+    let left = &x + &Scalar::new(1.);
+    let right = &x + &Scalar::new(1.);
+    let res = &left * &right;
+
+    let instructions = res.clone().compile();
+    assert_eq!(instructions.len(), 4);
+
+    let program = Program::compile(&[left.into_dyn(), right.into_dyn()]);
+    assert_eq!(program.outputs()[0], program.outputs()[1]);
+}
+
+#[test]
+fn registers_are_reused_after_last_use() {
+    let mut res = Scalar::variable("x").into_dyn();
+    for i in 0..32 {
+        res = &res + &Scalar::new(i as f32).into_dyn();
+    }
+
+    let program = Program::compile(&[res.clone()]);
+    assert_eq!(program.register_count(), 2);
+
+    let env: Environment = [("x", 0.5)].into_iter().collect();
+    assert_eq!(program.run_with(&env), vec![res.execute_with(&env)]);
+}
+
+#[test]
+fn fixed_register_file_matches_vm() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let program = Program::compile(&[(&x * &y).into_dyn(), (&(&x + &y) / &x).into_dyn()]);
+
+    let env: Environment = [("x", 2.), ("y", 3.)].into_iter().collect();
+    let mut outputs = [0.; 2];
+    program.run_fixed::<4>(&env, &mut outputs);
+    assert_eq!(outputs.to_vec(), program.run_with(&env));
+}
+
+#[test]
+#[should_panic(expected = "registers")]
+fn fixed_register_file_must_be_large_enough() {
+    let x = Scalar::variable("x");
+    let program = Program::compile(&[(&x * &Scalar::new(2.)).into_dyn()]);
+    program.run_fixed::<1>(&Environment::new(), &mut [0.]);
+}
+
+#[test]
+fn wide_sums_compile_to_balanced_trees() {
+    let terms: Vec<DynScalar> = (0..10_000)
+        .map(|i| Scalar::variable(format!("x{}", i)).into_dyn())
+        .collect();
+    let sum = DynScalar::sum(&terms);
+    let program = Program::compile(std::slice::from_ref(&sum));
+    assert!(program.register_count() <= 14);
+
+    let env: Environment = (0..10_000).map(|i| (format!("x{}", i), 0.1)).collect();
+    let naive = (0..10_000).fold(0f32, |acc, _| acc + 0.1);
+    let exact = 1000.;
+    assert!((program.run_with(&env)[0] - exact).abs() < (naive - exact).abs());
+    assert_eq!(program.run_with(&env)[0], sum.execute_with(&env));
+}
+
+#[test]
+fn empty_sums_and_products() {
+    assert_eq!(DynScalar::sum(&[]).execute(), 0.);
+    assert_eq!(DynScalar::product(&[]).execute(), 1.);
+    let factors = [Scalar::new(2.).into_dyn(), Scalar::new(3.).into_dyn()];
+    assert_eq!(DynScalar::product(&factors).execute(), 6.);
+}
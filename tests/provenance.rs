@@ -0,0 +1,103 @@
+use rust_lazy::operation::{Environment, Program, Scalar};
+
+fn at(x: f32) -> Environment {
+    [("x", x)].into_iter().collect()
+}
+
+#[test]
+fn notes_are_transparent() {
+    let x = Scalar::variable("x");
+    let scaled = (&x * &Scalar::new(3.)).with_provenance("alice, R-17, 2026-03-01");
+    let expr = &scaled + &Scalar::new(1.);
+    assert_eq!(scaled.note(), "alice, R-17, 2026-03-01");
+    assert_eq!(
+        scaled.clone().into_dyn().provenance().as_deref(),
+        Some("alice, R-17, 2026-03-01")
+    );
+    assert_eq!(x.clone().into_dyn().provenance(), None);
+    assert_eq!(
+        expr.to_string(),
+        (&(&x * &Scalar::new(3.)) + &Scalar::new(1.)).to_string()
+    );
+    assert_eq!(expr.execute_with(&at(2.)), 7.);
+    assert_eq!(expr.derivative(&x).execute_with(&at(2.)), 3.);
+}
+
+#[test]
+fn notes_reach_the_program() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let product = (&x * &y).with_provenance("R-1");
+    let program = Program::compile(&[(&product + &x).into_dyn()]);
+    let noted: Vec<_> = program
+        .instructions()
+        .iter()
+        .filter_map(|instruction| instruction.provenance())
+        .collect();
+    assert_eq!(noted, ["R-1"]);
+    assert!(
+        program.to_string().contains("mul %0 %1  ; R-1"),
+        "{}",
+        program
+    );
+
+    let ir = program.to_llvm_ir().unwrap();
+    assert!(ir.contains("fmul float %x, %y, !provenance !0"), "{}", ir);
+    assert!(ir.ends_with("!0 = !{!\"R-1\"}\n"), "{}", ir);
+}
+
+#[test]
+fn merged_instructions_keep_every_note() {
+    let x = Scalar::variable("x");
+    let a = (&x + &Scalar::new(1.)).with_provenance("R-1");
+    let b = (&x + &Scalar::new(1.)).with_provenance("R-2");
+    let program = Program::compile(&[(&a * &b).into_dyn()]);
+    let noted: Vec<_> = program
+        .instructions()
+        .iter()
+        .filter_map(|instruction| instruction.provenance())
+        .collect();
+    assert_eq!(noted, ["R-1; R-2"]);
+}
+
+#[test]
+fn bytecode_keeps_notes() {
+    let x = Scalar::variable("x");
+    let program = Program::compile(&[(&x / &Scalar::new(4.)).with_provenance("bob").into_dyn()]);
+    let bytes = program.to_bytes();
+    assert_eq!(bytes[4], 5);
+    let loaded = Program::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded.to_string(), program.to_string());
+
+    let plain = Program::compile(&[(&x / &Scalar::new(4.)).into_dyn()]);
+    assert_eq!(plain.to_bytes()[4], 1);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn notes_survive_serde() {
+    use rust_lazy::operation::DynScalar;
+
+    let x = Scalar::variable("x");
+    let expr = (&(&x * &x).with_provenance("R-9") - &x).into_dyn();
+    let json = serde_json::to_string(&expr).unwrap();
+    let restored: DynScalar = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, expr);
+    assert_eq!(restored.children()[0].provenance().as_deref(), Some("R-9"));
+
+    let program = Program::compile(&[expr]);
+    let json = serde_json::to_string(&program).unwrap();
+    let restored: Program = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.to_string(), program.to_string());
+}
+
+#[test]
+fn notes_count_towards_program_size() {
+    let x = Scalar::variable("x");
+    let plain = Program::compile(&[(&x * &x).into_dyn()]);
+    let noted = Program::compile(&[(&x * &x).with_provenance("R-1").into_dyn()]);
+    assert_eq!(
+        noted.estimated_bytes(),
+        plain.estimated_bytes() + "R-1".len()
+    );
+}
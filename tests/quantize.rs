@@ -0,0 +1,87 @@
+use rust_lazy::operation::{
+    parse, Calibration, Environment, Program, QuantParams, QuantType, QuantizeError, Scalar,
+};
+
+fn uniform(program: &Program, min: f32, max: f32) -> Calibration {
+    Calibration::new(vec![(min, max); program.instructions().len()])
+}
+
+fn grid() -> Vec<Environment> {
+    let mut envs = Vec::new();
+    for i in 0..=8 {
+        for j in 0..=8 {
+            let (x, y) = (-1. + i as f32 / 4., j as f32 * 0.375);
+            envs.push([("x", x), ("y", y)].into_iter().collect());
+        }
+    }
+    envs
+}
+
+#[test]
+fn tracks_the_float_program() {
+    let program = Program::compile(&[
+        parse("(x + 2) * y - x / 4").unwrap(),
+        parse("(y + 1) / (x + 2)").unwrap(),
+    ]);
+    for (ty, tolerance) in [(QuantType::I16, 0.01), (QuantType::U8, 0.5)] {
+        let quantized = program.quantize(&uniform(&program, -10., 10.), ty).unwrap();
+        for env in grid() {
+            for (q, f) in quantized.run_with(&env).iter().zip(program.run_with(&env)) {
+                assert!((q - f).abs() < tolerance, "{:?}: {} vs {}", ty, q, f);
+            }
+        }
+    }
+}
+
+#[test]
+fn selects_and_compares() {
+    let program = Program::compile(&[parse("x < y").unwrap()]);
+    let x = Scalar::variable("x").into_dyn();
+    let select = Program::compile(&[x
+        .lt(&Scalar::new(0.).into_dyn())
+        .select(&(&x * &Scalar::new(-1.).into_dyn()), &x)]);
+    for env in grid() {
+        // 1 and 0 are each within half a step of what the result can hold.
+        let quantized = program.quantize(&uniform(&program, -4., 4.), QuantType::U8);
+        let less = quantized.unwrap().run_with(&env)[0];
+        assert!((less - program.run_with(&env)[0]).abs() < 4. / 255.);
+        let quantized = select.quantize(&uniform(&select, -4., 4.), QuantType::I16);
+        let abs = quantized.unwrap().run_with(&env)[0];
+        assert!((abs - env.get("x").unwrap().abs()).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn runs_on_integers() {
+    let program = Program::compile(&[parse("x * 3").unwrap()]);
+    let quantized = program
+        .quantize(&uniform(&program, -4., 4.), QuantType::I16)
+        .unwrap();
+    let params = QuantParams::from_range(-4., 4., QuantType::I16);
+    assert_eq!(quantized.input_params(), [params]);
+    let output = quantized.run_quantized(&[params.quantize(0.25, QuantType::I16)]);
+    assert!((quantized.output_params()[0].dequantize(output[0]) - 0.75).abs() < 1e-3);
+    // 6 is out of range and saturates.
+    let saturated = quantized.run_quantized(&[params.quantize(2., QuantType::I16)]);
+    assert_eq!(saturated, [i16::MAX as i32]);
+}
+
+#[test]
+fn rejects_noise_and_missing_ranges() {
+    let noisy = Program::compile(&[(&Scalar::variable("x")
+        + &Scalar::gaussian_noise(1., Some(1)))
+        .into_dyn()]);
+    assert!(matches!(
+        noisy.quantize(&uniform(&noisy, -1., 1.), QuantType::U8),
+        Err(QuantizeError::Unsupported(_))
+    ));
+    assert_eq!(
+        noisy
+            .quantize(&Calibration::new(vec![(0., 1.)]), QuantType::U8)
+            .unwrap_err(),
+        QuantizeError::RangeCount {
+            expected: 3,
+            found: 1
+        }
+    );
+}
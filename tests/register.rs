@@ -0,0 +1,126 @@
+use rust_lazy::operation::{
+    parse, CompileError, CompileOptions, DynScalar, Environment, LinearScan, Program,
+    RegisterAllocator, Sequential, Spilling,
+};
+
+#[test]
+fn allocator_stops_at_its_limit() {
+    let mut registers = RegisterAllocator::with_limit(2);
+    assert_eq!(registers.alloc(), Ok(0));
+    assert_eq!(registers.alloc(), Ok(1));
+    assert_eq!(
+        registers.alloc(),
+        Err(CompileError::OutOfRegisters { limit: 2 })
+    );
+    assert_eq!(registers.allocated(), 2);
+}
+
+#[test]
+fn compile_with_registers_checks_the_allocated_program() {
+    let expr = parse("a * b + c * d").unwrap();
+    let env = [("a", 1.), ("b", 2.), ("c", 3.), ("d", 4.)]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        Program::compile(std::slice::from_ref(&expr)).register_count(),
+        3
+    );
+
+    assert_eq!(
+        Program::compile_with_registers(std::slice::from_ref(&expr), 2).err(),
+        Some(CompileError::OutOfRegisters { limit: 2 })
+    );
+    let program = Program::compile_with_registers(std::slice::from_ref(&expr), 3).unwrap();
+    assert_eq!(program.run_with(&env), [14.]);
+
+    // A failed compile leaves the graph ready to compile again.
+    assert_eq!(Program::compile(&[expr]).run_with(&env), [14.]);
+}
+
+fn wide() -> (DynScalar, Environment) {
+    let expr = parse("(a * b + c * d) * (a - d) / ((b + c) * (a + 2) - d * (c - 1))").unwrap();
+    let env = [("a", 1.5), ("b", -2.), ("c", 3.25), ("d", 4.)]
+        .into_iter()
+        .collect();
+    (expr, env)
+}
+
+#[test]
+fn sequential_gives_every_value_its_own_register() {
+    let (expr, env) = wide();
+    let options = CompileOptions::new().with_strategy(Sequential);
+    let program = Program::compile_with(std::slice::from_ref(&expr), &options).unwrap();
+    assert_eq!(program.register_count(), program.instructions().len());
+    assert_eq!(program.run_with(&env), [expr.execute_with(&env)]);
+
+    let options = options.with_registers(4);
+    assert_eq!(
+        Program::compile_with(&[expr], &options).err(),
+        Some(CompileError::OutOfRegisters { limit: 4 })
+    );
+}
+
+#[test]
+fn linear_scan_is_the_default() {
+    let (expr, _) = wide();
+    let options = CompileOptions::new().with_strategy(LinearScan);
+    assert_eq!(
+        Program::compile_with(std::slice::from_ref(&expr), &options)
+            .unwrap()
+            .to_string(),
+        Program::compile(&[expr]).to_string()
+    );
+}
+
+#[test]
+fn spilling_fits_any_register_file() {
+    let (expr, env) = wide();
+    let expected = expr.execute_with(&env);
+    let needed = Program::compile(std::slice::from_ref(&expr)).register_count();
+    for registers in 2..=needed {
+        let options = CompileOptions::new()
+            .with_registers(registers)
+            .with_strategy(Spilling);
+        let program = Program::compile_with(std::slice::from_ref(&expr), &options).unwrap();
+        let text = program.to_string();
+        assert_eq!(text.contains("store"), registers < needed, "{}", text);
+        assert_eq!(program.run_with(&env), [expected], "{}", text);
+        // Everything but spill code stays within the register file.
+        for line in text.lines().filter(|line| !line.contains("store")) {
+            if let Some(ret) = line.split(':').next().and_then(|r| r.strip_prefix('%')) {
+                assert!(ret.parse::<usize>().unwrap() < registers, "{}", text);
+            }
+        }
+    }
+}
+
+#[test]
+fn spilling_keeps_every_output() {
+    let (expr, env) = wide();
+    let roots = [
+        expr.clone(),
+        parse("a * d").unwrap(),
+        parse("b - c").unwrap(),
+    ];
+    let options = CompileOptions::new()
+        .with_registers(2)
+        .with_strategy(Spilling);
+    let program = Program::compile_with(&roots, &options).unwrap();
+    assert_eq!(program.run_with(&env), [expr.execute_with(&env), 6., -5.25]);
+    let bytes = program.to_bytes();
+    assert_eq!(&bytes[..5], b"LZBC\x02");
+    let reloaded = Program::from_bytes(&bytes).unwrap();
+    assert_eq!(reloaded.run_with(&env), program.run_with(&env));
+}
+
+#[test]
+fn spilling_needs_two_registers() {
+    let (expr, _) = wide();
+    let options = CompileOptions::new()
+        .with_registers(1)
+        .with_strategy(Spilling);
+    assert_eq!(
+        Program::compile_with(&[expr], &options).err(),
+        Some(CompileError::OutOfRegisters { limit: 1 })
+    );
+}
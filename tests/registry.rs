@@ -0,0 +1,200 @@
+use std::sync::{Arc, Barrier};
+
+use rust_lazy::operation::{Environment, Registry, RegistryError, Scalar, SwapOptions};
+
+fn at(x: f32, y: f32) -> Environment {
+    [("x", x), ("y", y)].into_iter().collect()
+}
+
+fn registry() -> Registry {
+    let registry = Registry::new();
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    registry.register("area", &(&x * &y) + &(&x * &y)).unwrap();
+    registry
+}
+
+#[test]
+fn swaps_an_equivalent_formula() {
+    let registry = registry();
+    let evaluator = registry.evaluator("area").unwrap();
+    let before = evaluator.program();
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    registry
+        .hot_swap("area", &(&x * &y) * &Scalar::new(2.))
+        .unwrap();
+    assert_eq!(evaluator.run_with(&at(3., 4.)), vec![24.]);
+    assert!(!Arc::ptr_eq(&before, &evaluator.program()));
+    assert_eq!(before.run_with(&at(3., 4.)), vec![24.]);
+}
+
+#[test]
+fn rejects_different_variables() {
+    let registry = registry();
+    let (x, z) = (Scalar::variable("x"), Scalar::variable("z"));
+    let error = registry.hot_swap("area", &x * &z).unwrap_err();
+    match error {
+        RegistryError::Variables { expected, found } => {
+            assert_eq!(expected, ["x", "y"]);
+            assert_eq!(found, ["x", "z"]);
+        }
+        error => panic!("unexpected {}", error),
+    }
+}
+
+#[test]
+fn rejects_nonconforming_formula_unless_forced() {
+    let registry = registry();
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    let changed = &(&x * &y) * &Scalar::new(3.);
+    let error = registry.hot_swap("area", changed.clone()).unwrap_err();
+    assert!(matches!(error, RegistryError::Nonconforming(ref report) if report.max > 4));
+    assert_eq!(
+        registry.get("area").unwrap().run_with(&at(1., 1.)),
+        vec![2.]
+    );
+
+    registry
+        .hot_swap_with("area", changed, &SwapOptions::new().with_force(true))
+        .unwrap();
+    assert_eq!(
+        registry.get("area").unwrap().run_with(&at(1., 1.)),
+        vec![3.]
+    );
+}
+
+#[test]
+fn checks_explicit_inputs() {
+    let registry = registry();
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    // Differs only where x is 0, which sampling does not hit.
+    let patched = x
+        .eq(&Scalar::new(0.))
+        .select(&Scalar::new(1.), &(&(&x * &y) * &Scalar::new(2.)));
+    let options = SwapOptions::new().with_inputs(vec![at(0., 5.)]);
+    assert!(registry
+        .hot_swap_with("area", patched.clone(), &options)
+        .is_err());
+    assert!(registry.hot_swap("area", patched).is_ok());
+}
+
+#[test]
+fn unknown_and_duplicate_names() {
+    let registry = registry();
+    let x = Scalar::variable("x");
+    assert!(matches!(
+        registry.hot_swap("volume", x.clone()),
+        Err(RegistryError::Unknown(_))
+    ));
+    assert!(matches!(
+        registry.register("area", x),
+        Err(RegistryError::Registered(_))
+    ));
+    assert_eq!(registry.names(), ["area"]);
+}
+
+#[test]
+fn running_evaluators_pick_up_swaps() {
+    let registry = Arc::new(registry());
+    let before = registry.get("area").unwrap();
+    let barrier = Arc::new(Barrier::new(2));
+    let worker = {
+        let evaluator = registry.evaluator("area").unwrap();
+        let barrier = barrier.clone();
+        std::thread::spawn(move || {
+            let pinned = evaluator.program();
+            barrier.wait();
+            barrier.wait();
+            (pinned, evaluator.program())
+        })
+    };
+    barrier.wait();
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    let sum = &(&x * &y) + &(&y * &x);
+    registry.hot_swap("area", sum).unwrap();
+    barrier.wait();
+    let (pinned, current) = worker.join().unwrap();
+    assert!(Arc::ptr_eq(&before, &pinned));
+    assert!(Arc::ptr_eq(&current, &registry.get("area").unwrap()));
+    assert!(!Arc::ptr_eq(&before, &current));
+    assert_eq!(pinned.run_with(&at(2., 5.)), current.run_with(&at(2., 5.)));
+}
+
+#[test]
+fn swaps_add_versions() {
+    let registry = registry();
+    assert_eq!(registry.version("area"), Some(1));
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    registry
+        .hot_swap("area", &(&x * &y) * &Scalar::new(2.))
+        .unwrap();
+    assert!(registry.hot_swap("area", &x * &y).is_err());
+    assert_eq!(registry.version("area"), Some(2));
+    let first = registry.get_version("area", 1).unwrap();
+    assert_eq!(first.run_with(&at(3., 4.)), vec![24.]);
+    assert!(Arc::ptr_eq(
+        &registry.get_version("area", 2).unwrap(),
+        &registry.get("area").unwrap()
+    ));
+    assert!(registry.get_version("area", 0).is_none());
+    assert!(registry.get_version("area", 3).is_none());
+    assert_eq!(registry.version("volume"), None);
+}
+
+#[test]
+fn keeps_metadata() {
+    let registry = registry();
+    registry.set_metadata("area", "owner", "geometry").unwrap();
+    registry.set_metadata("area", "units", "m^2").unwrap();
+    registry.set_metadata("area", "owner", "physics").unwrap();
+    let metadata = registry.metadata("area").unwrap();
+    assert_eq!(
+        metadata.into_iter().collect::<Vec<_>>(),
+        [
+            ("owner".to_string(), "physics".to_string()),
+            ("units".to_string(), "m^2".to_string())
+        ]
+    );
+    assert!(matches!(
+        registry.set_metadata("volume", "owner", "geometry"),
+        Err(RegistryError::Unknown(_))
+    ));
+}
+
+#[test]
+fn saves_and_loads() {
+    let dir = std::env::temp_dir().join(format!("rust_lazy-registry-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("registry");
+    let registry = registry();
+    let (x, y) = (Scalar::variable("x"), Scalar::variable("y"));
+    registry
+        .hot_swap("area", &(&x * &y) * &Scalar::new(2.))
+        .unwrap();
+    registry.set_metadata("area", "units", "m^2").unwrap();
+    registry.register("sum", &x + &y).unwrap();
+    registry.save(&path).unwrap();
+
+    let loaded = Registry::load(&path).unwrap();
+    assert_eq!(loaded.names(), ["area", "sum"]);
+    assert_eq!(loaded.version("area"), Some(2));
+    assert_eq!(loaded.metadata("area"), registry.metadata("area"));
+    assert!(loaded.metadata("sum").unwrap().is_empty());
+    for version in 1..=2 {
+        assert_eq!(
+            loaded.get_version("area", version).unwrap().to_bytes(),
+            registry.get_version("area", version).unwrap().to_bytes()
+        );
+    }
+    assert_eq!(
+        loaded.evaluator("sum").unwrap().run_with(&at(3., 4.)),
+        vec![7.]
+    );
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    std::fs::write(&path, &bytes).unwrap();
+    let error = Registry::load(&path).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
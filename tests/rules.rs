@@ -0,0 +1,74 @@
+use rust_lazy::operation::{
+    rules::{Decision, Rule, RuleSet},
+    Environment, Scalar,
+};
+
+fn applicant(income: f32, debt: f32) -> Environment {
+    [("income", income), ("debt", debt)].into_iter().collect()
+}
+
+fn limits() -> RuleSet {
+    let income = Scalar::variable("income");
+    let debt = Scalar::variable("debt");
+    RuleSet::new(Scalar::new(0.))
+        .with_rule(Rule::new(
+            "overextended",
+            debt.gt(&(&income * &Scalar::new(0.5))),
+            Scalar::new(0.),
+        ))
+        .with_rule(Rule::new(
+            "high-income",
+            income.ge(&Scalar::new(100.)),
+            &income * &Scalar::new(2.),
+        ))
+        .with_rule(Rule::new(
+            "standard",
+            income.gt(&Scalar::new(0.)),
+            &income - &debt,
+        ))
+}
+
+#[test]
+fn first_rule_that_holds_decides() {
+    let rules = limits();
+    assert_eq!(
+        rules.evaluate(&applicant(150., 10.)),
+        Decision {
+            value: 300.,
+            applied: Some("high-income".to_string()),
+            fired: vec!["high-income".to_string(), "standard".to_string()],
+        }
+    );
+    let decision = rules.evaluate(&applicant(150., 100.));
+    assert_eq!(decision.value, 0.);
+    assert_eq!(decision.applied.as_deref(), Some("overextended"));
+    assert_eq!(decision.fired, ["overextended", "high-income", "standard"]);
+
+    assert_eq!(rules.evaluate(&applicant(40., 5.)).value, 35.);
+}
+
+#[test]
+fn default_when_nothing_fires() {
+    let decision = limits().evaluate(&applicant(0., 0.));
+    assert_eq!(decision.value, 0.);
+    assert_eq!(decision.applied, None);
+    assert!(decision.fired.is_empty());
+}
+
+#[test]
+fn rule_sets_are_expressions() {
+    let rules = limits();
+    for (income, debt) in [(150., 10.), (150., 100.), (40., 5.), (0., 0.)] {
+        let env = applicant(income, debt);
+        assert_eq!(rules.expr().execute_with(&env), rules.evaluate(&env).value);
+    }
+    let program = rules.compile();
+    assert_eq!(program.outputs().len(), 4);
+    let notes: Vec<_> = program
+        .instructions()
+        .iter()
+        .filter_map(|instruction| instruction.provenance())
+        .collect();
+    assert!(notes.contains(&"rule high-income"), "{}", program);
+    assert!(notes.contains(&"rule standard"), "{}", program);
+}
@@ -0,0 +1,48 @@
+use rust_lazy::operation::{parse, Environment, Program, Scalar};
+
+#[test]
+fn renders_rust_source() {
+    let program = Program::compile(&[parse("(x + 2) * y - x").unwrap()]);
+    assert_eq!(program.variables(), ["x", "y"]);
+    assert_eq!(
+        program.to_rust().unwrap(),
+        "pub fn eval(x: f32, y: f32) -> f32 {
+    let t0 = x;
+    let t1 = 2.0_f32;
+    let t1 = t0 + t1;
+    let t2 = y;
+    let t1 = t1 * t2;
+    let t0 = t1 - t0;
+    t0
+}
+"
+    );
+}
+
+#[test]
+fn several_outputs_are_returned_as_an_array() {
+    let x = Scalar::variable("x");
+    let program = Program::compile(&[
+        (&x * &x).into_dyn(),
+        (&x - &Scalar::new(f32::NAN)).into_dyn(),
+    ]);
+    let source = program.to_rust().unwrap();
+    assert!(source.starts_with("pub fn eval(x: f32) -> [f32; 2] {"));
+    assert!(source.contains("f32::from_bits(0x7fc00000)"));
+}
+
+#[test]
+fn closure_matches_execute() {
+    let expr = parse("(a + 2.5) * b / (c - 1) - a * a").unwrap();
+    let closure = Program::compile(std::slice::from_ref(&expr)).to_closure();
+    let env: Environment = [("a", 1.5), ("b", 4.), ("c", 3.)].into_iter().collect();
+    assert_eq!(closure(&[1.5, 4., 3.]), expr.execute_with(&env));
+}
+
+#[test]
+fn closure_samples_noise() {
+    let expr = &Scalar::new(1.) + &Scalar::gaussian_noise(1., Some(11));
+    let closure = Program::compile(&[expr.clone().into_dyn()]).to_closure();
+    assert_eq!(closure(&[]), expr.execute());
+    assert_ne!(closure(&[]), closure(&[]));
+}
@@ -0,0 +1,67 @@
+use rust_lazy::operation::{DynScalar, Environment, Program, Scalar};
+
+fn piecewise() -> DynScalar {
+    let flag = Scalar::variable("flag");
+    let x = Scalar::variable("x");
+    flag.select(&(&x * &x), &(&x + &Scalar::new(1.))).into()
+}
+
+fn env(flag: f32, x: f32) -> Environment {
+    [("flag", flag), ("x", x)].into_iter().collect()
+}
+
+#[test]
+fn picks_an_arm_on_the_condition() {
+    let expr = piecewise();
+    assert_eq!(expr.execute_with(&env(1., 3.)), 9.);
+    assert_eq!(expr.execute_with(&env(-2., 3.)), 9.);
+    assert_eq!(expr.execute_with(&env(0., 3.)), 4.);
+    assert_eq!(expr.execute_with(&env(-0., 3.)), 4.);
+    assert_eq!(expr.execute_with(&env(f32::NAN, 3.)), 9.);
+    assert_eq!(expr.to_string(), "select(flag, (x * x), (x + 1))");
+}
+
+#[test]
+fn execute_skips_the_other_arm() {
+    let x = Scalar::variable("x");
+    let lazy = Scalar::new(1.).select(&x, &Scalar::wildcard("never"));
+    assert_eq!(lazy.execute_with(&[("x", 2.)].into_iter().collect()), 2.);
+}
+
+#[test]
+fn derivative_follows_the_chosen_arm() {
+    let expr = piecewise();
+    assert_eq!(expr.execute_dual(&env(1., 3.), "x").derivative, 6.);
+    assert_eq!(expr.execute_dual(&env(0., 3.), "x").derivative, 1.);
+}
+
+#[test]
+fn compiled_forms_agree() {
+    let expr = piecewise();
+    let program = Program::compile(std::slice::from_ref(&expr));
+    let closure = program.to_closure();
+    let frozen = expr.freeze();
+    let mut memo = expr.memoize();
+    let reloaded = Program::from_bytes(&program.to_bytes()).unwrap();
+    for flag in [1., 0., -0., f32::NAN, -4.5] {
+        let env = env(flag, 3.);
+        let expected = expr.execute_with(&env);
+        assert_eq!(program.run_with(&env), [expected]);
+        assert_eq!(reloaded.run_with(&env), [expected]);
+        assert_eq!(closure(&[flag, 3.]), expected);
+        assert_eq!(frozen.call(&[flag, 3.]), expected);
+        assert_eq!(memo.execute_with(&env), expected);
+    }
+}
+
+#[test]
+fn renders_in_every_backend() {
+    let program = Program::compile(&[piecewise()]);
+    assert!(program.to_c().unwrap().contains("!= 0.0f ? t"));
+    assert!(program.to_rust().unwrap().contains("if t0 != 0.0 { t"));
+    assert!(program.to_wgsl().unwrap().contains("= select(t"));
+    assert!(program.to_glsl().unwrap().contains("!= 0.0 ? t"));
+    let ir = program.to_llvm_ir().unwrap();
+    assert!(ir.contains("fcmp une float %flag, 0.0"), "{}", ir);
+    assert!(ir.contains("select i1"), "{}", ir);
+}
@@ -0,0 +1,240 @@
+// Every operation against every combination of awkward inputs, on every way
+// of running an expression. The semantics are IEEE 754 single precision:
+// arithmetic keeps signed zeros, infinities and subnormals, and any NaN
+// operand gives NaN. Comparisons are ordered, so they fail on NaN except
+// for !=, and -0 equals 0. Select takes its first branch for any condition
+// that does not equal zero, NaN included. A new op or backend belongs here.
+
+use rust_lazy::operation::{Comparison, DynScalar, Environment, Program, Scalar, WasmModule};
+use wasmi::{core::F32, Engine, Linker, Module, Store, Val};
+
+fn values() -> Vec<f32> {
+    vec![
+        0.,
+        -0.,
+        1.,
+        -1.,
+        2.5,
+        f32::MAX,
+        -f32::MAX,
+        f32::MIN_POSITIVE,
+        f32::from_bits(1),
+        -f32::MIN_POSITIVE / 4.,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+    ]
+}
+
+type Build = Box<dyn Fn(&[DynScalar]) -> DynScalar>;
+type Evaluate = Box<dyn FnMut(&[f32]) -> f32>;
+
+struct Case {
+    name: String,
+    build: Build,
+    expected: Evaluate,
+    arity: usize,
+}
+
+fn binary(
+    name: &str,
+    build: impl Fn(&DynScalar, &DynScalar) -> DynScalar + 'static,
+    expected: impl Fn(f32, f32) -> f32 + 'static,
+) -> Case {
+    Case {
+        name: name.to_string(),
+        build: Box::new(move |v| build(&v[0], &v[1])),
+        expected: Box::new(move |v| expected(v[0], v[1])),
+        arity: 2,
+    }
+}
+
+fn cases() -> Vec<Case> {
+    let mut cases = vec![
+        binary("add", |a, b| a + b, |a, b| a + b),
+        binary("sub", |a, b| a - b, |a, b| a - b),
+        binary("mul", |a, b| a * b, |a, b| a * b),
+        binary("div", |a, b| a / b, |a, b| a / b),
+        Case {
+            name: "select".to_string(),
+            build: Box::new(|v| v[0].select(&v[1], &v[2])),
+            expected: Box::new(|v| if v[0] != 0. { v[1] } else { v[2] }),
+            arity: 3,
+        },
+    ];
+    for comparison in [
+        Comparison::Eq,
+        Comparison::Ne,
+        Comparison::Lt,
+        Comparison::Le,
+        Comparison::Gt,
+        Comparison::Ge,
+    ] {
+        cases.push(binary(
+            comparison.name(),
+            move |a, b| a.compare(comparison, b),
+            move |a, b| {
+                let holds = match comparison {
+                    Comparison::Eq => a == b,
+                    Comparison::Ne => a != b,
+                    Comparison::Lt => a < b,
+                    Comparison::Le => a <= b,
+                    Comparison::Gt => a > b,
+                    Comparison::Ge => a >= b,
+                };
+                if holds {
+                    1.
+                } else {
+                    0.
+                }
+            },
+        ));
+    }
+    cases
+}
+
+const NAMES: [&str; 3] = ["a", "b", "c"];
+
+fn inputs(arity: usize) -> Vec<Vec<f32>> {
+    let mut inputs = vec![Vec::new()];
+    for _ in 0..arity {
+        inputs = inputs
+            .into_iter()
+            .flat_map(|input| {
+                values().into_iter().map(move |value| {
+                    let mut input = input.clone();
+                    input.push(value);
+                    input
+                })
+            })
+            .collect();
+    }
+    inputs
+}
+
+// NaNs need not agree on their payload, everything else to the bit.
+fn agrees(actual: f32, expected: f32) -> bool {
+    (actual.is_nan() && expected.is_nan()) || actual.to_bits() == expected.to_bits()
+}
+
+// Runs `run` on every input of every case and reports each disagreement
+// with the expected semantics.
+fn check(path: &str, mut run: impl FnMut(&DynScalar, &Program) -> Evaluate) {
+    let mut failures = Vec::new();
+    for mut case in cases() {
+        let variables: Vec<DynScalar> = NAMES[..case.arity]
+            .iter()
+            .map(|name| Scalar::variable(*name).into_dyn())
+            .collect();
+        let expr = (case.build)(&variables);
+        let program = Program::compile(std::slice::from_ref(&expr));
+        assert_eq!(program.variables(), NAMES[..case.arity]);
+        let mut evaluate = run(&expr, &program);
+        for input in inputs(case.arity) {
+            let (actual, expected) = (evaluate(&input), (case.expected)(&input));
+            if !agrees(actual, expected) {
+                failures.push(format!(
+                    "{} {:?}: got {}, expected {}",
+                    case.name, input, actual, expected
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}:\n{}", path, failures.join("\n"));
+}
+
+fn environment(input: &[f32]) -> Environment {
+    NAMES.iter().copied().zip(input.iter().copied()).collect()
+}
+
+#[test]
+fn execute() {
+    check("execute", |expr, _| {
+        let expr = expr.clone();
+        Box::new(move |input| expr.execute_with(&environment(input)))
+    });
+}
+
+#[test]
+fn frozen() {
+    check("frozen", |expr, _| {
+        let frozen = expr.freeze();
+        Box::new(move |input| frozen.call(input))
+    });
+}
+
+#[test]
+fn memoized() {
+    check("memoized", |expr, _| {
+        let mut memo = expr.memoize();
+        Box::new(move |input| memo.execute_with(&environment(input)))
+    });
+}
+
+#[test]
+fn interpreter() {
+    check("interpreter", |_, program| {
+        let program = program.clone();
+        Box::new(move |input| program.run_with(&environment(input))[0])
+    });
+}
+
+#[test]
+fn fixed_registers() {
+    check("fixed registers", |_, program| {
+        let program = program.clone();
+        Box::new(move |input| {
+            let mut output = [0.];
+            program.run_fixed::<4>(&environment(input), &mut output);
+            output[0]
+        })
+    });
+}
+
+#[test]
+fn closure() {
+    check("closure", |_, program| {
+        let closure = program.to_closure();
+        Box::new(move |input| closure(input))
+    });
+}
+
+#[test]
+fn wasm() {
+    check("wasm", |_, program| {
+        let module = WasmModule::compile(program.instructions()).unwrap();
+        let engine = Engine::default();
+        let module = Module::new(&engine, module.bytes()).unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = Linker::<()>::new(&engine)
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        let eval = instance.get_func(&store, "eval").unwrap();
+        Box::new(move |input| {
+            let args: Vec<Val> = input.iter().map(|&v| Val::F32(F32::from(v))).collect();
+            let mut result = [Val::F32(F32::from(0.))];
+            eval.call(&mut store, &args, &mut result).unwrap();
+            result[0].f32().unwrap().into()
+        })
+    });
+}
+
+#[cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+#[test]
+fn jit() {
+    check("jit", |_, program| {
+        let jit = rust_lazy::operation::Jit::compile(program.instructions()).unwrap();
+        Box::new(move |input| jit.call(input))
+    });
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel() {
+    check("parallel", |expr, _| {
+        let frozen = expr.freeze();
+        Box::new(move |input| frozen.par_call(input, 0))
+    });
+}
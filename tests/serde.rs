@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+
+use rust_lazy::operation::{DynScalar, Environment, Instruction, Program, Scalar};
+
+#[test]
+fn graph_round_trip() {
+    let x = Scalar::variable("x");
+    let shared = &(&x * &Scalar::new(2.)) + &Scalar::pi();
+    let graph = &(&shared / &shared) - &Scalar::new(1.5);
+    let graph = graph.into_dyn();
+
+    let json = serde_json::to_string(&graph).unwrap();
+    let restored: DynScalar = serde_json::from_str(&json).unwrap();
+
+    let env: Environment = [("x", 3.)].into_iter().collect();
+    assert_eq!(restored.execute_with(&env), graph.execute_with(&env));
+    assert_eq!(restored.pretty(), graph.pretty());
+}
+
+#[test]
+fn malformed_graphs_are_rejected() {
+    assert!(serde_json::from_str::<DynScalar>("[]").is_err());
+    assert!(serde_json::from_str::<DynScalar>(r#"[{"kind":"Add","operands":[0]}]"#).is_err());
+    assert!(serde_json::from_str::<DynScalar>(
+        r#"[{"kind":{"Constant":1.0},"operands":[]},{"kind":"Add","operands":[0,2]}]"#
+    )
+    .is_err());
+}
+
+#[test]
+fn instructions_round_trip() {
+    let graph = &Scalar::new(4.) / &(&Scalar::variable("y") - &Scalar::laplace_noise(1., Some(7)));
+    let instructions = graph.compile();
+
+    let json = serde_json::to_string(&instructions).unwrap();
+    let restored: Vec<Instruction> = serde_json::from_str(&json).unwrap();
+
+    let render = |instrs: &[Instruction]| instrs.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(render(&restored), render(&instructions));
+}
+
+#[test]
+fn program_round_trip() {
+    let a = Scalar::variable("a");
+    let program = Program::compile(&[(&a + &a).into_dyn(), (&a * &a).into_dyn()]);
+
+    let json = serde_json::to_string(&program).unwrap();
+    let restored: Program = serde_json::from_str(&json).unwrap();
+
+    let env: Environment = [("a", 5.)].into_iter().collect();
+    assert_eq!(restored.run_with(&env), program.run_with(&env));
+}
+
+#[test]
+fn malformed_programs_are_rejected() {
+    let error = serde_json::from_str::<Program>(
+        r#"{"instructions":[{"op":{"Add":[5,7]},"ret":0}],"outputs":[0]}"#,
+    )
+    .err()
+    .unwrap();
+    assert!(error
+        .to_string()
+        .contains("instruction 0 reads %5 before it is written"));
+    assert!(serde_json::from_str::<Program>(
+        r#"{"instructions":[{"op":{"Constant":1.0},"ret":0}],"outputs":[1]}"#
+    )
+    .is_err());
+}
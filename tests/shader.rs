@@ -0,0 +1,65 @@
+use rust_lazy::operation::{parse, Program, Scalar};
+
+#[test]
+fn renders_wgsl_and_glsl() {
+    let program = Program::compile(&[parse("(x + 2) * y - x").unwrap()]);
+    assert_eq!(
+        program.to_wgsl().unwrap(),
+        "fn eval(x: f32, y: f32) -> f32 {
+    var t0 = x;
+    var t1 = 2.0;
+    t1 = t0 + t1;
+    var t2 = y;
+    t1 = t1 * t2;
+    t0 = t1 - t0;
+    return t0;
+}
+"
+    );
+    assert_eq!(
+        program.to_glsl().unwrap(),
+        "float eval(float x, float y) {
+    float t0 = x;
+    float t1 = 2.0;
+    t1 = t0 + t1;
+    float t2 = y;
+    t1 = t1 * t2;
+    t0 = t1 - t0;
+    return t0;
+}
+"
+    );
+}
+
+#[test]
+fn several_outputs_are_returned_as_an_array() {
+    let x = Scalar::variable("x");
+    let program = Program::compile(&[(&x * &x).into_dyn(), (&x / &Scalar::new(0.)).into_dyn()]);
+    let wgsl = program.to_wgsl().unwrap();
+    assert!(wgsl.starts_with("fn eval(x: f32) -> array<f32, 2> {"));
+    assert!(wgsl.contains("return array<f32, 2>("));
+    let glsl = program.to_glsl().unwrap();
+    assert!(glsl.starts_with("float[2] eval(float x) {"));
+    assert!(glsl.contains("return float[2]("));
+}
+
+#[test]
+fn special_values_are_bit_casts() {
+    let program = Program::compile(&[Scalar::new(f32::INFINITY).into_dyn()]);
+    assert!(program
+        .to_wgsl()
+        .unwrap()
+        .contains("bitcast<f32>(0x7f800000u)"));
+    assert!(program
+        .to_glsl()
+        .unwrap()
+        .contains("uintBitsToFloat(0x7f800000u)"));
+}
+
+#[test]
+fn rejects_noise_and_clashing_names() {
+    let noisy = &Scalar::new(1.) + &Scalar::gaussian_noise(1., None);
+    assert!(Program::compile(&[noisy.into_dyn()]).to_wgsl().is_err());
+    let clash = Program::compile(&[Scalar::variable("t0").into_dyn()]);
+    assert!(clash.to_glsl().is_err());
+}
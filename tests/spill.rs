@@ -0,0 +1,88 @@
+use rust_lazy::operation::{
+    parse, CompileError, CompileOptions, DynScalar, Environment, Program, Scalar, Spilling,
+};
+
+fn large() -> (DynScalar, Environment) {
+    let terms: Vec<DynScalar> = (0..40)
+        .map(|i| {
+            let x = Scalar::variable(format!("x{}", i % 7));
+            (&(&x * &Scalar::new(i as f32 + 0.5)) - &Scalar::variable("y")).into()
+        })
+        .collect();
+    let products: Vec<DynScalar> = terms.chunks(5).map(DynScalar::product).collect();
+    let env = (0..7)
+        .map(|i| (format!("x{}", i), i as f32 * 0.125 - 0.25))
+        .chain([("y".to_string(), 0.75)])
+        .collect();
+    (DynScalar::sum(&products), env)
+}
+
+#[test]
+fn spilled_programs_run_in_a_small_register_file() {
+    let (expr, env) = large();
+    let expected = expr.execute_with(&env);
+    let options = CompileOptions::new()
+        .with_registers(3)
+        .with_strategy(Spilling);
+    let program = Program::compile_with(&[expr], &options).unwrap();
+    assert_eq!(program.register_file(), 3);
+    assert!(program.memory_cells() > 0);
+
+    let mut memory = vec![0.; program.memory_cells()];
+    let mut outputs = [0.];
+    program.run_fixed_with_memory::<3>(&env, &mut memory, &mut outputs);
+    assert_eq!(outputs, [expected]);
+    assert_eq!(program.run_with(&env), [expected]);
+}
+
+#[test]
+fn spill_pass_fits_compiled_programs() {
+    let (expr, env) = large();
+    let program = Program::compile(std::slice::from_ref(&expr));
+    let loaded = Program::from_bytes(&program.to_bytes()).unwrap();
+    assert!(loaded.register_file() > 4);
+    assert_eq!(loaded.memory_cells(), 0);
+
+    let spilled = loaded.spill(4).unwrap();
+    assert_eq!(spilled.register_file(), 4);
+    let mut memory = vec![0.; spilled.memory_cells()];
+    let mut outputs = [0.];
+    spilled.run_fixed_with_memory::<4>(&env, &mut memory, &mut outputs);
+    assert_eq!(outputs, [expr.execute_with(&env)]);
+    assert_eq!(
+        spilled.fold_constants().run_with(&env),
+        [expr.execute_with(&env)]
+    );
+}
+
+#[test]
+fn outputs_may_live_in_memory() {
+    let program = Program::compile(&[
+        parse("a * b").unwrap(),
+        parse("a + b").unwrap(),
+        parse("a - b").unwrap(),
+    ]);
+    let spilled = program.spill(2).unwrap();
+    let env = [("a", 6.), ("b", 2.)].into_iter().collect();
+    let mut memory = vec![0.; spilled.memory_cells()];
+    let mut outputs = [0.; 3];
+    spilled.run_fixed_with_memory::<2>(&env, &mut memory, &mut outputs);
+    assert_eq!(outputs, [12., 8., 4.]);
+}
+
+#[test]
+fn too_few_registers() {
+    let program = Program::compile(&[parse("a * b").unwrap()]);
+    assert_eq!(
+        program.spill(1).err(),
+        Some(CompileError::OutOfRegisters { limit: 1 })
+    );
+}
+
+#[test]
+#[should_panic(expected = "memory cells")]
+fn memory_must_be_large_enough() {
+    let (expr, env) = large();
+    let program = Program::compile(&[expr]).spill(3).unwrap();
+    program.run_fixed::<3>(&env, &mut [0.]);
+}
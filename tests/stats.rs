@@ -0,0 +1,44 @@
+use rust_lazy::operation::{parse, ExecutionStats, Program, StatsError};
+
+fn program(source: &str) -> Program {
+    Program::compile(&[parse(source).unwrap()])
+}
+
+#[test]
+fn fingerprints_follow_the_program() {
+    assert_eq!(
+        program("x * 2 + 1").fingerprint(),
+        program("x * 2 + 1").fingerprint()
+    );
+    assert_ne!(
+        program("x * 2 + 1").fingerprint(),
+        program("x * 2 - 1").fingerprint()
+    );
+}
+
+#[test]
+fn round_trips_through_text() {
+    let (hot, cold) = (program("a / b"), program("a - b"));
+    let mut stats = ExecutionStats::new();
+    stats.set_runs(&hot, 5000);
+    stats.set_runs(&cold, 3);
+
+    let restored: ExecutionStats = stats.to_string().parse().unwrap();
+    assert_eq!(restored, stats);
+    assert_eq!(restored.runs(&hot), 5000);
+    assert_eq!(restored.runs(&program("a * b")), 0);
+
+    let mut merged = restored.clone();
+    merged.merge(&stats);
+    assert_eq!(merged.runs(&hot), 10000);
+    assert_eq!(merged.len(), 2);
+}
+
+#[test]
+fn rejects_malformed_lines() {
+    assert_eq!(
+        "00000000000000ff 3\n\nnot stats\n".parse::<ExecutionStats>(),
+        Err(StatsError { line: 3 })
+    );
+    assert!("".parse::<ExecutionStats>().unwrap().is_empty());
+}
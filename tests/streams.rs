@@ -0,0 +1,73 @@
+use rust_lazy::operation::{DynScalar, Environment, Program, Scalar};
+
+fn samples(program: &Program, seed: u64, output: usize) -> Vec<f32> {
+    let mut context = program.context_with_seed(seed);
+    (0..8)
+        .map(|_| program.run_in(&mut context, &Environment::new())[output])
+        .collect()
+}
+
+#[test]
+fn unrelated_nodes_leave_streams_alone() {
+    let noise: DynScalar = Scalar::gaussian_noise(1., Some(1)).into();
+    let alone = Program::compile(std::slice::from_ref(&noise));
+    let other = &Scalar::laplace_noise(2., Some(2)) * &Scalar::new(3.);
+    let crowded = Program::compile(&[other.into_dyn(), noise.clone()]);
+
+    assert_eq!(samples(&alone, 99, 0), samples(&crowded, 99, 1));
+    let frozen = noise.freeze();
+    let mut context = frozen.context_with_seed(99);
+    let called: Vec<f32> = (0..8).map(|_| frozen.call_in(&mut context, &[])).collect();
+    assert_eq!(samples(&alone, 99, 0), called);
+}
+
+#[test]
+fn program_seed_picks_the_streams() {
+    let program = Program::compile(&[Scalar::gaussian_noise(1., Some(1)).into_dyn()]);
+    assert_eq!(samples(&program, 5, 0), samples(&program, 5, 0));
+    assert_ne!(samples(&program, 5, 0), samples(&program, 6, 0));
+    assert_ne!(samples(&program, 5, 0), {
+        let mut context = program.context();
+        (0..8)
+            .map(|_| program.run_in(&mut context, &Environment::new())[0])
+            .collect::<Vec<_>>()
+    });
+}
+
+#[test]
+fn unseeded_noise_is_reproducible_under_a_program_seed() {
+    let program = Program::compile(&[
+        Scalar::gaussian_noise(1., None).into_dyn(),
+        Scalar::laplace_noise(1., None).into_dyn(),
+    ]);
+    assert_eq!(samples(&program, 7, 0), samples(&program, 7, 0));
+    assert_eq!(samples(&program, 7, 1), samples(&program, 7, 1));
+}
+
+#[test]
+fn unrelated_unseeded_noise_leaves_streams_alone() {
+    let x = Scalar::variable("x");
+    let noisy = (&x * &Scalar::gaussian_noise(1., None)).into_dyn();
+    let alone = Program::compile(std::slice::from_ref(&noisy));
+    // A new source that runs before the existing one.
+    let other = Scalar::laplace_noise(1., None).into_dyn();
+    let crowded = Program::compile(&[other.add(&noisy), noisy.clone()]);
+    let env: Environment = [("x", 2.)].into_iter().collect();
+    let run = |program: &Program, output: usize| {
+        let mut context = program.context_with_seed(3);
+        (0..8)
+            .map(|_| program.run_in(&mut context, &env)[output])
+            .collect::<Vec<f32>>()
+    };
+    assert_eq!(run(&alone, 0), run(&crowded, 1));
+
+    let frozen = |expr: &DynScalar| {
+        let frozen = expr.freeze();
+        let mut context = frozen.context_with_seed(3);
+        (0..8)
+            .map(|_| frozen.execute_in(&mut context, &env))
+            .collect::<Vec<f32>>()
+    };
+    let mixed = other.mul(&Scalar::new(0.).into_dyn()).add(&noisy);
+    assert_eq!(frozen(&noisy), frozen(&mixed));
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use rust_lazy::operation::{parse, DynScalar, GraphContext, Scalar};
+
+#[test]
+fn equal_shapes_are_equal() {
+    assert_eq!(parse("(x + 2) * y").unwrap(), parse("(x + 2) * y").unwrap());
+    assert_ne!(parse("x + 2").unwrap(), parse("2 + x").unwrap());
+    assert_ne!(parse("x + 2").unwrap(), parse("x - 2").unwrap());
+    assert_ne!(parse("x + 2").unwrap(), parse("y + 2").unwrap());
+    assert_ne!(parse("x + 2").unwrap(), parse("x + 3").unwrap());
+
+    let x = Scalar::variable("x");
+    assert_eq!(&x * &x, &Scalar::variable("x") * &Scalar::variable("x"));
+    assert_eq!(x.lt(&Scalar::new(1.)).into_dyn(), parse("x < 1").unwrap());
+}
+
+#[test]
+fn constants_compare_by_bits() {
+    assert_eq!(Scalar::new(f32::NAN), Scalar::new(f32::NAN));
+    assert_ne!(Scalar::new(0.), Scalar::new(-0.));
+}
+
+#[test]
+fn unseeded_noise_only_equals_itself() {
+    let noise = Scalar::gaussian_noise(1., None);
+    assert_eq!(&noise + &Scalar::new(1.), &noise + &Scalar::new(1.));
+    assert_ne!(noise, Scalar::gaussian_noise(1., None));
+    assert_eq!(
+        Scalar::gaussian_noise(1., Some(4)),
+        Scalar::gaussian_noise(1., Some(4))
+    );
+}
+
+// Keys only change through aliases, and these have none.
+#[allow(clippy::mutable_key_type)]
+#[test]
+fn equal_expressions_hash_alike() {
+    let mut counts: HashMap<DynScalar, usize> = HashMap::new();
+    for source in ["x * y + 1", "x * y + 1", "x * y - 1", "(x * y) + 1"] {
+        *counts.entry(parse(source).unwrap()).or_default() += 1;
+    }
+    assert_eq!(counts[&parse("x * y + 1").unwrap()], 3);
+    assert_eq!(counts.len(), 2);
+}
+
+#[test]
+fn deep_graphs_compare() {
+    let build = || {
+        let mut res = Scalar::variable("x").into_dyn();
+        for _ in 0..100_000 {
+            res = &res + &Scalar::new(1.).into_dyn();
+        }
+        res
+    };
+    assert_eq!(build(), build());
+}
+
+#[test]
+fn context_interns_expressions() {
+    let context = GraphContext::new();
+    let first = context.intern(parse("x * 2").unwrap());
+    let second = context.intern(parse("x * 2").unwrap());
+    let sum = &first + &second;
+    assert_eq!(sum, parse("x * 2 + x * 2").unwrap());
+    // The sum, one product and its two leaves.
+    assert_eq!(sum.preorder().count(), 4);
+}
@@ -0,0 +1,61 @@
+use rust_lazy::operation::{parse, Environment, Scalar};
+
+fn env(bindings: &[(&str, f32)]) -> Environment {
+    bindings.iter().copied().collect()
+}
+
+#[test]
+fn gradient_matches_dual_numbers() {
+    let expr = parse("(x * y + 3) / (x - y * y)").unwrap();
+    let env = env(&[("x", 1.5), ("y", -0.5)]);
+    let tape = expr.execute_taped(&env);
+    assert_eq!(tape.value(), expr.execute_with(&env));
+    let gradient = tape.gradient();
+    for name in ["x", "y"] {
+        let dual = expr.execute_dual(&env, name).derivative;
+        assert!((gradient[name] - dual).abs() < 1e-5, "d/d{}", name);
+    }
+    assert_eq!(tape.derivative("z"), 0.);
+}
+
+#[test]
+fn selects_differentiate_the_path_taken() {
+    let x = Scalar::variable("x");
+    let zero = Scalar::new(0.);
+    let expr = x.lt(&zero).select(&(&zero - &x), &(&x * &x));
+    assert_eq!(expr.execute_taped(&env(&[("x", -2.)])).derivative("x"), -1.);
+    let tape = expr.execute_taped(&env(&[("x", 3.)]));
+    assert_eq!(tape.derivative("x"), 6.);
+    // The untaken arm is not on the tape.
+    assert!(!tape.to_string().contains(" - "));
+}
+
+#[test]
+fn shared_nodes_accumulate() {
+    let x = Scalar::variable("x");
+    let square = &x * &x;
+    let expr = &square * &square;
+    let tape = expr.execute_taped(&env(&[("x", 2.)]));
+    assert_eq!(tape.value(), 16.);
+    assert_eq!(tape.derivative("x"), 32.);
+}
+
+#[test]
+fn noise_is_differentiated_as_drawn() {
+    let x = Scalar::variable("x");
+    let expr = &x * &Scalar::gaussian_noise(1., None);
+    let tape = expr.execute_taped(&env(&[("x", 2.)]));
+    assert_eq!(tape.derivative("x"), tape.value() / 2.);
+}
+
+#[test]
+fn displays_the_wengert_list() {
+    let tape = parse("x * 2 + x")
+        .unwrap()
+        .execute_taped(&env(&[("x", 3.)]));
+    assert_eq!(
+        tape.to_string(),
+        "t0 = x = 3\nt1 = 2 = 2\nt2 = t0 * t1 = 6\nt3 = x = 3\nt4 = t2 + t3 = 9\n"
+    );
+    assert_eq!(tape.len(), 5);
+}
@@ -0,0 +1,71 @@
+#![cfg(all(feature = "jit", not(target_arch = "wasm32")))]
+
+use rust_lazy::operation::{
+    parse, Environment, ExecutionStats, JitError, Program, Scalar, Tier, TieredProgram,
+};
+
+#[test]
+fn promotes_after_the_threshold() {
+    let expr = parse("(a + 2.5) * b / (a - 1)").unwrap();
+    let mut tiered =
+        TieredProgram::new(Program::compile(std::slice::from_ref(&expr))).with_threshold(3);
+    for run in 1..=6 {
+        let env: Environment = [("a", run as f32), ("b", 0.5)].into_iter().collect();
+        assert_eq!(tiered.run_with(&env), [expr.execute_with(&env)]);
+        let expected = if run > 3 {
+            Tier::Native
+        } else {
+            Tier::Interpreted
+        };
+        assert_eq!(tiered.tier(), expected);
+    }
+    assert_eq!(tiered.runs(), 6);
+}
+
+#[test]
+fn promote_compiles_immediately() {
+    let mut tiered = TieredProgram::new(Program::compile(&[parse("1 / 8").unwrap()]));
+    assert_eq!(tiered.promote(), Tier::Native);
+    assert_eq!(tiered.run(), [0.125]);
+}
+
+#[test]
+fn unsupported_programs_stay_interpreted() {
+    let noisy = &Scalar::new(1.) + &Scalar::gaussian_noise(1., Some(3));
+    let mut tiered = TieredProgram::new(Program::compile(&[noisy.into_dyn()])).with_threshold(0);
+    tiered.run();
+    assert_eq!(tiered.tier(), Tier::Interpreted);
+    assert!(matches!(
+        tiered.promotion_error(),
+        Some(JitError::Unsupported(_))
+    ));
+
+    let pair = Program::compile(&[parse("x + 1").unwrap(), parse("x * 2").unwrap()]);
+    let mut tiered = TieredProgram::new(pair).with_threshold(0);
+    let env: Environment = [("x", 4.)].into_iter().collect();
+    assert_eq!(tiered.run_with(&env), [5., 8.]);
+    assert_eq!(tiered.tier(), Tier::Interpreted);
+}
+
+#[test]
+fn restored_statistics_promote_on_load() {
+    let expr = parse("a * a + 1").unwrap();
+    let mut stats = ExecutionStats::new();
+    let mut first =
+        TieredProgram::new(Program::compile(std::slice::from_ref(&expr))).with_threshold(2);
+    for _ in 0..3 {
+        first.run_with(&[("a", 2.)].into_iter().collect());
+    }
+    first.record(&mut stats);
+
+    let restarted = TieredProgram::new(Program::compile(std::slice::from_ref(&expr)))
+        .with_threshold(2)
+        .with_stats(&stats.to_string().parse().unwrap());
+    assert_eq!(restarted.runs(), 3);
+    assert_eq!(restarted.tier(), Tier::Native);
+
+    let unseen = TieredProgram::new(Program::compile(&[parse("a - 1").unwrap()]))
+        .with_threshold(2)
+        .with_stats(&stats);
+    assert_eq!(unseen.tier(), Tier::Interpreted);
+}
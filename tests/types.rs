@@ -0,0 +1,89 @@
+use rust_lazy::operation::{
+    parse, Comparison, CompileError, CompileOptions, Program, Scalar, Signature, Type, TypeError,
+};
+
+#[test]
+fn infers_types() {
+    let signature = Signature::new();
+    assert_eq!(
+        signature.check(&parse("x * 2 + 1").unwrap()),
+        Ok(Type::Float32)
+    );
+    assert_eq!(signature.check(&parse("x < 2").unwrap()), Ok(Type::Bool));
+    // Products of comparisons, as rules use them, are numbers.
+    assert_eq!(
+        signature.check(&parse("(x < 2) * (y > 1)").unwrap()),
+        Ok(Type::Float32)
+    );
+
+    let signature = Signature::new()
+        .with_variable("n", Type::Int)
+        .with_variable("flag", Type::Bool);
+    assert_eq!(signature.check(&parse("n * n").unwrap()), Ok(Type::Int));
+    assert_eq!(signature.check(&parse("n * flag").unwrap()), Ok(Type::Int));
+    assert_eq!(signature.variable("z"), Type::Float32);
+}
+
+#[test]
+fn reports_mismatches() {
+    let signature = Signature::new()
+        .with_variable("n", Type::Int)
+        .with_variable("d", Type::Float64)
+        .with_variable("v", Type::Vector(3))
+        .with_variable("w", Type::Vector(4));
+    let error = signature.check(&parse("n + d").unwrap()).unwrap_err();
+    assert_eq!(
+        error,
+        TypeError::Mismatch {
+            op: "+".to_string(),
+            left: Type::Int,
+            right: Type::Float64
+        }
+    );
+    assert_eq!(error.to_string(), "`+` cannot combine int and f64");
+    assert!(signature.check(&parse("v * w").unwrap()).is_err());
+    assert_eq!(
+        signature.check(&parse("v * v").unwrap()),
+        Ok(Type::Vector(3))
+    );
+    assert_eq!(
+        signature
+            .check(&parse("v < v").unwrap())
+            .unwrap_err()
+            .to_string(),
+        "`<` needs a scalar, found vector(3)"
+    );
+}
+
+#[test]
+fn checks_at_construction() {
+    let signature = Signature::new().with_variable("n", Type::Int);
+    let n = Scalar::variable("n").into_dyn();
+    let x = Scalar::variable("x").into_dyn();
+    assert!(signature.add(&n, &n).is_ok());
+    assert!(signature.mul(&n, &x).is_err());
+    let condition = signature.compare(Comparison::Lt, &x, &x).unwrap();
+    assert!(signature.select(&condition, &x, &x).is_ok());
+    assert!(signature.select(&condition, &n, &x).is_err());
+}
+
+#[test]
+fn compiling_rejects_unsupported_types() {
+    let expr = parse("n + 1").unwrap();
+    let options =
+        CompileOptions::new().with_signature(Signature::new().with_variable("n", Type::Int));
+    assert!(matches!(
+        Program::compile_with(std::slice::from_ref(&expr), &options),
+        Err(CompileError::Type(TypeError::Mismatch { .. }))
+    ));
+
+    let expr = parse("n * n").unwrap();
+    match Program::compile_with(std::slice::from_ref(&expr), &options) {
+        Err(error) => assert_eq!(error.to_string(), "f32 programs cannot hold int values"),
+        Ok(_) => panic!("compiled an int program"),
+    }
+
+    let options =
+        CompileOptions::new().with_signature(Signature::new().with_variable("n", Type::Bool));
+    assert!(Program::compile_with(&[expr], &options).is_ok());
+}
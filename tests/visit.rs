@@ -0,0 +1,93 @@
+use rust_lazy::operation::{parse, OpKind, Scalar, Visitor};
+
+// Renders prefix notation, which the crate has no exporter for.
+struct Lisp;
+
+impl Visitor for Lisp {
+    type Output = String;
+
+    fn visit_leaf(&mut self, kind: &OpKind) -> String {
+        kind.to_string()
+    }
+
+    fn visit_binary(&mut self, kind: &OpKind, a: String, b: String) -> String {
+        format!("({} {} {})", kind, a, b)
+    }
+
+    fn visit_select(&mut self, condition: String, then: String, otherwise: String) -> String {
+        format!("(if {} {} {})", condition, then, otherwise)
+    }
+}
+
+#[derive(Default)]
+struct Constants {
+    seen: Vec<f32>,
+    nodes: usize,
+}
+
+impl Visitor for Constants {
+    type Output = ();
+
+    fn visit_leaf(&mut self, _kind: &OpKind) {
+        self.nodes += 1;
+    }
+
+    fn visit_constant(&mut self, value: f32) {
+        self.nodes += 1;
+        self.seen.push(value);
+    }
+
+    fn visit_binary(&mut self, _kind: &OpKind, _a: (), _b: ()) {
+        self.nodes += 1;
+    }
+
+    fn visit_select(&mut self, _condition: (), _then: (), _otherwise: ()) {
+        self.nodes += 1;
+    }
+}
+
+#[test]
+fn folds_bottom_up() {
+    let expr = parse("(x < 1) * (x + 2.5) / y").unwrap();
+    assert_eq!(expr.accept(&mut Lisp), "(/ (* (< x 1) (+ x 2.5)) y)");
+
+    let x = Scalar::variable("x");
+    let picked = x.gt(&Scalar::new(0.)).select(&x, &Scalar::pi());
+    assert_eq!(picked.accept(&mut Lisp), "(if (> x 0) x π)");
+}
+
+#[test]
+fn visits_shared_nodes_once() {
+    let square = parse("x * 3").unwrap();
+    let expr = square
+        .add(&square)
+        .add(&Scalar::alias(square.clone()).into());
+    let mut constants = Constants::default();
+    expr.accept(&mut constants);
+    assert_eq!(constants.seen, [3.]);
+    // x, 3, the product and two sums; the alias is passed through.
+    assert_eq!(constants.nodes, 5);
+    assert_eq!(expr.accept(&mut Lisp), "(+ (+ (* x 3) (* x 3)) (* x 3))");
+}
+
+#[test]
+fn iterates_each_node_once() {
+    let x = Scalar::variable("x");
+    let square = (&x * &x).into_dyn();
+    let expr = square.sub(&parse("y").unwrap()).add(&square);
+    let kinds = |nodes: Vec<OpKind>| nodes.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds(expr.preorder().map(|node| node.kind()).collect()),
+        ["+", "-", "*", "x", "y"]
+    );
+    assert_eq!(
+        kinds(expr.postorder().map(|node| node.kind()).collect()),
+        ["x", "*", "y", "-", "+"]
+    );
+    assert_eq!(
+        expr.postorder()
+            .filter(|node| node.children().is_empty())
+            .count(),
+        2
+    );
+}
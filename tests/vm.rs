@@ -0,0 +1,84 @@
+use rust_lazy::{
+    operation::{Environment, Scalar},
+    vm::Vm,
+};
+
+#[test]
+fn arithmetic_matches_execute() {
+    let scalar1 = Scalar::new(1.);
+    let scalar2 = Scalar::new(2.);
+    let scalar3 = Scalar::new(3.);
+    let scalar4 = Scalar::new(4.);
+    let scalar5 = Scalar::new(5.);
+
+    let add = &scalar1 + &scalar2;
+    let sub = &scalar3 - &scalar4;
+    let mul = &scalar5 * &add;
+    let res = &(&add * &sub) + &(&add / &mul);
+
+    let expected = res.execute();
+    assert_eq!(Vm::new().run(&res.compile()), expected);
+}
+
+#[test]
+fn shared_subexpression_matches_execute() {
+    let x = Scalar::new(1.5);
+    let square = &x * &x;
+    let res = &square - &(&square / &Scalar::new(4.));
+
+    let expected = res.execute();
+    assert_eq!(Vm::new().run(&res.compile()), expected);
+}
+
+#[test]
+fn variables_are_loaded_from_environment() {
+    let x = Scalar::variable("x");
+    let y = Scalar::variable("y");
+    let res = &(&x * &y) + &(&x / &Scalar::new(2.));
+    let program = res.clone().compile();
+
+    let mut vm = Vm::new();
+    for (x, y) in [(1., 2.), (-3., 0.5), (10., 10.)] {
+        let env: Environment = [("x", x), ("y", y)].into_iter().collect();
+        assert_eq!(vm.run_with(&program, &env), res.execute_with(&env));
+    }
+}
+
+#[test]
+fn symbolic_constants_match_execute() {
+    let res = &Scalar::pi() * &Scalar::e();
+
+    let expected = res.execute();
+    assert_eq!(Vm::new().run(&res.compile()), expected);
+}
+
+#[test]
+fn seeded_noise_matches_execute() {
+    let res = &Scalar::new(1.) + &Scalar::gaussian_noise(0.5, Some(42));
+    let program = res.clone().compile();
+
+    let mut vm = Vm::new();
+    for _ in 0..4 {
+        assert_eq!(vm.run(&program), res.execute());
+    }
+}
+
+#[test]
+#[should_panic(expected = "unbound variable x")]
+fn unbound_variable_panics() {
+    let res = &Scalar::variable("x") + &Scalar::new(1.);
+    Vm::new().run(&res.compile());
+}
+
+#[test]
+fn dynamic_graph_matches_execute() {
+    let mut res = Scalar::new(0.).into_dyn();
+    for i in 1..=8 {
+        let term = &Scalar::variable("x") * &Scalar::new(i as f32);
+        res = &res + &term.into_dyn();
+    }
+    let program = res.clone().compile();
+
+    let env: Environment = [("x", 0.25)].into_iter().collect();
+    assert_eq!(Vm::new().run_with(&program, &env), res.execute_with(&env));
+}
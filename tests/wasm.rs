@@ -0,0 +1,83 @@
+use rust_lazy::operation::{parse, Environment, Program, Scalar, WasmModule};
+use wasmi::{Engine, Linker, Module, Store};
+
+fn instantiate(module: &WasmModule) -> (Store<()>, wasmi::Instance) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, module.bytes()).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::<()>::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    (store, instance)
+}
+
+#[test]
+fn matches_execute() {
+    let expr = parse("(a + 2.5) * b / (c - 1) - a * a").unwrap();
+    let module = WasmModule::compile(&expr.clone().compile()).unwrap();
+    assert_eq!(module.variables(), ["a", "b", "c"]);
+
+    let (mut store, instance) = instantiate(&module);
+    let eval = instance
+        .get_typed_func::<(f32, f32, f32), f32>(&store, "eval")
+        .unwrap();
+    for (a, b, c) in [(1.5, 4., 3.), (-0.25, 1e3, 7.5), (0., 1., 1.)] {
+        let env: Environment = [("a", a), ("b", b), ("c", c)].into_iter().collect();
+        let result = eval.call(&mut store, (a, b, c)).unwrap();
+        assert_eq!(result.to_bits(), expr.execute_with(&env).to_bits());
+    }
+}
+
+#[test]
+fn constant_programs_take_no_arguments() {
+    let mut res = Scalar::new(1.).into_dyn();
+    for i in 0..200 {
+        res = &res + &Scalar::new(i as f32).into_dyn();
+    }
+    // Unallocated, so the register locals need multi-byte indices.
+    let unallocated = WasmModule::compile(&res.clone().compile()).unwrap();
+    let program = Program::compile(&[res.clone()]);
+    let allocated = WasmModule::compile(program.instructions()).unwrap();
+
+    for module in [unallocated, allocated] {
+        let (mut store, instance) = instantiate(&module);
+        let eval = instance.get_typed_func::<(), f32>(&store, "eval").unwrap();
+        assert_eq!(eval.call(&mut store, ()).unwrap(), res.execute());
+    }
+}
+
+#[test]
+fn select_tests_against_zero() {
+    let flag = Scalar::variable("flag");
+    let x = Scalar::variable("x");
+    let module = WasmModule::compile(&flag.select(&x, &(&x + &x)).compile()).unwrap();
+    let (mut store, instance) = instantiate(&module);
+    let eval = instance
+        .get_typed_func::<(f32, f32), f32>(&store, "eval")
+        .unwrap();
+    for (flag, expected) in [(1., 3.), (0., 6.), (-0., 6.), (f32::NAN, 3.)] {
+        assert_eq!(eval.call(&mut store, (flag, 3.)).unwrap(), expected);
+    }
+}
+
+#[test]
+fn noise_is_unsupported() {
+    let expr = &Scalar::variable("x") * &Scalar::laplace_noise(1., None);
+    assert!(WasmModule::compile(&expr.compile()).is_err());
+}
+
+#[test]
+fn comparisons_yield_one_or_zero() {
+    let (a, b) = (Scalar::variable("a"), Scalar::variable("b"));
+    let expr = &a.lt(&b) + &(&a.ne(&b) * &Scalar::new(2.));
+    let module = WasmModule::compile(&expr.compile()).unwrap();
+    let (mut store, instance) = instantiate(&module);
+    let eval = instance
+        .get_typed_func::<(f32, f32), f32>(&store, "eval")
+        .unwrap();
+    for (x, y, expected) in [(1., 2., 3.), (2., 2., 0.), (3., 2., 2.), (f32::NAN, 2., 2.)] {
+        assert_eq!(eval.call(&mut store, (x, y)).unwrap(), expected);
+    }
+}